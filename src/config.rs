@@ -0,0 +1,170 @@
+use std::{env, fs, path::Path};
+
+/// The file read for options from the input directory, unless `--config` is
+/// given.
+pub const FILE_NAME: &str = "photo2html.toml";
+
+/// A value in the file, as written.
+enum Value {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Sets the options in the TOML file at `path`, which are named by their long
+/// flags, e.g. `per-page = 20`. They are set as their environment variables,
+/// those set already being kept, so that both the environment and the command
+/// line take precedence over the file.
+pub fn apply(path: &Path, command: &clap::Command) -> Result<(), String> {
+    let s = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let values = parse(&s).map_err(|e| format!("{}:{e}", path.display()))?;
+    for (key, value) in values {
+        let error = |e: &str| format!("{}: {key}: {e}", path.display());
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(&key))
+            .ok_or_else(|| error("unknown option"))?;
+        let var = arg
+            .get_env()
+            .ok_or_else(|| error("only set on the command line"))?;
+        let value = match value {
+            Value::One(value) => value,
+            Value::Many(values) => match arg.get_value_delimiter() {
+                Some(delimiter) => values.join(&delimiter.to_string()),
+                None if values.len() == 1 => values.into_iter().next().unwrap(),
+                None => return Err(error("takes one value")),
+            },
+        };
+        if env::var_os(var).is_none() {
+            env::set_var(var, value);
+        }
+    }
+    Ok(())
+}
+
+/// Parses a TOML document that only sets keys to strings, numbers, booleans,
+/// dates and arrays of them, without tables.
+fn parse(s: &str) -> Result<Vec<(String, Value)>, String> {
+    let mut values: Vec<(String, Value)> = Vec::new();
+    let mut rest = s;
+    let error = |rest: &str, e: &str| {
+        let line = s[..s.len() - rest.len()].matches('\n').count() + 1;
+        format!("{line}: {e}")
+    };
+    loop {
+        rest = skip_blank(rest);
+        if rest.is_empty() {
+            return Ok(values);
+        }
+        if rest.starts_with('[') {
+            return Err(error(rest, "tables aren't supported"));
+        }
+        let (key, after) = match rest.chars().next() {
+            Some('"' | '\'') => string(rest).ok_or_else(|| error(rest, "unclosed string"))?,
+            _ => {
+                let end = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+                    .unwrap_or(rest.len());
+                (rest[..end].to_owned(), &rest[end..])
+            }
+        };
+        if key.is_empty() {
+            return Err(error(rest, "expected a key"));
+        }
+        if values.iter().any(|(k, _)| *k == key) {
+            return Err(error(rest, &format!("{key} is set twice")));
+        }
+        let after = after
+            .trim_start_matches([' ', '\t'])
+            .strip_prefix('=')
+            .ok_or_else(|| error(after, "expected ="))?
+            .trim_start_matches([' ', '\t']);
+        let (value, after) = if let Some(mut items) = after.strip_prefix('[') {
+            let mut values = Vec::new();
+            loop {
+                items = skip_blank(items);
+                if let Some(after) = items.strip_prefix(']') {
+                    break (Value::Many(values), after);
+                }
+                let (value, after) = scalar(items).ok_or_else(|| error(items, "invalid value"))?;
+                values.push(value);
+                items = skip_blank(after);
+                match items.strip_prefix(',') {
+                    Some(after) => items = after,
+                    None if items.starts_with(']') => {}
+                    None => return Err(error(items, "expected , or ]")),
+                }
+            }
+        } else {
+            let (value, after) = scalar(after).ok_or_else(|| error(after, "invalid value"))?;
+            (Value::One(value), after)
+        };
+        // Only a comment may follow on the line.
+        let after = after.trim_start_matches([' ', '\t']);
+        if !(after.is_empty() || after.starts_with(['#', '\n', '\r'])) {
+            return Err(error(after, "expected the end of the line"));
+        }
+        values.push((key, value));
+        rest = after;
+    }
+}
+
+/// Skips whitespace, line breaks and comments.
+fn skip_blank(mut s: &str) -> &str {
+    loop {
+        s = s.trim_start();
+        match s.strip_prefix('#') {
+            Some(comment) => s = comment.find('\n').map_or("", |end| &comment[end..]),
+            None => return s,
+        }
+    }
+}
+
+/// Parses the string, number, boolean or date at the start of `s`, returning it
+/// as written and what follows.
+fn scalar(s: &str) -> Option<(String, &str)> {
+    if s.starts_with(['"', '\'']) {
+        return string(s);
+    }
+    let end = s
+        .find(|c: char| !(c.is_ascii_alphanumeric() || "+-.:_".contains(c)))
+        .unwrap_or(s.len());
+    let (literal, rest) = s.split_at(end);
+    (!literal.is_empty()).then(|| (literal.replace('_', ""), rest))
+}
+
+/// Parses the basic or literal string at the start of `s`, which starts with
+/// its quote.
+fn string(s: &str) -> Option<(String, &str)> {
+    if let Some(literal) = s.strip_prefix('\'') {
+        let end = literal.find(['\'', '\n'])?;
+        return literal[end..]
+            .starts_with('\'')
+            .then(|| (literal[..end].to_owned(), &literal[end + 1..]));
+    }
+    let mut string = String::new();
+    let mut chars = s.strip_prefix('"')?.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((string, &s[1 + i + 1..])),
+            '\n' => return None,
+            '\\' => {
+                let escaped = match chars.next()?.1 {
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    c @ ('u' | 'U') => {
+                        let len = if c == 'u' { 4 } else { 8 };
+                        let hex: String = chars.by_ref().take(len).map(|(_, c)| c).collect();
+                        char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+                    }
+                    c => c,
+                };
+                string.push(escaped);
+            }
+            c => string.push(c),
+        }
+    }
+    None
+}
@@ -0,0 +1,50 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+/// Why a gallery couldn't be generated.
+#[derive(Debug)]
+pub enum Error {
+    /// The options are invalid, with why, e.g. a flag unknown to the command
+    /// line or a template using a value that doesn't exist.
+    Options(String),
+    /// Reading or writing the file or directory failed.
+    Io(PathBuf, io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Options(e) => write!(f, "{e}"),
+            Error::Io(path, e) => write!(f, "{}: {e}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Options(_) => None,
+            Error::Io(_, e) => Some(e),
+        }
+    }
+}
+
+/// For the errors of a photo, which are reported when it is skipped.
+impl From<Error> for String {
+    fn from(e: Error) -> Self {
+        e.to_string()
+    }
+}
+
+/// Names the file an I/O error is about.
+pub(crate) trait At<T> {
+    fn at(self, path: impl AsRef<Path>) -> Result<T, Error>;
+}
+
+impl<T> At<T> for io::Result<T> {
+    fn at(self, path: impl AsRef<Path>) -> Result<T, Error> {
+        self.map_err(|e| Error::Io(path.as_ref().to_owned(), e))
+    }
+}
@@ -0,0 +1,49 @@
+use std::{collections::HashMap, path::Path, process::Command};
+
+use crate::json::{self, Value};
+
+/// The tags read with ExifTool, which are named as in its output.
+const TAGS: &[&str] = &[
+    "-DateTimeOriginal",
+    "-OffsetTimeOriginal",
+    "-CreateDate",
+    "-OffsetTimeDigitized",
+    // Signed, unlike the EXIF tags of the same name.
+    "-Composite:GPSLatitude",
+    "-Composite:GPSLongitude",
+    "-Rating",
+    "-Keywords",
+    "-Subject",
+];
+
+/// Whether `exiftool` can be run.
+pub fn is_installed() -> bool {
+    Command::new("exiftool")
+        .arg("-ver")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+/// Reads the tags of a file with `exiftool -j`, with numbers unformatted.
+/// Returns `None` if it fails, e.g. for a file it doesn't support.
+pub fn read(path: &Path) -> Option<HashMap<String, Value>> {
+    let output = Command::new("exiftool")
+        .args(["-j", "-n"])
+        .args(TAGS)
+        .arg("--")
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let json = String::from_utf8(output.stdout).ok()?;
+    // One object per file.
+    let Value::List(files) = json::parse(&json)? else {
+        return None;
+    };
+    match files.into_iter().next()? {
+        Value::Object(tags) => Some(tags),
+        _ => None,
+    }
+}
@@ -0,0 +1,169 @@
+use std::{fs, io, path::Path};
+
+use exif::{Exif, In, Tag, Value};
+
+/// Removes the GPS tags from the EXIF metadata of a JPEG or WebP file, keeping
+/// all other tags. Does nothing for other formats.
+pub fn strip(path: &Path) -> io::Result<()> {
+    let mut bytes = fs::read(path)?;
+    if tiff(&mut bytes).is_some_and(clear) {
+        fs::write(path, bytes)?;
+    }
+    Ok(())
+}
+
+/// Returns whether the file has GPS tags that [`strip`] would remove.
+pub fn has_location(path: &Path) -> bool {
+    let Ok(mut bytes) = fs::read(path) else {
+        return false;
+    };
+    tiff(&mut bytes).is_some_and(clear)
+}
+
+/// Returns the latitude and longitude in degrees, north and east being
+/// positive.
+pub fn coordinates(exif: &Exif) -> Option<(f64, f64)> {
+    let degrees = |tag, ref_tag, negative| {
+        let Value::Rational(dms) = &exif.get_field(tag, In::PRIMARY)?.value else {
+            return None;
+        };
+        if dms.len() != 3 {
+            return None;
+        }
+        let degrees: f64 = dms
+            .iter()
+            .zip([1.0, 60.0, 3600.0])
+            .map(|(r, per_degree)| r.to_f64() / per_degree)
+            .sum();
+        let Value::Ascii(reference) = &exif.get_field(ref_tag, In::PRIMARY)?.value else {
+            return None;
+        };
+        let sign = match reference.first()?.first() {
+            Some(r) if *r == negative => -1.0,
+            _ => 1.0,
+        };
+        Some(sign * degrees).filter(|d| d.is_finite())
+    };
+    Some((
+        degrees(Tag::GPSLatitude, Tag::GPSLatitudeRef, b'S')?,
+        degrees(Tag::GPSLongitude, Tag::GPSLongitudeRef, b'W')?,
+    ))
+}
+
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const GPS_INFO: u16 = 0x8825;
+
+/// Finds the TIFF structure holding the EXIF metadata.
+fn tiff(bytes: &mut [u8]) -> Option<&mut [u8]> {
+    let range = if bytes.starts_with(&[0xff, 0xd8]) {
+        jpeg_exif(bytes)?
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        webp_exif(bytes)?
+    } else {
+        return None;
+    };
+    let exif = &mut bytes[range];
+    Some(match exif.strip_prefix(EXIF_HEADER) {
+        Some(_) => &mut exif[EXIF_HEADER.len()..],
+        None => exif,
+    })
+}
+
+fn jpeg_exif(bytes: &[u8]) -> Option<std::ops::Range<usize>> {
+    let mut i = 2;
+    while i + 4 <= bytes.len() && bytes[i] == 0xff {
+        let marker = bytes[i + 1];
+        // The image data follows, without further metadata.
+        if marker == 0xd9 || marker == 0xda {
+            return None;
+        }
+        let len = u16::from_be_bytes([bytes[i + 2], bytes[i + 3]]) as usize;
+        let data = i + 4..(i + 2 + len).min(bytes.len());
+        if marker == 0xe1 && bytes[data.clone()].starts_with(EXIF_HEADER) {
+            return Some(data);
+        }
+        i += 2 + len;
+    }
+    None
+}
+
+fn webp_exif(bytes: &[u8]) -> Option<std::ops::Range<usize>> {
+    let mut i = 12;
+    while i + 8 <= bytes.len() {
+        let len = u32::from_le_bytes(bytes[i + 4..i + 8].try_into().unwrap()) as usize;
+        let data = i + 8..(i + 8 + len).min(bytes.len());
+        if &bytes[i..i + 4] == b"EXIF" {
+            return Some(data);
+        }
+        // Chunks are padded to an even length.
+        i += 8 + len + len % 2;
+    }
+    None
+}
+
+/// Zeroes the GPS IFD and the values it points to, leaving an empty IFD in its
+/// place. Returns whether there were any GPS tags.
+fn clear(tiff: &mut [u8]) -> bool {
+    let big_endian = match tiff.get(..2) {
+        Some(b"MM") => true,
+        Some(b"II") => false,
+        _ => return false,
+    };
+    let u16_at = |tiff: &[u8], i: usize| {
+        let b: [u8; 2] = tiff.get(i..i + 2)?.try_into().unwrap();
+        Some(if big_endian {
+            u16::from_be_bytes(b)
+        } else {
+            u16::from_le_bytes(b)
+        })
+    };
+    let u32_at = |tiff: &[u8], i: usize| {
+        let b: [u8; 4] = tiff.get(i..i + 4)?.try_into().unwrap();
+        Some(if big_endian {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        } as usize)
+    };
+    let Some(ifd0) = u32_at(tiff, 4) else {
+        return false;
+    };
+    let Some(count) = u16_at(tiff, ifd0) else {
+        return false;
+    };
+    let Some(gps) = (0..count as usize)
+        .map(|i| ifd0 + 2 + 12 * i)
+        .find(|&entry| u16_at(tiff, entry) == Some(GPS_INFO))
+        .and_then(|entry| u32_at(tiff, entry + 8))
+    else {
+        return false;
+    };
+    let Some(count) = u16_at(tiff, gps).map(usize::from) else {
+        return false;
+    };
+    if count == 0 {
+        return false;
+    }
+    for entry in (0..count).map(|i| gps + 2 + 12 * i) {
+        let (Some(kind), Some(n)) = (u16_at(tiff, entry + 2), u32_at(tiff, entry + 4)) else {
+            break;
+        };
+        let size = n.saturating_mul(match kind {
+            3 | 8 => 2,
+            4 | 9 | 11 => 4,
+            5 | 10 | 12 => 8,
+            _ => 1,
+        });
+        // Larger values are stored elsewhere, at the offset in the entry.
+        if size > 4 {
+            if let Some(offset) = u32_at(tiff, entry + 8) {
+                if let Some(value) = tiff.get_mut(offset..offset.saturating_add(size)) {
+                    value.fill(0);
+                }
+            }
+        }
+    }
+    let end = (gps + 2 + 12 * count + 4).min(tiff.len());
+    tiff[gps..end].fill(0);
+    true
+}
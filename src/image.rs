@@ -0,0 +1,692 @@
+use std::{
+    fmt::Debug,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::OnceLock,
+};
+
+use ::image::{
+    imageops::{self, FilterType},
+    DynamicImage, ImageDecoder as _, ImageReader, Rgba, RgbaImage,
+};
+use clap::ValueEnum;
+use jpeg_encoder::{ColorType, Encoder, SamplingFactor};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Backend {
+    /// Decode, resize and encode JPEG and PNG in the process, and the rest with
+    /// `--magick` if it is installed
+    Native,
+    /// Call ImageMagick or GraphicsMagick, see `--magick`
+    Magick,
+    /// Only create empty image files, e.g. to check the pages quickly
+    None,
+}
+
+impl Backend {
+    /// `magick` is the program of the magick backend, which the native one
+    /// falls back to if it runs, or always with `fallback`.
+    pub fn processor(self, magick: &Path, fallback: bool) -> Box<dyn ImageProcessor> {
+        let magick = Magick {
+            program: magick.to_owned(),
+        };
+        match self {
+            Backend::Native => Box::new(Native {
+                available: if fallback {
+                    OnceLock::from(true)
+                } else {
+                    OnceLock::new()
+                },
+                magick,
+            }),
+            Backend::Magick => Box::new(magick),
+            Backend::None => Box::new(Touch),
+        }
+    }
+
+    /// Describes the program the backend calls, or why it can't be run.
+    pub fn version(self, magick: &Path) -> Result<String, String> {
+        match self {
+            Backend::Magick => {
+                let output = Command::new(magick)
+                    .arg("-version")
+                    .output()
+                    .map_err(|e| format!("cannot run {}: {e}", magick.display()))?;
+                if !output.status.success() {
+                    return Err(format!(
+                        "{} -version failed: {}",
+                        magick.display(),
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ));
+                }
+                let version = String::from_utf8_lossy(&output.stdout);
+                let version = version.lines().next().unwrap_or_default();
+                Ok(version
+                    .strip_prefix("Version: ")
+                    .unwrap_or(version)
+                    .to_owned())
+            }
+            Backend::Native | Backend::None => Ok("built in".to_owned()),
+        }
+    }
+}
+
+/// How an image is turned into another.
+#[derive(Debug, Default)]
+pub struct Conversion {
+    /// Only convert the first frame, e.g. of a GIF.
+    pub first_frame: bool,
+    /// Remove the metadata.
+    pub strip: bool,
+    /// Except for the color profile.
+    pub keep_icc: bool,
+    /// Composite transparent images onto this color, e.g. `#222` or `black`.
+    pub background: Option<String>,
+    /// Degrees to turn the image by clockwise, which then has no orientation.
+    /// Otherwise it is turned as its orientation says.
+    pub rotate: Option<u32>,
+    pub quality: Option<u8>,
+    pub resize: Option<Resize>,
+    /// Used for resizing, otherwise ImageMagick's choice, or Lanczos natively.
+    pub filter: Option<Filter>,
+    /// Halve the resolution of the colors, as most JPEGs do.
+    pub subsample: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Resize {
+    /// Scale to this width.
+    Width(u32),
+    /// Scale to this height.
+    Height(u32),
+    /// Scale down to fit into a square of this size, if larger.
+    Shrink(u32),
+    /// Scale and crop the center to fill a square of this size.
+    Fill(u32),
+    /// Scale to fit into a square of this size, padded with transparency.
+    Pad(u32),
+}
+
+/// How the pixels are sampled when resizing, named as in ImageMagick. Mitchell
+/// is Gaussian natively, the closest the image crate has.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Filter {
+    /// Sharp, suited to downscaling photos
+    Lanczos,
+    /// Sharp, with less ringing at high-contrast edges than Lanczos
+    Catrom,
+    /// Smooth, with fewer artifacts
+    Mitchell,
+    /// Soft and fast
+    Triangle,
+    /// Blocky, keeping hard edges, e.g. of pixel art
+    Point,
+}
+
+/// Images tiled into one, with their labels below them.
+#[derive(Debug)]
+pub struct Montage<'a> {
+    pub title: Option<String>,
+    pub tiles: Vec<(Option<String>, &'a Path)>,
+    pub columns: u32,
+    /// Size of the square each image is fitted into.
+    pub size: u32,
+    /// Space around each tile.
+    pub spacing: u32,
+}
+
+/// Makes the images, so that everything else can run without ImageMagick.
+/// The errors name the file that failed and why.
+pub trait ImageProcessor: Debug + Sync {
+    fn convert(&self, input: &Path, output: &Path, conversion: &Conversion) -> Result<(), String>;
+
+    /// Returns the width and height of the first frame.
+    fn dimensions(&self, input: &Path) -> Result<(u32, u32), String>;
+
+    fn montage(&self, montage: &Montage, output: &Path) -> Result<(), String>;
+
+    /// Combines images into the pages of a PDF file, at this many dots per
+    /// inch.
+    fn pdf(&self, pages: &[PathBuf], density: u32, output: &Path) -> Result<(), String>;
+
+    /// Writes the first frame of a video as a JPEG with `ffmpeg`, turned by
+    /// the rotation of the video.
+    fn poster(&self, ffmpeg: &Path, video: &Path, output: &Path) -> Result<(), String> {
+        let mut command = Command::new(ffmpeg);
+        command
+            .args(["-v", "error", "-y", "-i"])
+            .arg(video)
+            .args(["-frames:v", "1", "-f", "image2"])
+            .arg(output);
+        Magick::run(command, video).map(drop)
+    }
+}
+
+/// The tools of ImageMagick and GraphicsMagick, which are called differently
+/// by version.
+#[derive(Clone, Copy, Debug)]
+enum Tool {
+    Convert,
+    Identify,
+    Montage,
+}
+
+#[derive(Debug)]
+pub struct Magick {
+    /// `magick` of ImageMagick 7, `convert` of ImageMagick 6, whose other
+    /// tools are next to it, or `gm` of GraphicsMagick.
+    program: PathBuf,
+}
+
+impl Magick {
+    fn command(&self, tool: Tool) -> Command {
+        let name = self.program.file_stem().and_then(|n| n.to_str());
+        let (program, subcommand) = match (name, tool) {
+            (Some("convert"), Tool::Identify) => (self.program.with_file_name("identify"), None),
+            (Some("convert"), Tool::Montage) => (self.program.with_file_name("montage"), None),
+            (Some("gm"), Tool::Convert) => (self.program.clone(), Some("convert")),
+            (_, Tool::Convert) => (self.program.clone(), None),
+            (_, Tool::Identify) => (self.program.clone(), Some("identify")),
+            (_, Tool::Montage) => (self.program.clone(), Some("montage")),
+        };
+        let mut command = Command::new(program);
+        command.args(subcommand);
+        command
+    }
+
+    /// Runs `command` on `file`, returning its output.
+    fn run(mut command: Command, file: &Path) -> Result<Vec<u8>, String> {
+        debug!("running {command:?}");
+        let output = command.output().map_err(|e| {
+            format!(
+                "cannot run {} on {}: {e}",
+                command.get_program().to_string_lossy(),
+                file.display()
+            )
+        })?;
+        if !output.status.success() {
+            return Err(format!(
+                "{} failed on {}: {}",
+                command.get_program().to_string_lossy(),
+                file.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(output.stdout)
+    }
+}
+
+impl ImageProcessor for Magick {
+    fn convert(&self, input: &Path, output: &Path, conversion: &Conversion) -> Result<(), String> {
+        let mut command = self.command(Tool::Convert);
+        if matches!(conversion.resize, Some(Resize::Pad(_))) {
+            command.arg("-background").arg("none");
+        }
+        if conversion.first_frame {
+            let mut frame = input.as_os_str().to_owned();
+            frame.push("[0]");
+            command.arg(frame);
+        } else {
+            command.arg(input.as_os_str());
+        }
+        if let Some(background) = &conversion.background {
+            command.arg("-background").arg(background).arg("-flatten");
+        }
+        // Before stripping, which would lose the orientation.
+        match conversion.rotate {
+            Some(degrees) => {
+                command.arg("-rotate").arg(degrees.to_string());
+                if !conversion.strip {
+                    command.arg("-orient").arg("TopLeft");
+                }
+            }
+            None => {
+                command.arg("-auto-orient");
+            }
+        }
+        if conversion.strip && conversion.keep_icc {
+            command.arg("+profile").arg("!icc,*");
+        } else if conversion.strip {
+            command.arg("-strip");
+        }
+        if let Some(quality) = conversion.quality {
+            command.arg("-quality").arg(format!("{quality}%"));
+        }
+        if let (Some(_), Some(filter)) = (conversion.resize, conversion.filter) {
+            command.arg("-filter").arg(format!("{filter:?}"));
+        }
+        match conversion.resize {
+            None => {}
+            Some(Resize::Width(width)) => {
+                command.arg("-resize").arg(width.to_string());
+            }
+            Some(Resize::Height(height)) => {
+                command.arg("-resize").arg(format!("x{height}"));
+            }
+            Some(Resize::Shrink(max)) => {
+                command.arg("-resize").arg(format!("{max}x{max}>"));
+            }
+            Some(Resize::Fill(size) | Resize::Pad(size)) => {
+                let size = format!("{size}x{size}");
+                let resize = match conversion.resize {
+                    Some(Resize::Fill(_)) => format!("{size}^"),
+                    _ => size.clone(),
+                };
+                command
+                    .arg("-resize")
+                    .arg(resize)
+                    .arg("-gravity")
+                    .arg("center")
+                    .arg("-extent")
+                    .arg(size);
+            }
+        }
+        if conversion.subsample {
+            command.arg("-sampling-factor").arg("4:2:0");
+        }
+        command.arg(output.as_os_str());
+        Self::run(command, input).map(drop)
+    }
+
+    fn dimensions(&self, input: &Path) -> Result<(u32, u32), String> {
+        let mut frame = input.as_os_str().to_owned();
+        frame.push("[0]");
+        let mut command = self.command(Tool::Identify);
+        command.arg("-format").arg("%w %h").arg(frame);
+        let output = String::from_utf8_lossy(&Self::run(command, input)?).into_owned();
+        output
+            .trim()
+            .split_once(' ')
+            .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+            .ok_or_else(|| format!("unexpected size of {}: {output}", input.display()))
+    }
+
+    fn montage(&self, montage: &Montage, output: &Path) -> Result<(), String> {
+        let mut command = self.command(Tool::Montage);
+        if let Some(title) = &montage.title {
+            command.arg("-title").arg(title);
+        }
+        for (label, path) in &montage.tiles {
+            if let Some(label) = label {
+                command.arg("-label").arg(label);
+            }
+            command.arg(path);
+        }
+        let Montage {
+            columns,
+            size,
+            spacing,
+            ..
+        } = montage;
+        command
+            .arg("-tile")
+            .arg(format!("{columns}x"))
+            .arg("-geometry")
+            .arg(format!("{size}x{size}+{spacing}+{spacing}"))
+            .arg(output.as_os_str());
+        Self::run(command, output).map(drop)
+    }
+
+    fn pdf(&self, pages: &[PathBuf], density: u32, output: &Path) -> Result<(), String> {
+        let mut command = self.command(Tool::Convert);
+        command
+            .args(pages)
+            .arg("-units")
+            .arg("PixelsPerInch")
+            .arg("-density")
+            .arg(density.to_string())
+            .arg(output.as_os_str());
+        Self::run(command, output).map(drop)
+    }
+}
+
+impl Filter {
+    fn native(self) -> FilterType {
+        match self {
+            Filter::Lanczos => FilterType::Lanczos3,
+            Filter::Catrom => FilterType::CatmullRom,
+            Filter::Mitchell => FilterType::Gaussian,
+            Filter::Triangle => FilterType::Triangle,
+            Filter::Point => FilterType::Nearest,
+        }
+    }
+}
+
+/// Why the native backend didn't make an image.
+enum Failure {
+    /// The image crate can't, but ImageMagick may.
+    Unsupported(String),
+    Failed(String),
+}
+
+/// Makes JPEGs, PNGs and ICOs from the formats the image crate decodes, and the rest
+/// with `magick` if it runs.
+#[derive(Debug)]
+pub struct Native {
+    magick: Magick,
+    /// Whether `magick` runs, found out when it is first needed.
+    available: OnceLock<bool>,
+}
+
+impl Native {
+    /// Returns the result of `fallback` with the magick backend if `result` is
+    /// unsupported.
+    fn or_fallback<T>(
+        &self,
+        result: Result<T, Failure>,
+        fallback: impl FnOnce(&Magick) -> Result<T, String>,
+    ) -> Result<T, String> {
+        match result {
+            Ok(value) => Ok(value),
+            Err(Failure::Unsupported(e)) => {
+                let available = *self
+                    .available
+                    .get_or_init(|| Backend::Magick.version(&self.magick.program).is_ok());
+                if available {
+                    fallback(&self.magick)
+                } else {
+                    Err(format!(
+                        "{e}, and {} cannot be run, see --magick",
+                        self.magick.program.display()
+                    ))
+                }
+            }
+            Err(Failure::Failed(e)) => Err(e),
+        }
+    }
+
+    fn convert(input: &Path, output: &Path, conversion: &Conversion) -> Result<(), Failure> {
+        let extension = output
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if !["jpg", "jpeg", "png", "ico"].contains(&extension.as_str()) {
+            return Err(Failure::Unsupported(format!(
+                "cannot write {} natively",
+                output.display()
+            )));
+        }
+        let failed =
+            |e: &dyn std::fmt::Display| Failure::Failed(format!("{}: {e}", input.display()));
+        let unsupported =
+            |e: &dyn std::fmt::Display| Failure::Unsupported(format!("{}: {e}", input.display()));
+        let mut decoder = ImageReader::open(input)
+            .and_then(ImageReader::with_guessed_format)
+            .map_err(|e| failed(&e))?
+            .into_decoder()
+            .map_err(|e| unsupported(&e))?;
+        let orientation = decoder.orientation().map_err(|e| failed(&e))?;
+        let icc = decoder.icc_profile().map_err(|e| failed(&e))?;
+        let exif = decoder.exif_metadata().map_err(|e| failed(&e))?;
+        // Only the first frame is decoded.
+        let mut image = DynamicImage::from_decoder(decoder).map_err(|e| failed(&e))?;
+
+        match conversion.rotate {
+            Some(90) => image = image.rotate90(),
+            Some(180) => image = image.rotate180(),
+            Some(270) => image = image.rotate270(),
+            Some(_) => {}
+            None => image.apply_orientation(orientation),
+        }
+        if let Some(background) = conversion
+            .background
+            .as_ref()
+            .filter(|_| image.color().has_alpha())
+        {
+            let color = parse_color(background).ok_or_else(|| {
+                Failure::Unsupported(format!("cannot read the color {background} natively"))
+            })?;
+            let mut flattened = RgbaImage::from_pixel(image.width(), image.height(), color);
+            imageops::overlay(&mut flattened, &image.to_rgba8(), 0, 0);
+            image = flattened.into();
+        }
+        let filter = conversion
+            .filter
+            .map_or(FilterType::Lanczos3, Filter::native);
+        match conversion.resize {
+            None => {}
+            Some(Resize::Width(width)) => image = image.resize(width, u32::MAX, filter),
+            Some(Resize::Height(height)) => image = image.resize(u32::MAX, height, filter),
+            Some(Resize::Shrink(max)) if image.width().max(image.height()) > max => {
+                image = image.resize(max, max, filter);
+            }
+            Some(Resize::Shrink(_)) => {}
+            Some(Resize::Fill(size)) => image = image.resize_to_fill(size, size, filter),
+            Some(Resize::Pad(size)) => {
+                let resized = image.resize(size, size, filter).to_rgba8();
+                let mut padded = RgbaImage::new(size, size);
+                let x = (size - resized.width()) / 2;
+                let y = (size - resized.height()) / 2;
+                imageops::overlay(&mut padded, &resized, x.into(), y.into());
+                image = padded.into();
+            }
+        }
+
+        if extension == "png" || extension == "ico" {
+            return image.save(output).map_err(|e| failed(&e));
+        }
+        let (Ok(width), Ok(height)) = (u16::try_from(image.width()), u16::try_from(image.height()))
+        else {
+            return Err(Failure::Unsupported(format!(
+                "{} is too large for a JPEG",
+                input.display()
+            )));
+        };
+        // ImageMagick's default for JPEG, when it can't be read from the image.
+        let mut encoder =
+            Encoder::new_file(output, conversion.quality.unwrap_or(92)).map_err(|e| failed(&e))?;
+        encoder.set_sampling_factor(if conversion.subsample {
+            SamplingFactor::R_4_2_0
+        } else {
+            SamplingFactor::R_4_4_4
+        });
+        if let Some(icc) = icc.filter(|_| !conversion.strip || conversion.keep_icc) {
+            encoder.add_icc_profile(&icc).map_err(|e| failed(&e))?;
+        }
+        if let Some(mut exif) = exif.filter(|_| !conversion.strip) {
+            // The pixels are turned already.
+            reset_orientation(&mut exif);
+            encoder.add_exif_metadata(&exif).map_err(|e| failed(&e))?;
+        }
+        let pixels = image.to_rgb8();
+        encoder
+            .encode(&pixels, width, height, ColorType::Rgb)
+            .map_err(|e| failed(&e))
+    }
+}
+
+impl ImageProcessor for Native {
+    fn convert(&self, input: &Path, output: &Path, conversion: &Conversion) -> Result<(), String> {
+        self.or_fallback(Self::convert(input, output, conversion), |magick| {
+            magick.convert(input, output, conversion)
+        })
+    }
+
+    fn dimensions(&self, input: &Path) -> Result<(u32, u32), String> {
+        let result = ::image::image_dimensions(input)
+            .map_err(|e| Failure::Unsupported(format!("{}: {e}", input.display())));
+        self.or_fallback(result, |magick| magick.dimensions(input))
+    }
+
+    fn montage(&self, montage: &Montage, output: &Path) -> Result<(), String> {
+        let unsupported =
+            Failure::Unsupported(format!("cannot montage {} natively", output.display()));
+        self.or_fallback(Err(unsupported), |magick| magick.montage(montage, output))
+    }
+
+    fn pdf(&self, pages: &[PathBuf], density: u32, output: &Path) -> Result<(), String> {
+        let unsupported =
+            Failure::Unsupported(format!("cannot write {} natively", output.display()));
+        self.or_fallback(Err(unsupported), |magick| {
+            magick.pdf(pages, density, output)
+        })
+    }
+}
+
+/// Draws the shapes of the placeholder icon, `ICON_SVG`, into a PNG of `size`
+/// pixels square, which the other icons can be made from without decoding
+/// SVG.
+pub fn draw_placeholder_icon(size: u32, output: &Path) -> Result<(), String> {
+    // In the units of the SVG's viewBox, 64 square.
+    const ROUNDING: f64 = 12.0;
+    const SUN: (f64, f64, f64) = (22.0, 22.0, 7.0);
+    const MOUNTAINS: [(f64, f64); 5] = [
+        (8.0, 52.0),
+        (26.0, 32.0),
+        (36.0, 43.0),
+        (44.0, 35.0),
+        (56.0, 52.0),
+    ];
+    let in_tile = |x: f64, y: f64| {
+        let dx = (ROUNDING - x).max(x - (64.0 - ROUNDING)).max(0.0);
+        let dy = (ROUNDING - y).max(y - (64.0 - ROUNDING)).max(0.0);
+        dx * dx + dy * dy <= ROUNDING * ROUNDING
+    };
+    let in_sun = |x: f64, y: f64| (x - SUN.0).powi(2) + (y - SUN.1).powi(2) <= SUN.2 * SUN.2;
+    // By the even-odd rule.
+    let in_mountains = |x: f64, y: f64| {
+        let mut inside = false;
+        for (i, &(x1, y1)) in MOUNTAINS.iter().enumerate() {
+            let (x2, y2) = MOUNTAINS[(i + 1) % MOUNTAINS.len()];
+            if (y1 > y) != (y2 > y) && x < x1 + (y - y1) * (x2 - x1) / (y2 - y1) {
+                inside = !inside;
+            }
+        }
+        inside
+    };
+    // Each pixel is the average of a grid of samples, which smooths the edges.
+    const SAMPLES: u32 = 4;
+    let scale = 64.0 / f64::from(size * SAMPLES);
+    let image = RgbaImage::from_fn(size, size, |px, py| {
+        let (mut tile, mut white) = (0, 0);
+        for sy in 0..SAMPLES {
+            for sx in 0..SAMPLES {
+                let x = (f64::from(px * SAMPLES + sx) + 0.5) * scale;
+                let y = (f64::from(py * SAMPLES + sy) + 0.5) * scale;
+                if in_tile(x, y) {
+                    tile += 1;
+                    if in_sun(x, y) || in_mountains(x, y) {
+                        white += 1;
+                    }
+                }
+            }
+        }
+        if tile == 0 {
+            return Rgba([0, 0, 0, 0]);
+        }
+        // Between #444 and #fff.
+        let value = (0x44 * (tile - white) + 0xff * white) / tile;
+        let alpha = 255 * tile / (SAMPLES * SAMPLES);
+        Rgba([value as u8, value as u8, value as u8, alpha as u8])
+    });
+    image
+        .save(output)
+        .map_err(|e| format!("{}: {e}", output.display()))
+}
+
+/// Reads a color of `--thumbnail-background` given as `#rgb`, `#rrggbb`, or
+/// black or white.
+fn parse_color(color: &str) -> Option<Rgba<u8>> {
+    let digits = match color {
+        "black" => "000000".to_owned(),
+        "white" => "ffffff".to_owned(),
+        _ => {
+            let hex = color.strip_prefix('#')?;
+            match hex.len() {
+                3 => hex.chars().flat_map(|c| [c, c]).collect(),
+                6 => hex.to_owned(),
+                _ => return None,
+            }
+        }
+    };
+    let channel = |i: usize| u8::from_str_radix(digits.get(i..i + 2)?, 16).ok();
+    Some(Rgba([channel(0)?, channel(2)?, channel(4)?, 255]))
+}
+
+/// Sets the orientation in EXIF data, a TIFF structure, to upright.
+fn reset_orientation(exif: &mut [u8]) {
+    let big_endian = match exif.get(..2) {
+        Some(b"MM") => true,
+        Some(b"II") => false,
+        _ => return,
+    };
+    let read = |exif: &[u8], at: usize, len: usize| -> Option<u32> {
+        let bytes = exif.get(at..at + len)?;
+        let mut value = 0;
+        for i in 0..len {
+            let byte = if big_endian {
+                bytes[i]
+            } else {
+                bytes[len - 1 - i]
+            };
+            value = value << 8 | u32::from(byte);
+        }
+        Some(value)
+    };
+    let Some(ifd) = read(exif, 4, 4).map(|ifd| ifd as usize) else {
+        return;
+    };
+    let count = read(exif, ifd, 2).unwrap_or(0) as usize;
+    for entry in (0..count).map(|i| ifd + 2 + i * 12) {
+        if read(exif, entry, 2) == Some(0x0112) {
+            // A SHORT, in the first two bytes of the value.
+            let upright = if big_endian { [0, 1] } else { [1, 0] };
+            if let Some(value) = exif.get_mut(entry + 8..entry + 10) {
+                value.copy_from_slice(&upright);
+            }
+            return;
+        }
+    }
+}
+
+/// Creates empty outputs, and takes every image to be 0x0.
+#[derive(Debug)]
+pub struct Touch;
+
+impl ImageProcessor for Touch {
+    fn convert(&self, _: &Path, output: &Path, _: &Conversion) -> Result<(), String> {
+        touch(output)
+    }
+
+    fn dimensions(&self, _: &Path) -> Result<(u32, u32), String> {
+        Ok((0, 0))
+    }
+
+    fn montage(&self, _: &Montage, output: &Path) -> Result<(), String> {
+        touch(output)
+    }
+
+    fn pdf(&self, _: &[PathBuf], _: u32, output: &Path) -> Result<(), String> {
+        touch(output)
+    }
+
+    fn poster(&self, _: &Path, _: &Path, output: &Path) -> Result<(), String> {
+        touch(output)
+    }
+}
+
+fn touch(output: &Path) -> Result<(), String> {
+    fs::write(output, b"").map_err(|e| format!("cannot write {}: {e}", output.display()))
+}
+
+/// Hashes how the image at `path` looks, as whether each pixel of it shrunk to
+/// 9x8 and gray is brighter than the next in its row, so that images differing
+/// in few bits look alike, e.g. re-exports and bursts.
+pub fn difference_hash(path: &Path) -> Result<u64, String> {
+    let image = ImageReader::open(path)
+        .and_then(ImageReader::with_guessed_format)
+        .map_err(|e| format!("{}: {e}", path.display()))?
+        .decode()
+        .map_err(|e| format!("{}: {e}", path.display()))?;
+    let gray = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            hash |= u64::from(gray.get_pixel(x, y)[0] > gray.get_pixel(x + 1, y)[0]);
+        }
+    }
+    Ok(hash)
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+/// A parsed JSON value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Number(f64),
+    List(Vec<Value>),
+    Object(HashMap<String, Value>),
+    /// Booleans and null.
+    Other,
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            Value::String(s) => s.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns the strings of a list, or the value as the only one.
+    pub fn strings(&self) -> Vec<String> {
+        match self {
+            Value::List(items) => items.iter().flat_map(Value::strings).collect(),
+            Value::String(s) => vec![s.clone()],
+            Value::Number(n) => vec![n.to_string()],
+            Value::Object(_) | Value::Other => Vec::new(),
+        }
+    }
+}
+
+/// Parses a JSON document.
+pub fn parse(json: &str) -> Option<Value> {
+    let (value, rest) = value(json)?;
+    rest.trim_start().is_empty().then_some(value)
+}
+
+/// Parses the value at the start of `s`, returning it and what follows.
+fn value(s: &str) -> Option<(Value, &str)> {
+    let s = s.trim_start();
+    match s.chars().next()? {
+        '"' => {
+            let (string, rest) = string(s)?;
+            Some((Value::String(string), rest))
+        }
+        '[' => {
+            let mut items = Vec::new();
+            let mut rest = s[1..].trim_start();
+            if let Some(rest) = rest.strip_prefix(']') {
+                return Some((Value::List(items), rest));
+            }
+            loop {
+                let (item, after) = value(rest)?;
+                items.push(item);
+                let after = after.trim_start();
+                match after.chars().next()? {
+                    ',' => rest = &after[1..],
+                    ']' => return Some((Value::List(items), &after[1..])),
+                    _ => return None,
+                }
+            }
+        }
+        '{' => {
+            let mut members = HashMap::new();
+            let mut rest = s[1..].trim_start();
+            if let Some(rest) = rest.strip_prefix('}') {
+                return Some((Value::Object(members), rest));
+            }
+            loop {
+                let (name, after) = string(rest.trim_start())?;
+                let after = after.trim_start().strip_prefix(':')?;
+                let (member, after) = value(after)?;
+                members.insert(name, member);
+                let after = after.trim_start();
+                match after.chars().next()? {
+                    ',' => rest = &after[1..],
+                    '}' => return Some((Value::Object(members), &after[1..])),
+                    _ => return None,
+                }
+            }
+        }
+        _ => {
+            let end = s
+                .find(|c: char| !(c.is_ascii_alphanumeric() || "+-.".contains(c)))
+                .unwrap_or(s.len());
+            let (literal, rest) = s.split_at(end);
+            let value = match literal {
+                "true" | "false" | "null" => Value::Other,
+                number => Value::Number(number.parse().ok()?),
+            };
+            Some((value, rest))
+        }
+    }
+}
+
+/// Parses the string at the start of `s`, which starts with its quote.
+fn string(s: &str) -> Option<(String, &str)> {
+    let mut string = String::new();
+    let mut chars = s.strip_prefix('"')?.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((string, &s[1 + i + 1..])),
+            '\\' => {
+                let escaped = match chars.next()?.1 {
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'u' => {
+                        let code = |chars: &mut std::str::CharIndices| {
+                            let hex: String = chars.take(4).map(|(_, c)| c).collect();
+                            u32::from_str_radix(&hex, 16).ok()
+                        };
+                        let high = code(&mut chars)?;
+                        let code = if (0xd800..0xdc00).contains(&high) {
+                            // The low surrogate follows as another escape.
+                            chars.next().filter(|(_, c)| *c == '\\')?;
+                            chars.next().filter(|(_, c)| *c == 'u')?;
+                            let low = code(&mut chars)?;
+                            0x10000 + ((high - 0xd800) << 10) + (low.checked_sub(0xdc00)?)
+                        } else {
+                            high
+                        };
+                        char::from_u32(code)?
+                    }
+                    c => c,
+                };
+                string.push(escaped);
+            }
+            c => string.push(c),
+        }
+    }
+    None
+}
@@ -0,0 +1,6835 @@
+//! Generates a static HTML gallery from directories of photos.
+//!
+//! The `photo2html` command is [`main`]. As a library, the options are parsed
+//! like its arguments, and the pages are written by a [`Renderer`]:
+//!
+//! ```no_run
+//! let options = photo2html::Options::parse_from(["photo2html", "photos/", "-o", "gallery/"])?;
+//! let photos = photo2html::generate(&options, &photo2html::Html)?;
+//! println!("{} photos", photos.len());
+//! # Ok::<(), photo2html::Error>(())
+//! ```
+
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    fs::{self, create_dir_all, File},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    iter,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+    sync::{mpsc, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Datelike, FixedOffset, Local, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta,
+    TimeZone,
+};
+use clap::{CommandFactory as _, Parser, ValueEnum};
+use exif::{Context, In, Tag, Value};
+use itertools::Itertools as _;
+use notify::{
+    event::{CreateKind, RemoveKind},
+    EventKind, RecursiveMode, Watcher as _,
+};
+use rayon::iter::{
+    IndexedParallelIterator as _, IntoParallelIterator as _, IntoParallelRefIterator as _,
+    ParallelIterator as _,
+};
+use sha2::{Digest, Sha256};
+
+// Declared first, so that its macros can be used in the other modules.
+#[macro_use]
+mod report;
+
+mod config;
+mod error;
+mod exiftool;
+mod gps;
+mod image;
+mod json;
+mod locale;
+mod mp4;
+mod png;
+mod preview;
+mod serve;
+mod template;
+mod xmp;
+
+use error::At as _;
+pub use error::Error;
+
+#[derive(Parser)]
+#[command(
+    disable_version_flag = true,
+    after_help = "Every option can also be set with the environment variable shown, or in photo2html.toml in the input directory by its long name, e.g. per-page = 20. The command line takes precedence over the environment, which takes precedence over the file."
+)]
+struct Args {
+    /// Photos, and directories containing photos [default: ./]
+    #[arg(env = "PHOTO2HTML_INPUT")]
+    inputs: Vec<String>,
+
+    /// Directory to write the gallery to [default: ../web relative to the input]
+    #[arg(short, long, env = "PHOTO2HTML_OUTPUT_DIR")]
+    output_dir: Option<String>,
+
+    /// Keep running and regenerate when the input directory changes
+    #[arg(short, long, env = "PHOTO2HTML_WATCH")]
+    watch: bool,
+
+    /// Serve the output directory at this address, 127.0.0.1:8000 by default,
+    /// and reload pages when they are regenerated. Implies --watch
+    #[arg(long, num_args = 0..=1, default_missing_value = "127.0.0.1:8000", conflicts_with = "clean", env = "PHOTO2HTML_SERVE")]
+    serve: Option<String>,
+
+    /// Run this command with the shell in the output directory after each
+    /// generation, e.g. `rsync -a --delete ./ host:www/`, giving it the paths
+    /// of the files changed since it last succeeded on its standard input
+    #[arg(long, conflicts_with_all = ["clean", "check"], env = "PHOTO2HTML_DEPLOY")]
+    deploy: Option<String>,
+
+    /// Remove everything generated in the output directory instead of generating
+    #[arg(long, conflicts_with = "watch", env = "PHOTO2HTML_CLEAN")]
+    clean: bool,
+
+    /// Check that the gallery can be generated, without writing anything, and
+    /// exit with an error if it can't, e.g. in CI. Lists the stale outputs that
+    /// would be removed
+    #[arg(long, conflicts_with_all = ["watch", "clean"], env = "PHOTO2HTML_CHECK")]
+    check: bool,
+
+    /// Exit without writing any page if there are no photos to show, instead
+    /// of generating an empty gallery, e.g. in automation
+    #[arg(long, conflicts_with = "watch", env = "PHOTO2HTML_NOOP_ON_EMPTY")]
+    noop_on_empty: bool,
+
+    /// Title of the generated pages
+    #[arg(long, default_value = "Photos", env = "PHOTO2HTML_TITLE")]
+    title: String,
+
+    /// Color scheme of the generated stylesheet
+    #[arg(long, value_enum, default_value_t = Theme::Light, env = "PHOTO2HTML_THEME")]
+    theme: Theme,
+
+    /// Value of the theme-color meta tag and the web app manifest [default: the
+    /// background color of the theme]
+    #[arg(long, env = "PHOTO2HTML_THEME_COLOR")]
+    theme_color: Option<String>,
+
+    /// Generate a site.webmanifest and icons into the output directory
+    #[arg(long, env = "PHOTO2HTML_PWA")]
+    pwa: bool,
+
+    /// Image to generate the icons from, a placeholder is used if omitted
+    #[arg(long, requires = "pwa", env = "PHOTO2HTML_ICON")]
+    icon: Option<String>,
+
+    /// strftime-style format of the day headings and navigation labels
+    #[arg(long, default_value = "%Y-%m-%d", value_parser = parse_date_format, env = "PHOTO2HTML_DATE_FORMAT")]
+    date_format: String,
+
+    /// Language of the month and day names and other texts, e.g. de-DE
+    #[arg(long, env = "PHOTO2HTML_LOCALE")]
+    locale: Option<String>,
+
+    /// Open the generated gallery in the default browser
+    #[arg(long, env = "PHOTO2HTML_OPEN")]
+    open: bool,
+
+    /// Maximum number of columns of the grid, fewer are used on narrow screens
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..), env = "PHOTO2HTML_COLUMNS")]
+    columns: u32,
+
+    /// How the thumbnails are arranged
+    #[arg(long, value_enum, default_value_t = Layout::Masonry, env = "PHOTO2HTML_LAYOUT")]
+    layout: Layout,
+
+    /// Height of the rows of --layout justified in pixels, which each row is
+    /// scaled from to fill the width of the page
+    #[arg(long, default_value_t = 240, value_parser = clap::value_parser!(u32).range(1..), env = "PHOTO2HTML_ROW_HEIGHT")]
+    row_height: u32,
+
+    /// Defaults for the image options below, explicit options take precedence
+    #[arg(long, value_enum, env = "PHOTO2HTML_PRESET")]
+    preset: Option<Preset>,
+
+    /// Format of the generated images [default: jpeg]
+    #[arg(long, value_enum, env = "PHOTO2HTML_FORMAT")]
+    format: Option<Format>,
+
+    /// Format of the thumbnails, instead of --format
+    #[arg(long, value_enum, env = "PHOTO2HTML_THUMBNAIL_FORMAT")]
+    thumbnail_format: Option<Format>,
+
+    /// Format of the full images, instead of --format
+    #[arg(long, value_enum, env = "PHOTO2HTML_IMAGE_FORMAT")]
+    image_format: Option<Format>,
+
+    /// Also make the thumbnails in these formats, which browsers that show
+    /// them pick in this order over those of --thumbnail-format
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        env = "PHOTO2HTML_SOURCE_FORMATS"
+    )]
+    source_formats: Vec<Format>,
+
+    /// Color that transparent images are put onto in thumbnails without
+    /// transparency, e.g. `#222` [default: white]
+    #[arg(long, env = "PHOTO2HTML_THUMBNAIL_BACKGROUND")]
+    thumbnail_background: Option<String>,
+
+    /// Quality of the thumbnails [default: 65]
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=100), env = "PHOTO2HTML_THUMBNAIL_QUALITY")]
+    thumbnail_quality: Option<u8>,
+
+    /// Width of the thumbnails in pixels, and their height with --thumbnail-fit
+    /// cover
+    #[arg(long, default_value_t = 512, value_parser = clap::value_parser!(u32).range(1..), env = "PHOTO2HTML_THUMBNAIL_SIZE")]
+    thumbnail_size: u32,
+
+    /// Also make thumbnails of these widths, for browsers to choose from by
+    /// the size and density of the screen. Only --thumbnail-size for none
+    #[arg(long, value_delimiter = ',', default_values_t = [256, 1024], value_parser = clap::value_parser!(u32).range(1..), env = "PHOTO2HTML_THUMBNAIL_WIDTHS")]
+    thumbnail_widths: Vec<u32>,
+
+    /// How images are resampled when resized
+    #[arg(long, value_enum, default_value_t = image::Filter::Lanczos, env = "PHOTO2HTML_RESIZE_FILTER")]
+    resize_filter: image::Filter,
+
+    /// Quality of the full images [default: chosen by ImageMagick]
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=100), env = "PHOTO2HTML_IMAGE_QUALITY")]
+    image_quality: Option<u8>,
+
+    /// Encode larger full images at a lower quality, from --max-quality at 1
+    /// megapixel down to --min-quality at 24 megapixels
+    #[arg(
+        long,
+        conflicts_with = "image_quality",
+        env = "PHOTO2HTML_QUALITY_BY_SIZE"
+    )]
+    quality_by_size: bool,
+
+    /// Quality of the largest full images with --quality-by-size
+    #[arg(long, default_value_t = 70, requires = "quality_by_size", value_parser = clap::value_parser!(u8).range(1..=100), env = "PHOTO2HTML_MIN_QUALITY")]
+    min_quality: u8,
+
+    /// Quality of the smallest full images with --quality-by-size
+    #[arg(long, default_value_t = 90, requires = "quality_by_size", value_parser = clap::value_parser!(u8).range(1..=100), env = "PHOTO2HTML_MAX_QUALITY")]
+    max_quality: u8,
+
+    /// Downscale full images so neither side exceeds this many pixels
+    #[arg(long, env = "PHOTO2HTML_MAX_DIMENSION")]
+    max_dimension: Option<u32>,
+
+    /// Keep full images at their full resolution, e.g. against --preset web
+    #[arg(
+        long,
+        conflicts_with = "max_dimension",
+        env = "PHOTO2HTML_NO_MAX_DIMENSION"
+    )]
+    no_max_dimension: bool,
+
+    /// Re-encode full images larger than this at a lower quality until they
+    /// fit, e.g. 500K or 2M. Originals that are copied are left as they are
+    #[arg(long, value_parser = parse_size, env = "PHOTO2HTML_MAX_BYTES")]
+    max_bytes: Option<u64>,
+
+    /// Keep the metadata of the full images instead of stripping it
+    #[arg(long, overrides_with = "no_keep_exif", env = "PHOTO2HTML_KEEP_EXIF")]
+    keep_exif: bool,
+
+    /// Strip the metadata of the full images, e.g. against --preset print
+    #[arg(long, overrides_with = "keep_exif", env = "PHOTO2HTML_NO_KEEP_EXIF")]
+    no_keep_exif: bool,
+
+    /// Keep the color profile of all images when stripping their metadata
+    #[arg(long, env = "PHOTO2HTML_KEEP_ICC")]
+    keep_icc: bool,
+
+    /// Stylesheet to use instead of the generated one
+    #[arg(long, env = "PHOTO2HTML_CSS")]
+    css: Option<String>,
+
+    /// Directory of templates to use instead of the built-in ones [default:
+    /// templates in the input directory, if it exists]. page.html may use {{lang}}, {{title}}, {{root}}, {{head}}, {{main}}, {{nav}} and
+    /// {{script}}, figure.html {{id}}, {{class}}, {{href}}, {{lightbox}},
+    /// {{img}}, {{src}}, {{label}}, {{exif}} and {{caption}}, and nav.html
+    /// {{items}}
+    #[arg(long, env = "PHOTO2HTML_TEMPLATES")]
+    templates: Option<PathBuf>,
+
+    /// Read the options from this TOML file instead of photo2html.toml in the
+    /// input directory
+    #[arg(long, env = "PHOTO2HTML_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Generate a page with the photos taken on today's date in earlier years
+    #[arg(long, env = "PHOTO2HTML_ON_THIS_DAY")]
+    on_this_day: bool,
+
+    /// URL the gallery is published at, used for canonical links
+    #[arg(long, env = "PHOTO2HTML_BASE_URL")]
+    base_url: Option<String>,
+
+    /// Montage the thumbnails of each page into page_N-sheet.jpg, used as its
+    /// preview image
+    #[arg(long, env = "PHOTO2HTML_CONTACT_SHEET")]
+    contact_sheet: bool,
+
+    /// Order of the photos, filenames need no dates and put all photos on one
+    /// page
+    #[arg(long, value_enum, default_value_t = Sort::Datetime, env = "PHOTO2HTML_SORT")]
+    sort: Sort,
+
+    /// What the photos are grouped by, each group having its own heading
+    #[arg(long, value_enum, default_value_t = GroupBy::Day, env = "PHOTO2HTML_GROUP_BY")]
+    group_by: GroupBy,
+
+    /// Where the photos without a date are grouped
+    #[arg(long, value_enum, default_value_t = EmptyDate::Mtime, env = "PHOTO2HTML_GROUP_EMPTY_AS")]
+    group_empty_as: EmptyDate,
+
+    /// Whether the group of --group-empty-as unknown is shown first or last
+    #[arg(long, value_enum, default_value_t = Position::Bottom, env = "PHOTO2HTML_UNDATED_POSITION")]
+    undated_position: Position,
+
+    /// Also write the whole gallery into this file, with the thumbnails embedded
+    #[arg(long, env = "PHOTO2HTML_SINGLE_FILE")]
+    single_file: Option<String>,
+
+    /// Embed the full images into the --single-file document as well
+    #[arg(long, requires = "single_file", env = "PHOTO2HTML_INLINE_IMAGES")]
+    inline_images: bool,
+
+    /// Generate the images of the photos matching this glob even if they are
+    /// up to date, e.g. `*.jpg` or `2023/*`
+    #[arg(long, value_parser = parse_glob, env = "PHOTO2HTML_REGENERATE")]
+    regenerate: Option<glob::Pattern>,
+
+    /// Print all photos as one page to stdout instead of writing the pages,
+    /// the images are still written to --output-dir
+    #[arg(
+        long,
+        requires = "output_dir",
+        conflicts_with_all = ["watch", "permalinks", "on_this_day", "map", "contact_sheet", "single_file", "archive", "pdf"],
+        env = "PHOTO2HTML_STDOUT"
+    )]
+    stdout: bool,
+
+    /// Also bundle the whole gallery into this zip file
+    #[arg(long, env = "PHOTO2HTML_ARCHIVE")]
+    archive: Option<String>,
+
+    /// Put a hash of the content into the names of the images, so that they
+    /// can be cached forever, and list the names in assets.json
+    #[arg(long, env = "PHOTO2HTML_HASHED_ASSETS")]
+    hashed_assets: bool,
+
+    /// Generate a table of contents listing every day, linked from the nav
+    #[arg(long, env = "PHOTO2HTML_TOC")]
+    toc: bool,
+
+    /// Generate map.html, placing each geotagged photo on a map, linked from
+    /// the nav. This publishes the locations despite --strip-gps
+    #[arg(long, env = "PHOTO2HTML_MAP")]
+    map: bool,
+
+    /// Also write opds.xml, an OPDS catalog with an entry per day linking its
+    /// images, for photo and e-reader apps
+    #[arg(long, env = "PHOTO2HTML_OPDS")]
+    opds: bool,
+
+    /// Which photo of each album is its cover on albums.html, and of each day in
+    /// the --opds catalog
+    #[arg(long, value_enum, default_value_t = AlbumCover::Pinned, env = "PHOTO2HTML_ALBUM_COVER")]
+    album_cover: AlbumCover,
+
+    /// Also write atom.xml, an Atom feed of the newest this many photos that
+    /// links each to where it is on the pages
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), env = "PHOTO2HTML_FEED")]
+    feed: Option<u32>,
+
+    /// Also write gallery.json, describing every photo, its images and its
+    /// page, for other frontends
+    #[arg(long, env = "PHOTO2HTML_JSON")]
+    json: bool,
+
+    /// Write gallery.json instead of the pages, see --json
+    #[arg(long, env = "PHOTO2HTML_JSON_ONLY")]
+    json_only: bool,
+
+    /// URL template of the tiles of the --map, in Leaflet's syntax
+    #[arg(
+        long,
+        default_value = "https://tile.openstreetmap.org/{z}/{x}/{y}.png",
+        env = "PHOTO2HTML_MAP_TILES"
+    )]
+    map_tiles: String,
+
+    /// HTML crediting the tiles of the --map, as their provider requires
+    #[arg(
+        long,
+        default_value = "&copy; <a href=\"https://www.openstreetmap.org/copyright\">OpenStreetMap</a> contributors",
+        env = "PHOTO2HTML_MAP_ATTRIBUTION"
+    )]
+    map_attribution: String,
+
+    /// Only link this many of the newest pages from the nav, and the others
+    /// from an archive page
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), env = "PHOTO2HTML_MAX_PAGES")]
+    max_pages: Option<u32>,
+
+    /// How the photos are split into pages: by --per-page, or into a page of
+    /// each month or year, whose links stay the same as photos are added
+    #[arg(long, value_enum, default_value_t = PaginateBy::Count, env = "PHOTO2HTML_PAGINATE_BY")]
+    paginate_by: PaginateBy,
+
+    /// About how many photos are put on a page. Days are not split across
+    /// pages, so one with more has its own page
+    #[arg(long, default_value_t = 50, value_parser = clap::value_parser!(u32).range(1..), env = "PHOTO2HTML_PER_PAGE")]
+    per_page: u32,
+
+    /// Split the days with more photos than fit on a page across pages,
+    /// repeating their heading, instead of giving each its own long page. Only
+    /// with --paginate-by count
+    #[arg(long, env = "PHOTO2HTML_SPLIT_LARGE_DAYS")]
+    split_large_days: bool,
+
+    /// Only include the photos with faces marked in their XMP metadata, e.g. by
+    /// digiKam
+    #[arg(long, env = "PHOTO2HTML_ONLY_WITH_PEOPLE")]
+    only_with_people: bool,
+
+    /// Add the names of the people marked in a photo to its caption
+    #[arg(long, env = "PHOTO2HTML_PEOPLE_IN_CAPTION")]
+    people_in_caption: bool,
+
+    /// Show the days with more photos than this as a strip of small thumbnails,
+    /// which can be expanded to the full grid
+    #[arg(long, env = "PHOTO2HTML_DENSE_DAYS")]
+    dense_days: Option<usize>,
+
+    /// Show the photos taken within this many seconds of each other as one, which
+    /// can be expanded to the others
+    #[arg(long, env = "PHOTO2HTML_STACK_BURSTS")]
+    stack_bursts: Option<u32>,
+
+    /// Skip the files with the same contents as another, keeping the first by
+    /// path, e.g. re-exports of a photo
+    #[arg(long, env = "PHOTO2HTML_SKIP_DUPLICATES")]
+    skip_duplicates: bool,
+
+    /// Skip the photos whose thumbnails look like that of another, keeping the
+    /// first by path, if their hashes differ in at most this many of 64 bits,
+    /// e.g. 5
+    #[arg(long, value_parser = clap::value_parser!(u32).range(0..=64), env = "PHOTO2HTML_SKIP_SIMILAR")]
+    skip_similar: Option<u32>,
+
+    /// Preload the first this many thumbnails of each page, and load the
+    /// others lazily when scrolled to
+    #[arg(long, env = "PHOTO2HTML_PRELOAD_THUMBNAILS")]
+    preload_thumbnails: Option<usize>,
+
+    /// Make the images from the JPEG preview embedded in the photo if it is
+    /// large enough, as they always are for raw files
+    #[arg(long, env = "PHOTO2HTML_PREFER_EMBEDDED_PREVIEW")]
+    prefer_embedded_preview: bool,
+
+    /// Only include the photos taken on or after this day, e.g. 2023-08-15
+    #[arg(long, value_parser = parse_day, env = "PHOTO2HTML_SINCE")]
+    since: Option<NaiveDate>,
+
+    /// Only include the photos taken on or before this day
+    #[arg(long, value_parser = parse_day, env = "PHOTO2HTML_UNTIL")]
+    until: Option<NaiveDate>,
+
+    /// Only include the photos taken today
+    #[arg(long, env = "PHOTO2HTML_TODAY")]
+    today: bool,
+
+    /// Leave out the photos taken on this day, can be repeated
+    #[arg(long, value_parser = parse_day, value_delimiter = ',', env = "PHOTO2HTML_EXCLUDE_DATE")]
+    exclude_date: Vec<NaiveDate>,
+
+    /// Leave out the photos taken in these days, given as START..END with both
+    /// included, can be repeated
+    #[arg(long, value_parser = parse_range, value_delimiter = ',', env = "PHOTO2HTML_EXCLUDE_RANGE")]
+    exclude_range: Vec<RangeInclusive<NaiveDate>>,
+
+    /// Only include the photos taken in this span before now, e.g. 24h, 7d, 2w,
+    /// 3mo or 1y
+    #[arg(long, value_parser = parse_span, env = "PHOTO2HTML_LAST")]
+    last: Option<Span>,
+
+    /// The offset from UTC of capture times that don't have one, e.g. +09:00
+    /// or UTC, instead of the local one at that time
+    #[arg(long, value_parser = parse_offset, env = "PHOTO2HTML_TIMEZONE")]
+    timezone: Option<FixedOffset>,
+
+    /// What makes the images
+    #[arg(long, value_enum, default_value_t = image::Backend::Native, env = "PHOTO2HTML_IMAGE_BACKEND")]
+    image_backend: image::Backend,
+
+    /// Require --magick for the images the native backend can't make, e.g. of
+    /// HEIC photos, in WebP or with montages, instead of only using it if it runs
+    #[arg(long, env = "PHOTO2HTML_MAGICK_FALLBACK")]
+    magick_fallback: bool,
+
+    /// The program of the magick backend: magick of ImageMagick 7, convert of
+    /// ImageMagick 6, which is found with its other tools, or gm of
+    /// GraphicsMagick. A name is searched for in the PATH
+    #[arg(long, default_value = "magick", env = "PHOTO2HTML_MAGICK")]
+    magick: PathBuf,
+
+    /// The program that takes the first frames of videos as their posters. A
+    /// name is searched for in the PATH
+    #[arg(long, default_value = "ffmpeg", env = "PHOTO2HTML_FFMPEG")]
+    ffmpeg: PathBuf,
+
+    /// How many photos are processed at once [default: the number of CPUs]
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..), env = "PHOTO2HTML_JOBS")]
+    jobs: Option<u32>,
+
+    /// How messages are written to stderr
+    #[arg(long, value_enum, default_value_t = report::LogFormat::Human, env = "PHOTO2HTML_LOG_FORMAT")]
+    log_format: report::LogFormat,
+
+    /// Report more of what is done, and with -vv the state it is done with
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Only report warnings and errors
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Print the version and which image backends can be used, instead of
+    /// generating
+    #[arg(short = 'V', long)]
+    version: bool,
+
+    /// Also read the files in the input directories whose names start with a dot
+    #[arg(long, env = "PHOTO2HTML_INCLUDE_HIDDEN")]
+    include_hidden: bool,
+
+    /// Also read the photos in the subdirectories of the input directories,
+    /// except for the output directory
+    #[arg(short, long, env = "PHOTO2HTML_RECURSIVE")]
+    recursive: bool,
+
+    /// How many levels of subdirectories --recursive reads [default: all]
+    #[arg(long, env = "PHOTO2HTML_DEPTH")]
+    depth: Option<u32>,
+
+    /// Give the photos of each subdirectory of the input directories a page,
+    /// linked with a cover from albums.html. Implies --recursive
+    #[arg(long, env = "PHOTO2HTML_ALBUMS")]
+    albums: bool,
+
+    /// Give each keyword of the photos a page, linked from tags.html and
+    /// under the photos with it
+    #[arg(long, env = "PHOTO2HTML_TAGS")]
+    tags: bool,
+
+    /// Copy the files in this directory into the output directory, keeping
+    /// their paths, e.g. for a favicon.ico or CNAME
+    #[arg(long, env = "PHOTO2HTML_COPY_ASSETS")]
+    copy_assets: Option<String>,
+
+    /// Also write a printable contact sheet of the thumbnails into this PDF
+    /// file, each day starting a new page
+    #[arg(long, env = "PHOTO2HTML_PDF")]
+    pdf: Option<String>,
+
+    /// Skip images whose width or height is below this many pixels
+    #[arg(long, env = "PHOTO2HTML_MIN_DIMENSION")]
+    min_dimension: Option<u32>,
+
+    /// How thumbnails are fitted into their square, see --thumbnail-size
+    #[arg(long, value_enum, default_value_t = Fit::Contain, env = "PHOTO2HTML_THUMBNAIL_FIT")]
+    thumbnail_fit: Fit,
+
+    /// Also publish the original files in original/, linked from each photo
+    #[arg(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        default_missing_value = "symlink",
+        env = "PHOTO2HTML_ORIGINALS"
+    )]
+    originals: Option<Originals>,
+
+    /// Give each photo its own page under photo/, linked from the thumbnails
+    #[arg(long, env = "PHOTO2HTML_PERMALINKS")]
+    permalinks: bool,
+
+    /// Show all EXIF tags of each photo in a collapsed panel, without the
+    /// location unless --strip-gps is false
+    #[arg(long, env = "PHOTO2HTML_FULL_EXIF")]
+    full_exif: bool,
+
+    /// Show the camera, lens and exposure of each photo over its thumbnail
+    /// while it is hovered over
+    #[arg(long, env = "PHOTO2HTML_HOVER_SETTINGS")]
+    hover_settings: bool,
+
+    /// Open the full images on their own instead of over the page, where the
+    /// arrows, arrow keys and swipes go through the photos of the page
+    #[arg(long, env = "PHOTO2HTML_NO_LIGHTBOX")]
+    no_lightbox: bool,
+
+    /// Which photo to show large at the top of each page
+    #[arg(long, value_enum, default_value_t = Hero::None, env = "PHOTO2HTML_HERO")]
+    hero: Hero,
+
+    /// Link the thumbnails to the originals instead of generating full images
+    #[arg(long, env = "PHOTO2HTML_NO_FULL_IMAGE")]
+    no_full_image: bool,
+
+    /// Precede each photo with a comment naming its source file and time
+    #[arg(long, env = "PHOTO2HTML_VERBOSE_HTML")]
+    verbose_html: bool,
+
+    /// Remove the location from published images that keep their metadata
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set, env = "PHOTO2HTML_STRIP_GPS")]
+    strip_gps: bool,
+
+    /// Mark where the days on a page cross into another month or year
+    #[arg(long, value_enum, default_value_t = Separators::None, env = "PHOTO2HTML_SECTION_SEPARATORS")]
+    section_separators: Separators,
+
+    /// Which date photos are grouped into days by, and so paginated by
+    #[arg(long, value_enum, default_value_t = DateSource::Original, env = "PHOTO2HTML_GROUP_DATE_SOURCE")]
+    group_date_source: DateSource,
+
+    /// Which date orders the photos within a day
+    #[arg(long, value_enum, default_value_t = DateSource::Original, env = "PHOTO2HTML_SORT_DATE_SOURCE")]
+    sort_date_source: DateSource,
+
+    /// What reads the dates, locations, ratings and keywords of the photos
+    #[arg(long, value_enum, default_value_t = MetadataBackend::Builtin, env = "PHOTO2HTML_METADATA_BACKEND")]
+    metadata_backend: MetadataBackend,
+
+    /// Show a bar sized by the number of photos next to each page in the nav
+    #[arg(long, env = "PHOTO2HTML_NAV_HISTOGRAM")]
+    nav_histogram: bool,
+
+    /// Head the photos of today and yesterday with "Today" and "Yesterday"
+    #[arg(long, env = "PHOTO2HTML_RELATIVE_DATES")]
+    relative_dates: bool,
+
+    /// Generate the same output from the same photos, taking the current time
+    /// from SOURCE_DATE_EPOCH, or else the Unix epoch
+    #[arg(long, env = "PHOTO2HTML_DETERMINISTIC")]
+    deterministic: bool,
+}
+
+impl Args {
+    /// The directory of the first input, in which `photo2html.toml` and the
+    /// templates are looked for.
+    fn input_dir(&self) -> &Path {
+        let input = Path::new(self.inputs.first().map_or("./", |input| input.as_str()));
+        if input.is_dir() {
+            input
+        } else {
+            input.parent().unwrap_or(Path::new("."))
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Preset {
+    /// WebP at quality 80, at most 2048px
+    Web,
+    /// JPEG at quality 92, full resolution, metadata kept
+    Print,
+    /// Copies of the originals
+    Archive,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, ValueEnum)]
+enum Format {
+    Jpeg,
+    Webp,
+    Avif,
+    /// Copy the original file, only applies to the full images
+    Original,
+}
+
+impl Format {
+    /// Whether the output depends enough on the encoder's version to be
+    /// regenerated by a new one, see [`ENCODER_MANIFEST_PATH`].
+    fn is_versioned(&self) -> bool {
+        matches!(self, Format::Webp | Format::Avif)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Theme {
+    Light,
+    Dark,
+    /// Follow the preference of the browser
+    Auto,
+}
+
+struct Palette {
+    background: &'static str,
+    text: &'static str,
+    muted: &'static str,
+}
+
+const LIGHT: Palette = Palette {
+    background: "#ffffff",
+    text: "#222222",
+    muted: "#808080",
+};
+
+const DARK: Palette = Palette {
+    background: "#121212",
+    text: "#e0e0e0",
+    muted: "#9e9e9e",
+};
+
+impl Palette {
+    fn css(&self, scheme: &str) -> String {
+        format!(
+            ":root {{
+    color-scheme: {scheme};
+    --background: {};
+    --text: {};
+    --muted: {};
+}}
+",
+            self.background, self.text, self.muted
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Sort {
+    /// Newest first, grouped by day
+    Datetime,
+    /// By filename, in one group
+    Filename,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Originals {
+    Copy,
+    /// Falls back to copying if links are not supported
+    Symlink,
+    /// Falls back to copying across filesystems
+    Hardlink,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Hero {
+    None,
+    /// The first photo of the page
+    First,
+    /// The first photo with the keyword `cover`, if any, otherwise the first
+    Chosen,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum GroupBy {
+    /// The day the photo was taken, newest first, unless sorted by filename
+    Day,
+    /// The model of the lens
+    Lens,
+    /// The model of the camera
+    Camera,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum AlbumCover {
+    /// The one taken last
+    Newest,
+    /// The one taken first
+    Oldest,
+    /// The one rated highest, or taken last of those
+    TopRated,
+    /// The one with the keyword cover, or else the first shown
+    Pinned,
+}
+
+impl AlbumCover {
+    /// Chooses among photos in the order they are shown, if there are any.
+    fn choose<'a>(self, mut photos: impl Iterator<Item = &'a Photo>) -> Option<&'a Photo> {
+        match self {
+            AlbumCover::Newest => photos.max_by_key(|p| p.utc_datetime()),
+            AlbumCover::Oldest => photos.min_by_key(|p| p.utc_datetime()),
+            AlbumCover::TopRated => photos.max_by_key(|p| (p.rating, p.utc_datetime())),
+            AlbumCover::Pinned => photos.find(|p| p.cover),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum PaginateBy {
+    /// About --per-page photos on each page, numbered from the oldest
+    Count,
+    /// A page of each month, like 2024/05.html, listed under its year
+    Month,
+    /// A page of each year, like 2024.html
+    Year,
+}
+
+impl GroupBy {
+    fn key(self, photo: &Photo, options: &Options) -> GroupKey {
+        match self {
+            GroupBy::Day => match options.sort {
+                Sort::Datetime if photo.undated && options.group_empty_as == EmptyDate::Unknown => {
+                    GroupKey::Undated
+                }
+                Sort::Datetime => GroupKey::Date(photo.datetime.date()),
+                Sort::Filename => GroupKey::All,
+            },
+            GroupBy::Lens => GroupKey::Field(photo.setting("Lens").map(str::to_owned)),
+            GroupBy::Camera => GroupKey::Field(photo.setting("Camera").map(str::to_owned)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum EmptyDate {
+    /// By the time the file was last modified, among the others
+    Mtime,
+    /// Into one group of their own, apart from the days
+    Unknown,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Position {
+    Top,
+    Bottom,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum DateSource {
+    /// When the photo was taken
+    Original,
+    /// When the photo was digitized, e.g. scanned or imported
+    Digitized,
+    /// When the file was last modified
+    Modified,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum MetadataBackend {
+    /// Read EXIF, XMP and PNG metadata without other programs
+    Builtin,
+    /// Call ExifTool, which knows more formats and maker notes, falling back
+    /// to the builtin reader for what it doesn't find
+    Exiftool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Separators {
+    None,
+    Month,
+    Year,
+}
+
+impl Separators {
+    /// Returns the name of the section containing `date`.
+    fn section(self, date: NaiveDate, options: &Options) -> Option<String> {
+        match self {
+            Separators::None => None,
+            Separators::Month => Some(options.format(&date, "%B %Y")),
+            Separators::Year => Some(options.format(&date, "%Y")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Fit {
+    /// Scale down, keeping the whole photo
+    Contain,
+    /// Crop the center of the photo to a square
+    Cover,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Layout {
+    /// Up to --columns columns, in which the thumbnails keep their heights
+    Masonry,
+    /// Rows about --row-height high, each filling the width of the page
+    Justified,
+}
+
+fn parse_day(s: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").map_err(|e| format!("{e}, expected YYYY-MM-DD"))
+}
+
+fn parse_offset(s: &str) -> Result<FixedOffset, String> {
+    match s {
+        "UTC" | "Z" => Ok(FixedOffset::east_opt(0).unwrap()),
+        _ => s
+            .parse()
+            .map_err(|e| format!("{e}, expected an offset like +09:00")),
+    }
+}
+
+fn parse_range(s: &str) -> Result<RangeInclusive<NaiveDate>, String> {
+    let (start, end) = s
+        .split_once("..")
+        .ok_or_else(|| format!("invalid range: {s}, expected START..END"))?;
+    let (start, end) = (parse_day(start)?, parse_day(end)?);
+    if start > end {
+        return Err(format!("{start} is after {end}"));
+    }
+    Ok(start..=end)
+}
+
+/// A span of time before now, see `--last`.
+#[derive(Clone, Debug)]
+enum Span {
+    Exact(TimeDelta),
+    /// Months vary in length, so they are counted on the calendar.
+    Months(u32),
+}
+
+impl Span {
+    /// Returns when the span before `now` starts, or `None` if that is before
+    /// the earliest time there is.
+    fn before(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        match self {
+            Span::Exact(delta) => now.checked_sub_signed(*delta),
+            Span::Months(months) => now.checked_sub_months(Months::new(*months)),
+        }
+    }
+}
+
+fn parse_span(s: &str) -> Result<Span, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (amount, unit) = s.split_at(split);
+    let amount: u32 = amount
+        .parse()
+        .map_err(|_| format!("invalid span: {s}, expected e.g. 7d"))?;
+    let span = match unit {
+        "min" => Span::Exact(TimeDelta::minutes(amount.into())),
+        "h" => Span::Exact(TimeDelta::hours(amount.into())),
+        "d" => Span::Exact(TimeDelta::days(amount.into())),
+        "w" => Span::Exact(TimeDelta::weeks(amount.into())),
+        "mo" => Span::Months(amount),
+        "y" => Span::Months(amount.saturating_mul(12)),
+        _ => {
+            return Err(format!(
+                "invalid unit: {unit:?}, expected min, h, d, w, mo or y"
+            ))
+        }
+    };
+    Ok(span)
+}
+
+/// Parses a number of bytes, optionally with a `K`, `M` or `G` suffix for
+/// powers of 1024.
+fn parse_size(s: &str) -> Result<u64, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (amount, unit) = s.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("invalid size: {s}, expected e.g. 500K"))?;
+    let shift = match unit {
+        "" => 0,
+        "K" | "k" => 10,
+        "M" => 20,
+        "G" => 30,
+        _ => return Err(format!("invalid unit: {unit:?}, expected K, M or G")),
+    };
+    amount
+        .checked_mul(1 << shift)
+        .ok_or_else(|| format!("size too large: {s}"))
+}
+
+fn parse_glob(s: &str) -> Result<glob::Pattern, String> {
+    glob::Pattern::new(s).map_err(|e| e.to_string())
+}
+
+/// Returns whether the pattern matches the path, or the end of it, e.g. `2023/*`
+/// matches `photos/2023/a.jpg`.
+fn matches_glob(pattern: &glob::Pattern, path: &Path) -> bool {
+    let components: Vec<_> = path.components().collect();
+    (0..components.len())
+        .any(|i| pattern.matches_path(&components[i..].iter().collect::<PathBuf>()))
+}
+
+fn parse_date_format(s: &str) -> Result<String, String> {
+    if StrftimeItems::new(s).any(|i| i == Item::Error) {
+        Err(format!("invalid date format: {s}"))
+    } else {
+        Ok(s.to_owned())
+    }
+}
+
+/// How the gallery is generated, see `photo2html --help`.
+#[derive(Debug)]
+pub struct Options {
+    inputs: Vec<PathBuf>,
+    output_dir: PathBuf,
+    watch: bool,
+    serve: Option<String>,
+    deploy: Option<String>,
+    thumbnail_dir: PathBuf,
+    img_dir: PathBuf,
+    /// Set if the originals are published.
+    original_dir: Option<PathBuf>,
+    originals: Originals,
+    title: String,
+    theme: Theme,
+    theme_color: Option<String>,
+    pwa: bool,
+    icon: Option<PathBuf>,
+    date_format: String,
+    /// Set if month and day names are available in the language of `lang`.
+    locale: Option<chrono::Locale>,
+    lang: String,
+    phrases: &'static locale::Phrases,
+    columns: u32,
+    layout: Layout,
+    row_height: u32,
+    thumbnail_format: Format,
+    image_format: Format,
+    /// Other than `thumbnail_format`, and without duplicates.
+    source_formats: Vec<Format>,
+    /// The image backend and its version, only if images are made in a
+    /// format whose encoder is recorded, see [`ENCODER_MANIFEST_PATH`].
+    encoder: Option<String>,
+    /// How each image was made, see [`Options::image_signature`], by its path
+    /// relative to the output directory.
+    encoders: Mutex<HashMap<String, String>>,
+    /// What each photo was last built from and into, by its path, see
+    /// [`BUILD_MANIFEST_PATH`].
+    builds: Mutex<HashMap<PathBuf, Build>>,
+    thumbnail_background: Option<String>,
+    thumbnail_quality: u8,
+    thumbnail_size: u32,
+    /// The widths of the other thumbnails, each in a subdirectory of
+    /// `thumbnail_dir` named after it.
+    thumbnail_widths: Vec<u32>,
+    resize_filter: image::Filter,
+    image_quality: Option<u8>,
+    processor: Box<dyn image::ImageProcessor>,
+    ffmpeg: PathBuf,
+    jobs: Option<u32>,
+    /// The lowest and highest quality, see `--quality-by-size`.
+    quality_by_size: Option<(u8, u8)>,
+    max_dimension: Option<u32>,
+    max_bytes: Option<u64>,
+    keep_exif: bool,
+    keep_icc: bool,
+    css: Option<PathBuf>,
+    /// From `--templates`, read in [`Options::new`] and again when they
+    /// change with `--watch`.
+    templates: Mutex<template::Templates>,
+    templates_dir: Option<PathBuf>,
+    on_this_day: bool,
+    base_url: Option<String>,
+    thumbnail_fit: Fit,
+    contact_sheet: bool,
+    sort: Sort,
+    group_by: GroupBy,
+    group_empty_as: EmptyDate,
+    undated_position: Position,
+    single_file: Option<PathBuf>,
+    inline_images: bool,
+    regenerate: Option<glob::Pattern>,
+    stdout: bool,
+    archive: Option<PathBuf>,
+    pdf: Option<PathBuf>,
+    copy_assets: Option<PathBuf>,
+    include_hidden: bool,
+    recursive: bool,
+    depth: Option<u32>,
+    albums: bool,
+    tags: bool,
+    hashed_assets: bool,
+    toc: bool,
+    map: bool,
+    opds: bool,
+    album_cover: AlbumCover,
+    feed: Option<usize>,
+    json: bool,
+    json_only: bool,
+    map_tiles: String,
+    map_attribution: String,
+    max_pages: Option<usize>,
+    paginate_by: PaginateBy,
+    /// See `--per-page`.
+    per_page: usize,
+    split_large_days: bool,
+    only_with_people: bool,
+    people_in_caption: bool,
+    dense_days: Option<usize>,
+    stack_bursts: Option<u32>,
+    skip_duplicates: bool,
+    skip_similar: Option<u32>,
+    preload_thumbnails: Option<usize>,
+    prefer_embedded_preview: bool,
+    /// Only resolve the photos and their outputs, see [`check`].
+    check: bool,
+    noop_on_empty: bool,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    today: bool,
+    last: Option<Span>,
+    timezone: Option<FixedOffset>,
+    /// From `--exclude-date` and `--exclude-range`.
+    excluded: Vec<RangeInclusive<NaiveDate>>,
+    min_dimension: Option<u32>,
+    permalinks: bool,
+    full_exif: bool,
+    hover_settings: bool,
+    lightbox: bool,
+    hero: Hero,
+    /// The originals in `original_dir` are used as the full images.
+    no_full_image: bool,
+    verbose_html: bool,
+    strip_gps: bool,
+    section_separators: Separators,
+    nav_histogram: bool,
+    relative_dates: bool,
+    /// The current time, if fixed by `--deterministic`.
+    pinned_now: Option<DateTime<Local>>,
+    group_date_source: DateSource,
+    sort_date_source: DateSource,
+    /// Whether metadata is read with ExifTool, unset if it is not installed.
+    exiftool: bool,
+}
+
+impl From<Args> for Options {
+    fn from(value: Args) -> Self {
+        let mut inputs: Vec<PathBuf> = value.inputs.into_iter().map(PathBuf::from).collect();
+        if inputs.is_empty() {
+            inputs.push(PathBuf::from("./"));
+        }
+        let output_dir = if let Some(output_dir) = value.output_dir {
+            PathBuf::from(output_dir)
+        } else {
+            let input = &inputs[0];
+            let input_dir = if input.is_dir() {
+                input.as_path()
+            } else {
+                input.parent().unwrap_or(Path::new("."))
+            };
+            input_dir
+                .parent()
+                .unwrap_or(Path::new("."))
+                .join("web")
+                .to_owned()
+        };
+        let thumbnail_dir = output_dir.join("thumbnail");
+        let img_dir = output_dir.join("img");
+        let original_dir =
+            (value.originals.is_some() || value.no_full_image).then(|| output_dir.join("original"));
+        let (format, image_quality, max_dimension, keep_exif) = match value.preset {
+            None => (Format::Jpeg, None, None, false),
+            Some(Preset::Web) => (Format::Webp, Some(80), Some(2048), false),
+            Some(Preset::Print) => (Format::Jpeg, Some(92), None, true),
+            Some(Preset::Archive) => (Format::Original, None, None, true),
+        };
+        let mut date_format = value.date_format;
+        let date_locale = value.locale.as_deref().and_then(|tag| {
+            let date_locale = locale::parse(tag);
+            if date_locale.is_none() {
+                warn!("no month and day names for locale {tag}, using ISO dates");
+                date_format = "%Y-%m-%d".to_owned();
+            }
+            date_locale
+        });
+        let lang = value.locale.unwrap_or_else(|| "en".to_owned());
+        let thumbnail_format = value.thumbnail_format.or(value.format).unwrap_or(format);
+        let image_format = value.image_format.or(value.format).unwrap_or(format);
+        let source_formats: Vec<Format> = value
+            .source_formats
+            .into_iter()
+            .map(|format| match format {
+                Format::Original => Format::Jpeg,
+                format => format,
+            })
+            .filter(|format| *format != thumbnail_format)
+            .unique()
+            .collect();
+        let encoder = [thumbnail_format, image_format]
+            .iter()
+            .chain(&source_formats)
+            .any(Format::is_versioned)
+            .then(|| {
+                // Which the native backend can't write, but falls back for.
+                let backend = match value.image_backend {
+                    image::Backend::Native => image::Backend::Magick,
+                    backend => backend,
+                };
+                let version = backend.version(&value.magick).unwrap_or_default();
+                format!("{backend:?} {version}")
+            });
+        let encoders = read_encoder_manifest(&output_dir);
+        let builds = read_build_manifest(&output_dir);
+        Self {
+            inputs,
+            output_dir,
+            watch: value.watch || value.serve.is_some(),
+            serve: value.serve,
+            deploy: value.deploy,
+            thumbnail_dir,
+            img_dir,
+            original_dir,
+            originals: value.originals.unwrap_or(Originals::Symlink),
+            title: value.title,
+            theme: value.theme,
+            theme_color: value.theme_color,
+            pwa: value.pwa,
+            icon: value.icon.map(PathBuf::from),
+            date_format,
+            locale: date_locale,
+            phrases: locale::phrases(&lang),
+            lang,
+            columns: value.columns,
+            layout: value.layout,
+            row_height: value.row_height,
+            thumbnail_format,
+            image_format,
+            source_formats,
+            encoder,
+            encoders: Mutex::new(encoders),
+            builds: Mutex::new(builds),
+            thumbnail_background: value.thumbnail_background,
+            thumbnail_quality: value.thumbnail_quality.unwrap_or(65),
+            thumbnail_size: value.thumbnail_size,
+            thumbnail_widths: value
+                .thumbnail_widths
+                .into_iter()
+                .filter(|w| *w != value.thumbnail_size)
+                .collect(),
+            resize_filter: value.resize_filter,
+            image_quality: value.image_quality.or(image_quality),
+            processor: value
+                .image_backend
+                .processor(&value.magick, value.magick_fallback),
+            ffmpeg: value.ffmpeg,
+            jobs: value.jobs,
+            quality_by_size: value
+                .quality_by_size
+                .then_some((value.min_quality, value.max_quality)),
+            max_dimension: if value.no_max_dimension {
+                None
+            } else {
+                value.max_dimension.or(max_dimension)
+            },
+            max_bytes: value.max_bytes,
+            keep_exif: (value.keep_exif || keep_exif) && !value.no_keep_exif,
+            keep_icc: value.keep_icc,
+            css: value.css.map(PathBuf::from),
+            templates: Mutex::default(),
+            templates_dir: value.templates,
+            on_this_day: value.on_this_day,
+            base_url: value
+                .base_url
+                .map(|url| url.trim_end_matches('/').to_owned()),
+            thumbnail_fit: value.thumbnail_fit,
+            contact_sheet: value.contact_sheet,
+            sort: value.sort,
+            group_by: value.group_by,
+            group_empty_as: value.group_empty_as,
+            undated_position: value.undated_position,
+            single_file: value.single_file.map(PathBuf::from),
+            regenerate: value.regenerate,
+            stdout: value.stdout,
+            archive: value.archive.map(PathBuf::from),
+            pdf: value.pdf.map(PathBuf::from),
+            copy_assets: value.copy_assets.map(PathBuf::from),
+            include_hidden: value.include_hidden,
+            recursive: value.recursive || value.albums,
+            depth: value.depth,
+            albums: value.albums,
+            tags: value.tags,
+            hashed_assets: value.hashed_assets,
+            toc: value.toc,
+            map: value.map,
+            opds: value.opds,
+            album_cover: value.album_cover,
+            feed: value.feed.map(|feed| feed as usize),
+            json: value.json || value.json_only,
+            json_only: value.json_only,
+            map_tiles: value.map_tiles,
+            map_attribution: value.map_attribution,
+            max_pages: value.max_pages.map(|max| max as usize),
+            paginate_by: value.paginate_by,
+            per_page: value.per_page as usize,
+            split_large_days: value.split_large_days,
+            only_with_people: value.only_with_people,
+            people_in_caption: value.people_in_caption,
+            dense_days: value.dense_days,
+            stack_bursts: value.stack_bursts,
+            skip_duplicates: value.skip_duplicates,
+            skip_similar: value.skip_similar,
+            preload_thumbnails: value.preload_thumbnails,
+            prefer_embedded_preview: value.prefer_embedded_preview,
+            check: value.check,
+            noop_on_empty: value.noop_on_empty,
+            since: value.since,
+            until: value.until,
+            today: value.today,
+            last: value.last,
+            timezone: value.timezone,
+            excluded: value
+                .exclude_date
+                .into_iter()
+                .map(|date| date..=date)
+                .chain(value.exclude_range)
+                .collect(),
+            inline_images: value.inline_images,
+            min_dimension: value.min_dimension,
+            permalinks: value.permalinks,
+            full_exif: value.full_exif,
+            hover_settings: value.hover_settings,
+            lightbox: !value.no_lightbox,
+            hero: value.hero,
+            no_full_image: value.no_full_image,
+            verbose_html: value.verbose_html,
+            strip_gps: value.strip_gps,
+            section_separators: value.section_separators,
+            nav_histogram: value.nav_histogram,
+            relative_dates: value.relative_dates,
+            pinned_now: value.deterministic.then(|| {
+                let epoch = std::env::var("SOURCE_DATE_EPOCH")
+                    .ok()
+                    .and_then(|epoch| epoch.parse().ok())
+                    .unwrap_or(0);
+                DateTime::from_timestamp(epoch, 0).unwrap().into()
+            }),
+            group_date_source: value.group_date_source,
+            sort_date_source: value.sort_date_source,
+            exiftool: match value.metadata_backend {
+                MetadataBackend::Builtin => false,
+                MetadataBackend::Exiftool if exiftool::is_installed() => true,
+                MetadataBackend::Exiftool => {
+                    warn!("exiftool not found, reading the metadata without it");
+                    false
+                }
+            },
+        }
+    }
+}
+
+impl Options {
+    /// Parses the options from command-line arguments, the first of which is
+    /// the program name, and the environment. Unlike on the command line,
+    /// `photo2html.toml` isn't read.
+    pub fn parse_from<I, T>(args: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let args = Args::try_parse_from(args).map_err(|e| Error::Options(e.to_string()))?;
+        Options::new(args)
+    }
+
+    /// Loads the templates of `--templates`, which the rest of the options
+    /// don't need to be read.
+    fn new(mut args: Args) -> Result<Self, Error> {
+        if args.paginate_by != PaginateBy::Count
+            && (args.group_by != GroupBy::Day || args.sort != Sort::Datetime)
+        {
+            return Err(Error::Options(
+                "--paginate-by month and year need the photos grouped by day".to_owned(),
+            ));
+        }
+        if args.templates.is_none() {
+            let dir = args.input_dir().join(template::DIR_NAME);
+            args.templates = dir.is_dir().then_some(dir);
+        }
+        let templates = match &args.templates {
+            Some(dir) => template::Templates::load(dir).map_err(Error::Options)?,
+            None => template::Templates::default(),
+        };
+        let options: Options = args.into();
+        *options.templates.lock().unwrap() = templates;
+        Ok(options)
+    }
+
+    /// Creates the output directories, and marks them as generated.
+    fn create_dirs(&self) -> Result<(), Error> {
+        let css_dir = self.output_dir.join("css");
+        let permalink_dir = self.permalinks.then(|| self.output_dir.join(PERMALINK_DIR));
+        let film_strip_dir = self
+            .dense_days
+            .is_some()
+            .then(|| self.output_dir.join(FILM_STRIP_DIR));
+        let variant_dirs: Vec<PathBuf> = self
+            .thumbnail_widths
+            .iter()
+            .map(|w| self.thumbnail_dir.join(w.to_string()))
+            .collect();
+        for d in [&self.thumbnail_dir, &self.img_dir, &css_dir]
+            .into_iter()
+            .chain(&variant_dirs)
+            .chain(&self.original_dir)
+            .chain(&permalink_dir)
+            .chain(&film_strip_dir)
+        {
+            if !d.exists() {
+                create_dir_all(d).at(d)?;
+            }
+        }
+        let marker = self.output_dir.join(MARKER);
+        if !marker.exists() {
+            fs::write(&marker, "Generated by photo2html, see --clean.\n").at(&marker)?;
+        }
+        Ok(())
+    }
+
+    /// Identifies how an image in `format` is made, as recorded in the
+    /// [`ENCODER_MANIFEST_PATH`]: by which encoder if it is versioned, and
+    /// with the options that change the image. `thumbnail` is the width of a
+    /// thumbnail.
+    fn image_signature(&self, format: Format, thumbnail: Option<u32>) -> String {
+        let encoder = match self.encoder.as_ref().filter(|_| format.is_versioned()) {
+            Some(encoder) => format!("{encoder}, "),
+            None => String::new(),
+        };
+        let quality = match (
+            thumbnail.is_some(),
+            self.quality_by_size,
+            self.image_quality,
+        ) {
+            (true, _, _) => self.thumbnail_quality.to_string(),
+            (false, Some((min, max)), _) => format!("{min}-{max} by size"),
+            (false, None, Some(quality)) => quality.to_string(),
+            (false, None, None) => "default".to_owned(),
+        };
+        let size = match (thumbnail, self.max_dimension) {
+            (Some(size), _) => format!(", {size}px {:?}", self.thumbnail_fit),
+            (None, Some(max)) => format!(", at most {max}px"),
+            (None, None) => String::new(),
+        };
+        let budget = match self.max_bytes.filter(|_| thumbnail.is_none()) {
+            Some(max) => format!(", at most {max} bytes"),
+            None => String::new(),
+        };
+        format!("{encoder}{format:?}, quality {quality}{size}{budget}")
+    }
+
+    /// Returns the content hash recorded in the [`BUILD_MANIFEST_PATH`] for the
+    /// photo at `path`, unless it changed since.
+    fn recorded_hash(&self, path: &Path, stamp: FileStamp) -> Option<String> {
+        let builds = self.builds.lock().unwrap();
+        let build = builds.get(path).filter(|build| build.stamp == stamp)?;
+        build.hash.clone()
+    }
+
+    fn relative_path<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.output_dir).unwrap()
+    }
+
+    /// Like [`Options::relative_path`], for links and manifests. The outputs
+    /// are named in UTF-8, see [`output_names`].
+    fn relative_str<'a>(&self, path: &'a Path) -> Cow<'a, str> {
+        self.relative_path(path).to_string_lossy()
+    }
+
+    /// Returns whether a photo taken at `datetime` is within `--since`,
+    /// `--until`, `--today` and `--last`, which all apply.
+    fn includes(&self, datetime: DateTime<FixedOffset>) -> bool {
+        let date = datetime.date_naive();
+        let now = self.now();
+        let since = self.since.max(self.today.then(|| now.date_naive()));
+        since.is_none_or(|since| date >= since)
+            && self.until.is_none_or(|until| date <= until)
+            && self
+                .last
+                .as_ref()
+                .and_then(|last| last.before(now))
+                .is_none_or(|start| datetime >= start)
+            && !self.excluded.iter().any(|range| range.contains(&date))
+    }
+
+    /// Returns the current time, unless `--deterministic` pins it.
+    fn now(&self) -> DateTime<Local> {
+        self.pinned_now.unwrap_or_else(Local::now)
+    }
+
+    fn format_date(&self, date: &NaiveDate) -> String {
+        self.format(date, &self.date_format)
+    }
+
+    /// Formats a date for display, in the language of the pages.
+    fn format(&self, date: &NaiveDate, format: &str) -> String {
+        match self.locale {
+            Some(locale) => date.format_localized(format, locale).to_string(),
+            None => date.format(format).to_string(),
+        }
+    }
+}
+
+/// A photo or video of the gallery, with its images generated.
+#[derive(Debug)]
+pub struct Photo {
+    /// `ImageUniqueID` if present, otherwise a hash of the file content.
+    id: String,
+    original_path: PathBuf,
+    /// The original as it was read.
+    stamp: FileStamp,
+    /// The hash of the original if `id` is made from it, see [`content_hash`].
+    hash: Option<String>,
+    /// When the photo was taken, by `--group-date-source`, in the local time
+    /// of where it was taken. See [`Photo::zoned_datetime`].
+    datetime: NaiveDateTime,
+    /// The offset of `datetime` from UTC.
+    offset: FixedOffset,
+    /// Orders the photos of a day, by `--sort-date-source`.
+    sort_datetime: NaiveDateTime,
+    /// Has no date of `--group-date-source`, so `datetime` is when the file
+    /// was last modified.
+    undated: bool,
+    thumbnail_path: PathBuf,
+    /// The thumbnails of `--thumbnail-widths`, by width.
+    thumbnail_variants: Vec<(u32, PathBuf)>,
+    /// The thumbnails in each of `--source-formats`, by width, including
+    /// `--thumbnail-size`.
+    thumbnail_sources: Vec<(Format, Vec<(u32, PathBuf)>)>,
+    /// The width and height of the photo as it is shown, if known.
+    dimensions: Option<(u32, u32)>,
+    /// The full image, or the video itself.
+    img_path: PathBuf,
+    /// The first frame of a video, which its thumbnails are made from.
+    poster_path: Option<PathBuf>,
+    /// The published copy of the original, see `--originals`. Unset if it
+    /// is the full image already.
+    published_path: Option<PathBuf>,
+    caption: Option<String>,
+    /// Camera settings worth showing, as label and value.
+    settings: Vec<(&'static str, String)>,
+    /// Every EXIF tag as name and value, only read with `--full-exif`.
+    exif_fields: Vec<(String, String)>,
+    /// Only reachable by a direct link to the image, not shown on pages.
+    unlisted: bool,
+    /// Preferred as the hero of its page, see `--hero`.
+    cover: bool,
+    /// From 1 to 5 stars, from XMP, or with ExifTool any metadata.
+    rating: Option<u32>,
+    /// The file name, followed by its directory if other photos have the same
+    /// name. Used as the alternative text of the images.
+    label: String,
+    /// Latitude and longitude, see [`gps::coordinates`].
+    location: Option<(f64, f64)>,
+    /// From XMP, and with ExifTool IPTC too, except those marking it as
+    /// unlisted or as the cover.
+    keywords: Vec<String>,
+}
+
+impl Photo {
+    /// Returns `None` if the photo is excluded by the options, and an error if
+    /// it can't be read. `name` is the file name for the outputs, see
+    /// [`output_names`].
+    fn new(
+        path: PathBuf,
+        name: &OsStr,
+        options: &Options,
+        timings: &mut Timings,
+    ) -> Result<Option<Self>, String> {
+        let start = Instant::now();
+        let file = File::open(&path).map_err(|e| e.to_string())?;
+        let file_stamp = file
+            .metadata()
+            .and_then(|m| FileStamp::of(&m))
+            .map_err(|e| e.to_string())?;
+        let modified: DateTime<Local> = file_stamp.modified.into();
+        let mut buf_reader = BufReader::new(file);
+        let exif_reader = exif::Reader::new();
+        let exif = exif_reader.read_from_container(&mut buf_reader);
+        if let Some(min) = options.min_dimension {
+            let (width, height) = match exif.as_ref().ok().and_then(exif_dimensions) {
+                Some(dimensions) => dimensions,
+                None => options.processor.dimensions(&path)?,
+            };
+            if width < min || height < min {
+                info!("skipping {} ({width}x{height})", path.display());
+                timings.metadata += start.elapsed();
+                return Ok(None);
+            }
+        }
+        // Sidecars take precedence over the embedded packet, as they are what
+        // tools like digiKam and darktable write to.
+        let xmps: Vec<String> = xmp_sidecars(&path)
+            .filter_map(|sidecar| fs::read_to_string(sidecar).ok())
+            .chain(xmp::read_embedded(&path))
+            .collect();
+        let xmp_property = |name: &str| xmps.iter().find_map(|xmp| xmp::property(xmp, name));
+        let tags = options
+            .exiftool
+            .then(|| exiftool::read(&path))
+            .flatten()
+            .unwrap_or_default();
+        // Photos without any metadata are dated by when they were modified.
+        let exif = exif.ok();
+        let modified = || (modified.naive_local(), modified.fixed_offset().timezone());
+        let read_datetime = |source: DateSource| {
+            let (tag, offset_tag, xmp_names, tool_names) = match source {
+                DateSource::Original => (
+                    Tag::DateTimeOriginal,
+                    Tag::OffsetTimeOriginal,
+                    ["exif:DateTimeOriginal", "photoshop:DateCreated"],
+                    ["DateTimeOriginal", "OffsetTimeOriginal"],
+                ),
+                DateSource::Digitized => (
+                    Tag::DateTimeDigitized,
+                    Tag::OffsetTimeDigitized,
+                    ["exif:DateTimeDigitized", "xmp:CreateDate"],
+                    ["CreateDate", "OffsetTimeDigitized"],
+                ),
+                DateSource::Modified => return Some(modified()),
+            };
+            let [tool_name, tool_offset_name] = tool_names;
+            let tool_date = || {
+                let datetime = tags.get(tool_name)?.as_str()?;
+                match tags.get(tool_offset_name).and_then(json::Value::as_str) {
+                    Some(offset) => Some((
+                        parse_exif_datetime(datetime)?,
+                        offset.parse::<FixedOffset>().ok()?,
+                    )),
+                    // Taken from XMP, which has the offset in the date.
+                    None => match DateTime::parse_from_str(datetime, "%Y:%m:%d %H:%M:%S%:z") {
+                        Ok(datetime) => Some((datetime.naive_local(), *datetime.offset())),
+                        Err(_) => {
+                            with_local_offset(parse_exif_datetime(datetime)?, options.timezone)
+                        }
+                    },
+                }
+            };
+            tool_date()
+                .or_else(|| {
+                    let exif = exif.as_ref()?;
+                    let datetime = &exif.get_field(tag, In::PRIMARY)?.value;
+                    let datetime = parse_exif_datetime(&value_to_string(datetime)?)?;
+                    // Older cameras don't record it, so it is taken as local.
+                    match exif.get_field(offset_tag, In::PRIMARY) {
+                        Some(offset) => {
+                            let offset = value_to_string(&offset.value)?;
+                            Some((datetime, offset.parse::<FixedOffset>().ok()?))
+                        }
+                        None => with_local_offset(datetime, options.timezone),
+                    }
+                })
+                .or_else(|| {
+                    xmp_names
+                        .into_iter()
+                        .find_map(|name| parse_xmp_date(&xmp_property(name)?, options.timezone))
+                })
+                .or_else(|| parse_creation_time(&png::creation_time(&path)?, options.timezone))
+                .or_else(|| {
+                    let utc = mp4::creation_time(&path).filter(|_| is_video(&path))?;
+                    let datetime = match options.timezone {
+                        Some(timezone) => utc.with_timezone(&timezone),
+                        None => utc.with_timezone(&Local).fixed_offset(),
+                    };
+                    Some((datetime.naive_local(), *datetime.offset()))
+                })
+        };
+        let group_datetime = read_datetime(options.group_date_source);
+        let undated = group_datetime.is_none();
+        let (datetime, offset) = match options.sort {
+            Sort::Datetime
+                if !may_lack_exif(&path) && options.group_empty_as == EmptyDate::Mtime =>
+            {
+                match group_datetime {
+                    Some(datetime) => datetime,
+                    None if options.check => {
+                        error!("{} has no capture time", path.display());
+                        timings.metadata += start.elapsed();
+                        return Ok(None);
+                    }
+                    None => {
+                        warn!(
+                            "{} has no capture time, using when it was modified",
+                            path.display()
+                        );
+                        modified()
+                    }
+                }
+            }
+            _ => group_datetime.unwrap_or_else(modified),
+        };
+        let datetime = offset.from_local_datetime(&datetime).unwrap().naive_local();
+        let sort_datetime = if options.sort_date_source == options.group_date_source {
+            datetime
+        } else {
+            read_datetime(options.sort_date_source).map_or(datetime, |(datetime, _)| datetime)
+        };
+        if !options.includes(offset.from_local_datetime(&datetime).unwrap()) {
+            timings.metadata += start.elapsed();
+            return Ok(None);
+        }
+        let faces = xmps
+            .iter()
+            .map(|xmp| xmp::faces(xmp))
+            .find(|faces| !faces.is_empty())
+            .unwrap_or_default();
+        if options.only_with_people && faces.is_empty() {
+            timings.metadata += start.elapsed();
+            return Ok(None);
+        }
+        let unique_id = exif
+            .as_ref()
+            .and_then(|exif| exif.get_field(Tag::ImageUniqueID, In::PRIMARY))
+            .and_then(|f| value_to_string(&f.value))
+            .map(|id| sanitize_id(&id))
+            .filter(|id| !id.is_empty() && !id.bytes().all(|b| b == b'0'));
+        let hash = match unique_id {
+            Some(_) => None,
+            None => Some(
+                options
+                    .recorded_hash(&path, file_stamp)
+                    .map_or_else(|| content_hash(&path), Ok)?,
+            ),
+        };
+        let id = unique_id.or_else(|| hash.clone()).unwrap();
+        let keywords: Vec<String> = xmps
+            .iter()
+            .flat_map(|xmp| xmp::keywords(xmp))
+            .chain(
+                ["Keywords", "Subject"]
+                    .into_iter()
+                    .filter_map(|name| tags.get(name))
+                    .flat_map(json::Value::strings),
+            )
+            .collect();
+        let caption = xmps
+            .iter()
+            .find_map(|xmp| xmp::list(xmp, "dc:description").into_iter().next())
+            .or_else(|| {
+                let field = exif
+                    .as_ref()?
+                    .get_field(Tag::ImageDescription, In::PRIMARY)?;
+                value_to_string(&field.value)
+            })
+            .filter(|caption| !caption.is_empty());
+        let people = faces.into_iter().flatten().unique().join(", ");
+        let caption = match caption {
+            _ if !options.people_in_caption || people.is_empty() => caption,
+            Some(caption) => Some(format!("{caption} ({people})")),
+            None => Some(people),
+        };
+        let rating = tags
+            .get("Rating")
+            .and_then(json::Value::as_f64)
+            .map(|rating| rating.to_string())
+            .or_else(|| xmp_property("xmp:Rating"))
+            .and_then(|rating| rating.parse::<u32>().ok())
+            .filter(|rating| (1..=5).contains(rating));
+        let mut settings: Vec<_> = exif
+            .as_ref()
+            .map(|exif| {
+                [
+                    ("Camera", Tag::Model),
+                    ("Lens", Tag::LensModel),
+                    ("Focal length", Tag::FocalLength),
+                    ("Aperture", Tag::FNumber),
+                    ("Exposure", Tag::ExposureTime),
+                    ("ISO", Tag::PhotographicSensitivity),
+                ]
+                .into_iter()
+                .filter_map(|(label, tag)| {
+                    let field = exif.get_field(tag, In::PRIMARY)?;
+                    let value = match field.value {
+                        Value::Ascii(_) | Value::Undefined(..) => value_to_string(&field.value)?,
+                        _ => field.display_value().with_unit(exif).to_string(),
+                    };
+                    Some((label, value))
+                })
+                .collect()
+            })
+            .unwrap_or_default();
+        settings.extend(rating.map(|rating| ("Rating", "★".repeat(rating as usize))));
+        let exif_fields = match &exif {
+            Some(exif) if options.full_exif => exif
+                .fields()
+                .filter(|field| field.ifd_num == In::PRIMARY)
+                // Left out like from images keeping their metadata.
+                .filter(|field| !options.strip_gps || field.tag.context() != Context::Gps)
+                .map(|field| {
+                    let value = match &field.value {
+                        Value::Ascii(_) => value_to_string(&field.value).unwrap_or_default(),
+                        // Often binary, like maker notes.
+                        Value::Undefined(bytes, _) => value_to_string(&field.value)
+                            .filter(|s| !s.chars().any(char::is_control))
+                            .unwrap_or_else(|| format!("({} bytes)", bytes.len())),
+                        _ => field.display_value().with_unit(exif).to_string(),
+                    };
+                    (field.tag.to_string(), value)
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let location = tags
+            .get("GPSLatitude")
+            .and_then(json::Value::as_f64)
+            .zip(tags.get("GPSLongitude").and_then(json::Value::as_f64))
+            .or_else(|| exif.as_ref().and_then(gps::coordinates));
+        let unlisted =
+            keywords.iter().any(|k| k == UNLISTED) || path.with_added_extension(UNLISTED).exists();
+        timings.metadata += start.elapsed();
+
+        let start = Instant::now();
+        let force = options
+            .regenerate
+            .as_ref()
+            .is_some_and(|pattern| matches_glob(pattern, &path));
+        let rotate_sidecar = path.with_added_extension(ROTATE);
+        let rotation = match fs::read_to_string(&rotate_sidecar) {
+            Ok(rotation) => match rotation.trim().parse() {
+                Ok(degrees @ (0 | 90 | 180 | 270)) => Some(degrees),
+                _ => {
+                    warn!(
+                        "ignoring {}, expected 0, 90, 180 or 270",
+                        rotate_sidecar.display()
+                    );
+                    None
+                }
+            },
+            Err(_) => xmp_property("tiff:Orientation").and_then(|orientation| {
+                match orientation.as_str() {
+                    "1" => Some(0),
+                    "3" => Some(180),
+                    "6" => Some(90),
+                    "8" => Some(270),
+                    _ => None,
+                }
+            }),
+        };
+        // Raw files can only be shown by their preview.
+        let preview = exif
+            .as_ref()
+            .filter(|_| options.prefer_embedded_preview || is_raw(&path))
+            .and_then(preview::largest);
+        // Previews have no orientation of their own, so they are turned by that
+        // of the photo.
+        let rotation = rotation.or_else(|| {
+            preview?;
+            let orientation = exif
+                .as_ref()?
+                .get_field(Tag::Orientation, In::PRIMARY)?
+                .value
+                .get_uint(0)?;
+            match orientation {
+                3 => Some(180),
+                6 => Some(90),
+                8 => Some(270),
+                _ => None,
+            }
+        });
+        // An override is only reflected in the images if they are regenerated
+        // when it changes.
+        let stamp = match rotation {
+            Some(_) => {
+                iter::once(path.clone())
+                    .chain(xmp_sidecars(&path))
+                    .chain([rotate_sidecar])
+                    .filter_map(|p| Some((p.metadata().ok()?.modified().ok()?, p)))
+                    .max()
+                    .unwrap()
+                    .1
+            }
+            None => path.clone(),
+        };
+        let poster_path = is_video(&path)
+            .then(|| generate_poster(&path, name, force, options))
+            .transpose()?;
+        // Named after the video with its extension, which a photo of the same
+        // name, like the still of a live photo, doesn't have.
+        let image_name = match &poster_path {
+            Some(poster) => poster.file_name().unwrap(),
+            None => name,
+        };
+        let source = ImageSource {
+            path: poster_path.as_deref().unwrap_or(&path),
+            preview,
+            rotation,
+            stamp: &stamp,
+        };
+        let thumbnail_path =
+            Self::generate_image::<true>(source, image_name, None, None, force, options)?;
+        let thumbnail_variants = options
+            .thumbnail_widths
+            .iter()
+            .map(|&w| {
+                let path = Self::generate_image::<true>(
+                    source,
+                    image_name,
+                    Some(w),
+                    None,
+                    force,
+                    options,
+                )?;
+                Ok((w, path))
+            })
+            .collect::<Result<_, String>>()?;
+        let thumbnail_sources = options
+            .source_formats
+            .iter()
+            .map(|&format| {
+                let variants = iter::once(None)
+                    .chain(options.thumbnail_widths.iter().copied().map(Some))
+                    .map(|w| {
+                        let path = Self::generate_image::<true>(
+                            source,
+                            image_name,
+                            w,
+                            Some(format),
+                            force,
+                            options,
+                        )?;
+                        Ok((w.unwrap_or(options.thumbnail_size), path))
+                    })
+                    .collect::<Result<_, String>>()?;
+                Ok((format, variants))
+            })
+            .collect::<Result<_, String>>()?;
+        let mut published_path = options
+            .original_dir
+            .as_ref()
+            .map(|dir| publish_original(&path, &dir.join(name), options))
+            .transpose()?;
+        let img_path = if options.no_full_image {
+            published_path.take().unwrap()
+        } else if poster_path.is_some() {
+            // Videos are shown as they are.
+            publish_original(&path, &options.img_dir.join(name), options)?
+        } else {
+            Self::generate_image::<false>(source, name, None, None, force, options)?
+        };
+        // Only needed for thumbnails that keep the aspect ratio, the feed and
+        // gallery.json.
+        let dimensions =
+            (options.thumbnail_fit == Fit::Contain || options.feed.is_some() || options.json)
+                .then(|| {
+                    // The header, which unlike EXIF is never out of date, comes
+                    // before the image data.
+                    let mut header = Vec::new();
+                    File::open(source.path)
+                        .and_then(|f| f.take(1 << 20).read_to_end(&mut header))
+                        .ok();
+                    preview::dimensions(&header)
+                        .or_else(|| png::dimensions(&header))
+                        .or_else(|| {
+                            preview
+                                .filter(|_| is_raw(&path))
+                                .map(|p| (p.width, p.height))
+                        })
+                        .or_else(|| exif.as_ref().and_then(exif_dimensions))
+                })
+                .flatten()
+                .filter(|&(width, height)| width > 0 && height > 0)
+                .map(|(width, height)| {
+                    let turned = match rotation {
+                        Some(degrees) => degrees % 180 == 90,
+                        // Orientations 5 to 8 swap the width and height.
+                        None => exif
+                            .as_ref()
+                            .and_then(|e| {
+                                e.get_field(Tag::Orientation, In::PRIMARY)?
+                                    .value
+                                    .get_uint(0)
+                            })
+                            .is_some_and(|orientation| (5..=8).contains(&orientation)),
+                    };
+                    if turned {
+                        (height, width)
+                    } else {
+                        (width, height)
+                    }
+                });
+        timings.images += start.elapsed();
+
+        let file_name = path.file_name().unwrap().to_string_lossy();
+        // Only photos whose names collide get output names other than their own.
+        let label = if name == OsStr::new(&*file_name) {
+            file_name.into_owned()
+        } else if let Some(dir) = path.parent().and_then(Path::file_name) {
+            format!("{file_name} ({})", dir.to_string_lossy())
+        } else {
+            format!("{file_name} ({})", options.format_date(&datetime.date()))
+        };
+
+        return Ok(Some(Self {
+            id,
+            original_path: path,
+            stamp: file_stamp,
+            hash,
+            datetime,
+            offset,
+            sort_datetime,
+            undated,
+            thumbnail_path,
+            thumbnail_variants,
+            thumbnail_sources,
+            dimensions,
+            img_path,
+            poster_path,
+            published_path,
+            caption,
+            settings,
+            exif_fields,
+            unlisted,
+            cover: keywords.iter().any(|k| k == COVER),
+            rating,
+            label,
+            location,
+            keywords: keywords
+                .into_iter()
+                .filter(|k| k != UNLISTED && k != COVER)
+                .unique()
+                .collect(),
+        }));
+
+        fn sanitize_id(s: &str) -> String {
+            s.chars()
+                .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+                .collect()
+        }
+
+        /// Some cameras store text as `Undefined` instead of `Ascii`.
+        fn value_to_string(v: &Value) -> Option<String> {
+            let bytes: Vec<u8> = match v {
+                Value::Ascii(s) => s.iter().flatten().copied().collect(),
+                Value::Undefined(bytes, _) => bytes.clone(),
+                _ => return None,
+            };
+            let s = String::from_utf8(bytes).ok()?;
+            Some(s.trim_end_matches('\0').trim().to_owned())
+        }
+    }
+
+    fn cmp_filename(&self, other: &Photo) -> Ordering {
+        let name = |p: &Photo| {
+            p.original_path
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        };
+        natural_cmp(&name(self), &name(other))
+    }
+
+    /// The photo or video as found in the inputs.
+    pub fn original_path(&self) -> &Path {
+        &self.original_path
+    }
+
+    /// The file name, used as the alternative text of its images.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn caption(&self) -> Option<&str> {
+        self.caption.as_deref()
+    }
+
+    /// The thumbnail in the output directory.
+    pub fn thumbnail_path(&self) -> &Path {
+        &self.thumbnail_path
+    }
+
+    /// The full image in the output directory, or the video itself.
+    pub fn img_path(&self) -> &Path {
+        &self.img_path
+    }
+
+    /// Latitude and longitude, if known, which `--strip-gps` only keeps out of
+    /// the outputs.
+    pub fn location(&self) -> Option<(f64, f64)> {
+        self.location
+    }
+
+    pub fn keywords(&self) -> &[String] {
+        &self.keywords
+    }
+
+    /// The width and height of the photo as it is shown, if known.
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        self.dimensions
+    }
+
+    /// The width and height of the thumbnail, if known, see `--thumbnail-fit`.
+    fn thumbnail_dimensions(&self, options: &Options) -> Option<(u32, u32)> {
+        let size = options.thumbnail_size;
+        match options.thumbnail_fit {
+            Fit::Contain => {
+                let (width, height) = self.dimensions?;
+                let height = (u64::from(height) * u64::from(size)).div_ceil(u64::from(width));
+                Some((size, height as u32))
+            }
+            Fit::Cover => Some((size, size)),
+        }
+    }
+
+    /// The width and height of the full image, if known, which is smaller than
+    /// the photo with `--max-dimension`. Videos and GIFs are shown as they are.
+    fn img_dimensions(&self, options: &Options) -> Option<(u32, u32)> {
+        let (width, height) = self.dimensions?;
+        let max = options.max_dimension.filter(|&max| {
+            width.max(height) > max
+                && self.poster_path.is_none()
+                && !is_gif(&self.original_path)
+                && !options.no_full_image
+        });
+        let Some(max) = max else {
+            return Some((width, height));
+        };
+        let scale = f64::from(max) / f64::from(width.max(height));
+        let scaled = |n: u32| (f64::from(n) * scale).round() as u32;
+        Some((scaled(width), scaled(height)))
+    }
+
+    /// Returns when the photo was taken with its offset, for output in
+    /// RFC 3339 like `2023-08-15T14:22:10+09:00`.
+    pub fn zoned_datetime(&self) -> DateTime<FixedOffset> {
+        self.offset.from_local_datetime(&self.datetime).unwrap()
+    }
+
+    /// Returns the value of one of the `settings`.
+    fn setting(&self, label: &str) -> Option<&str> {
+        self.settings
+            .iter()
+            .find(|(l, _)| *l == label)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the paths of the `thumbnail_sources`.
+    fn source_thumbnails(&self) -> impl Iterator<Item = &PathBuf> {
+        self.thumbnail_sources
+            .iter()
+            .flat_map(|(_, variants)| variants.iter().map(|(_, path)| path))
+    }
+
+    fn utc_datetime(&self) -> NaiveDateTime {
+        self.zoned_datetime().naive_utc()
+    }
+
+    /// Unless `force` is set, the image is only generated if it is out of date.
+    /// It is made from `preview` instead of `input` if that is large enough.
+    /// With `--hashed-assets`, the returned path is that of the hashed name.
+    /// A thumbnail of a `width` other than `--thumbnail-size` is put in the
+    /// subdirectory of the thumbnails named after it. A thumbnail is made in
+    /// `format` if given, one of `--source-formats`.
+    fn generate_image<const THUMBNAIL: bool>(
+        source: ImageSource,
+        filename: &OsStr,
+        width: Option<u32>,
+        format: Option<Format>,
+        force: bool,
+        options: &Options,
+    ) -> Result<PathBuf, String> {
+        let input = source.path;
+        let format = if THUMBNAIL {
+            match format.unwrap_or(options.thumbnail_format) {
+                Format::Original => Format::Jpeg,
+                format => format,
+            }
+        } else if is_gif(input) {
+            // Converting would lose the animation.
+            Format::Original
+        } else {
+            match options.image_format {
+                // Which browsers can't show.
+                Format::Original if is_raw(input) => Format::Jpeg,
+                format => format,
+            }
+        };
+        let extension = match format {
+            Format::Jpeg => "jpg",
+            Format::Webp => "webp",
+            Format::Avif => "avif",
+            Format::Original => input.extension().and_then(|e| e.to_str()).unwrap_or(""),
+        };
+        let size = width.unwrap_or(options.thumbnail_size);
+        let output_path = match (THUMBNAIL, width) {
+            (true, Some(width)) => options.thumbnail_dir.join(width.to_string()),
+            (true, None) => options.thumbnail_dir.clone(),
+            (false, _) => options.img_dir.clone(),
+        }
+        .join(filename)
+        .with_extension(extension);
+        if options.check {
+            return Ok(output_path);
+        }
+        let finish = |output_path: PathBuf| {
+            if options.hashed_assets {
+                Ok(hashed_asset(output_path)?)
+            } else {
+                Ok(output_path)
+            }
+        };
+        let signature = options.image_signature(format, THUMBNAIL.then_some(size));
+        let key = options.relative_str(&output_path).into_owned();
+        let recorded = options.encoders.lock().unwrap().get(&key).cloned();
+        let same_signature = match &recorded {
+            Some(recorded) => *recorded == signature,
+            // Made before signatures were recorded for its format, presumably
+            // the way it would be now.
+            None => !format.is_versioned(),
+        };
+        if !force && is_up_to_date(&output_path, source.stamp) && same_signature {
+            if recorded.is_none() {
+                options.encoders.lock().unwrap().insert(key, signature);
+            }
+            return finish(output_path);
+        }
+        // Rotating needs a conversion, into the same format.
+        if format == Format::Original && source.rotation.is_none() {
+            fs::copy(input, &output_path).at(&output_path)?;
+            if options.strip_gps {
+                gps::strip(&output_path).at(&output_path)?;
+            }
+            mark_up_to_date(&output_path, source.stamp)?;
+            return finish(output_path);
+        }
+        let preview = source.preview.filter(|p| {
+            if is_raw(input) {
+                true
+            } else if THUMBNAIL {
+                match options.thumbnail_fit {
+                    Fit::Contain => p.width >= size,
+                    Fit::Cover => p.width.min(p.height) >= size,
+                }
+            } else {
+                // Previews have no metadata to keep, and the full image is
+                // only smaller than the photo with a maximum dimension.
+                !options.keep_exif
+                    && options
+                        .max_dimension
+                        .is_some_and(|max| p.width.max(p.height) >= max)
+            }
+        });
+        let start = Instant::now();
+        let decoded = match preview {
+            Some(p) => {
+                let decoded = output_path.with_added_extension("preview.jpg");
+                fs::write(&decoded, p.jpeg).at(&decoded)?;
+                decoded
+            }
+            None => input.to_owned(),
+        };
+        let mut conversion = image::Conversion {
+            first_frame: preview.is_none() && is_gif(input) && format != Format::Original,
+            strip: THUMBNAIL || !options.keep_exif,
+            keep_icc: options.keep_icc,
+            rotate: source.rotation,
+            filter: Some(options.resize_filter),
+            subsample: true,
+            ..Default::default()
+        };
+        if THUMBNAIL {
+            conversion.quality = Some(options.thumbnail_quality);
+            if format == Format::Jpeg {
+                let background = options.thumbnail_background.as_deref();
+                conversion.background = Some(background.unwrap_or("white").to_owned());
+            }
+            conversion.resize = Some(match options.thumbnail_fit {
+                Fit::Contain => image::Resize::Width(size),
+                Fit::Cover => image::Resize::Fill(size),
+            });
+        } else {
+            conversion.quality = match options.quality_by_size {
+                Some(range) => {
+                    let (width, height) = match preview {
+                        Some(p) => (p.width, p.height),
+                        None => options.processor.dimensions(input)?,
+                    };
+                    // The size after resizing is what is encoded.
+                    let scale = options.max_dimension.map_or(1.0, |max| {
+                        (f64::from(max) / f64::from(width.max(height))).min(1.0)
+                    });
+                    let pixels = f64::from(width) * f64::from(height) * scale * scale;
+                    Some(quality_for_size(pixels, range))
+                }
+                None => options.image_quality,
+            };
+            conversion.resize = options.max_dimension.map(image::Resize::Shrink);
+        }
+        let mut converted = options
+            .processor
+            .convert(&decoded, &output_path, &conversion);
+        if let Some(max) = options.max_bytes.filter(|_| !THUMBNAIL && !is_gif(input)) {
+            converted = converted
+                .and_then(|()| fit_in_bytes(&decoded, &output_path, &mut conversion, max, options));
+        }
+        if preview.is_some() {
+            fs::remove_file(&decoded).at(&decoded)?;
+        }
+        converted?;
+        if let Some(p) = preview {
+            info!(
+                "made {} from the {}x{} preview of {} in {:.2}s",
+                output_path.display(),
+                p.width,
+                p.height,
+                input.display(),
+                start.elapsed().as_secs_f64()
+            );
+        }
+        if !THUMBNAIL && options.keep_exif && options.strip_gps {
+            gps::strip(&output_path).at(&output_path)?;
+        }
+        options.encoders.lock().unwrap().insert(key, signature);
+        mark_up_to_date(&output_path, source.stamp)?;
+        finish(output_path)
+    }
+}
+
+/// What the images of a photo are made from.
+#[derive(Clone, Copy)]
+struct ImageSource<'a> {
+    path: &'a Path,
+    /// See `--prefer-embedded-preview`, and always used for raw files.
+    preview: Option<preview::Preview<'a>>,
+    /// Degrees clockwise to turn the stored pixels by, instead of following
+    /// the EXIF orientation.
+    rotation: Option<u32>,
+    /// The newest of the files the images are made from, whose modification
+    /// time they get.
+    stamp: &'a Path,
+}
+
+/// Compares strings so that numbers are ordered by their value, e.g. `img2` is
+/// before `img10`.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+    loop {
+        let (a_c, b_c) = match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => break,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_c), Some(b_c)) => (*a_c, *b_c),
+        };
+        if a_c.is_ascii_digit() && b_c.is_ascii_digit() {
+            let a_num: String = a_chars.peeking_take_while(|c| c.is_ascii_digit()).collect();
+            let b_num: String = b_chars.peeking_take_while(|c| c.is_ascii_digit()).collect();
+            let a_trimmed = a_num.trim_start_matches('0');
+            let b_trimmed = b_num.trim_start_matches('0');
+            let ordering = a_trimmed
+                .len()
+                .cmp(&b_trimmed.len())
+                .then_with(|| a_trimmed.cmp(b_trimmed));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        } else {
+            let ordering = a_c.cmp(&b_c);
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+            a_chars.next();
+            b_chars.next();
+        }
+    }
+    // Equal apart from zero padding, e.g. `img02` and `img2`.
+    a.cmp(b)
+}
+
+fn exif_dimensions(exif: &exif::Exif) -> Option<(u32, u32)> {
+    let get = |tags: [Tag; 2]| {
+        tags.iter()
+            .find_map(|tag| exif.get_field(*tag, In::PRIMARY)?.value.get_uint(0))
+    };
+    Some((
+        get([Tag::PixelXDimension, Tag::ImageWidth])?,
+        get([Tag::PixelYDimension, Tag::ImageLength])?,
+    ))
+}
+
+/// The lowest quality `--max-bytes` re-encodes an image at.
+const MIN_QUALITY_FOR_SIZE: u8 = 10;
+
+/// Re-encodes an image larger than `max` bytes at the highest quality that
+/// fits, found by a binary search down to [`MIN_QUALITY_FOR_SIZE`].
+fn fit_in_bytes(
+    input: &Path,
+    output_path: &Path,
+    conversion: &mut image::Conversion,
+    max: u64,
+    options: &Options,
+) -> Result<(), String> {
+    let size = || fs::metadata(output_path).map(|m| m.len()).at(output_path);
+    if size()? <= max {
+        return Ok(());
+    }
+    // ImageMagick's default for JPEG, when it can't be read from the image.
+    let start = conversion.quality.unwrap_or(92);
+    let floor = MIN_QUALITY_FOR_SIZE.min(start);
+    let (mut low, mut high) = (floor, start - 1);
+    let mut best = None;
+    while low <= high {
+        let quality = low + (high - low) / 2;
+        conversion.quality = Some(quality);
+        options.processor.convert(input, output_path, conversion)?;
+        if size()? <= max {
+            best = Some(quality);
+            low = quality + 1;
+        } else {
+            high = quality - 1;
+        }
+    }
+    match best {
+        Some(quality) => {
+            // The last attempt may have been too large.
+            if conversion.quality != Some(quality) {
+                conversion.quality = Some(quality);
+                options.processor.convert(input, output_path, conversion)?;
+            }
+            info!(
+                "encoded {} at quality {quality} to fit in {max} bytes",
+                output_path.display()
+            );
+        }
+        None => {
+            if conversion.quality != Some(floor) {
+                conversion.quality = Some(floor);
+                options.processor.convert(input, output_path, conversion)?;
+            }
+            warn!(
+                "{} is {} bytes even at quality {floor}, more than {max}",
+                output_path.display(),
+                size()?
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Interpolates linearly between `max` for images up to 1 megapixel and `min`
+/// for those of 24 megapixels or more.
+fn quality_for_size(pixels: f64, (min, max): (u8, u8)) -> u8 {
+    const SMALL: f64 = 1e6;
+    const LARGE: f64 = 24e6;
+    let t = ((pixels - SMALL) / (LARGE - SMALL)).clamp(0.0, 1.0);
+    (f64::from(max) - t * (f64::from(max) - f64::from(min))).round() as u8
+}
+
+fn is_gif(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("gif"))
+}
+
+/// Extensions of the raw files of cameras, which are shown by the JPEG preview
+/// embedded in them.
+const RAW_EXTENSIONS: &[&str] = &[
+    "3fr", "arw", "cr2", "cr3", "crw", "dng", "erf", "nef", "nrw", "orf", "pef", "raf", "rw2",
+    "srw", "x3f",
+];
+
+fn is_raw(path: &Path) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|e| RAW_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Extensions of the videos, which are shown by a poster of their first frame
+/// that links to them.
+const VIDEO_EXTENSIONS: &[&str] = &["m4v", "mov", "mp4", "webm"];
+
+fn is_video(path: &Path) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|e| VIDEO_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Writes the first frame of a video next to the full images, and returns its
+/// path.
+fn generate_poster(
+    video: &Path,
+    name: &OsStr,
+    force: bool,
+    options: &Options,
+) -> Result<PathBuf, String> {
+    let mut file_name = name.to_owned();
+    file_name.push(".jpg");
+    let output_path = options.img_dir.join(file_name);
+    if options.check || !force && is_up_to_date(&output_path, video) {
+        return Ok(output_path);
+    }
+    options
+        .processor
+        .poster(&options.ffmpeg, video, &output_path)?;
+    mark_up_to_date(&output_path, video)?;
+    Ok(output_path)
+}
+
+/// Leaves out the raw files that were also saved as another image, e.g. a JPEG
+/// of the same name by the camera, which is shown instead.
+fn remove_raw_duplicates(paths: &mut Vec<PathBuf>) {
+    let others: HashSet<PathBuf> = paths
+        .iter()
+        .filter(|path| !is_raw(path))
+        .map(|path| path.with_extension(""))
+        .collect();
+    paths.retain(|path| !is_raw(path) || !others.contains(&path.with_extension("")));
+}
+
+/// Whether a raw file of the same name as the photo is next to it, see
+/// [`remove_raw_duplicates`].
+fn has_raw_pair(path: &Path) -> bool {
+    let dir = match path.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        let other = entry.path();
+        other != path && is_raw(&other) && other.file_stem() == path.file_stem()
+    })
+}
+
+/// Whether the photo may have no EXIF metadata, and so no capture time: GIFs
+/// can't have it, and PNGs, like screenshots, rarely do.
+fn may_lack_exif(path: &Path) -> bool {
+    is_gif(path)
+        || path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("png"))
+}
+
+/// Quickly checks that the image is not truncated, which happens when it is
+/// read before it is completely written.
+fn check_image(path: &Path) -> Result<(), String> {
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let mut start = [0; 2];
+    if file.read_exact(&mut start).is_err() {
+        return Err("empty or truncated file".to_owned());
+    }
+    // Other formats are left to the decoder.
+    if start != [0xff, 0xd8] {
+        return Ok(());
+    }
+    // Some cameras pad the file after the end of image marker.
+    let len = file.metadata().map_err(|e| e.to_string())?.len();
+    let tail_len = len.min(4096);
+    let mut tail = vec![0; tail_len as usize];
+    file.seek(SeekFrom::Start(len - tail_len))
+        .and_then(|_| file.read_exact(&mut tail))
+        .map_err(|e| e.to_string())?;
+    if tail.windows(2).any(|w| w == [0xff, 0xd9]) {
+        Ok(())
+    } else {
+        Err("truncated JPEG, the end of image marker is missing".to_owned())
+    }
+}
+
+/// Extension of XMP sidecar files, either replacing or added to that of the photo.
+const XMP: &str = "xmp";
+
+fn xmp_sidecars(path: &Path) -> impl Iterator<Item = PathBuf> {
+    [path.with_added_extension(XMP), path.with_extension(XMP)].into_iter()
+}
+
+/// Extension added to that of a photo for a sidecar file with the degrees to
+/// turn it by clockwise, overriding its orientation.
+const ROTATE: &str = "rotate";
+
+/// Parses an EXIF date and time, `YYYY:MM:DD HH:MM:SS`, also as some cameras
+/// write it: without the seconds, with `-`, `.` or `/` between the parts of the
+/// date, or without the time, which is then taken as midnight.
+fn parse_exif_datetime(s: &str) -> Option<NaiveDateTime> {
+    let (date, time) = s.trim().split_once([' ', 'T']).unwrap_or((s.trim(), ""));
+    let date = NaiveDate::parse_from_str(&date.replace(['-', '.', '/'], ":"), "%Y:%m:%d").ok()?;
+    let time = match time.trim() {
+        "" => NaiveTime::MIN,
+        // Dots only separate the fraction of the seconds if there are colons.
+        time if time.contains(':') => ["%H:%M:%S%.f", "%H:%M"]
+            .into_iter()
+            .find_map(|format| NaiveTime::parse_from_str(time, format).ok())?,
+        time => NaiveTime::parse_from_str(time, "%H.%M.%S").ok()?,
+    };
+    Some(date.and_time(time))
+}
+
+/// Parses an XMP date, which lacks the offset if it is local time.
+fn parse_xmp_date(s: &str, timezone: Option<FixedOffset>) -> Option<(NaiveDateTime, FixedOffset)> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(s) {
+        return Some((datetime.naive_local(), datetime.timezone()));
+    }
+    let datetime = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M"]
+        .into_iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())?;
+    with_local_offset(datetime, timezone)
+}
+
+/// Parses the creation time of a PNG file, in any of the formats in use.
+fn parse_creation_time(
+    s: &str,
+    timezone: Option<FixedOffset>,
+) -> Option<(NaiveDateTime, FixedOffset)> {
+    let s = s.trim();
+    if let Ok(datetime) = DateTime::parse_from_rfc2822(s) {
+        return Some((datetime.naive_local(), datetime.timezone()));
+    }
+    parse_xmp_date(s, timezone).or_else(|| {
+        let datetime = ["%Y:%m:%d %H:%M:%S", "%Y-%m-%d %H:%M:%S"]
+            .into_iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())?;
+        with_local_offset(datetime, timezone)
+    })
+}
+
+/// Pairs a date and time without an offset with `timezone`, or else the local
+/// one at that time.
+fn with_local_offset(
+    datetime: NaiveDateTime,
+    timezone: Option<FixedOffset>,
+) -> Option<(NaiveDateTime, FixedOffset)> {
+    if let Some(timezone) = timezone {
+        return Some((datetime, timezone));
+    }
+    let local = Local.from_local_datetime(&datetime).earliest()?;
+    Some((datetime, local.fixed_offset().timezone()))
+}
+
+/// Keyword, or extension of an empty sidecar file, marking a photo as unlisted.
+const UNLISTED: &str = "unlisted";
+const COVER: &str = "cover";
+
+fn content_hash(path: &Path) -> Result<String, Error> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path).at(path)?, &mut hasher).at(path)?;
+    Ok(format!("{:x}", hasher.finalize())[..16].to_owned())
+}
+
+/// Links `path` under a name with its content hash, e.g. `IMG_0001.jpg` to
+/// `IMG_0001.a1b2c3d4.jpg`, and returns that. Hashed names of previous
+/// versions are removed.
+fn hashed_asset(path: PathBuf) -> Result<PathBuf, Error> {
+    let hash = &content_hash(&path)?[..8];
+    let extension = path.extension().map(OsStr::to_owned).unwrap_or_default();
+    let mut hashed_extension = OsString::from(format!("{hash}."));
+    hashed_extension.push(&extension);
+    let hashed = path.with_extension(hashed_extension);
+    if hashed.exists() {
+        return Ok(hashed);
+    }
+    let dir = path.parent().unwrap();
+    for entry in fs::read_dir(dir).at(dir)? {
+        let other = entry.at(dir)?.path();
+        if logical_path(&other).is_some_and(|logical| logical == path) {
+            fs::remove_file(&other).at(&other)?;
+        }
+    }
+    if fs::hard_link(&path, &hashed).is_err() {
+        fs::copy(&path, &hashed).at(&hashed)?;
+    }
+    Ok(hashed)
+}
+
+/// Returns the name a [`hashed_asset`] was linked from, if `path` is one.
+fn logical_path(path: &Path) -> Option<PathBuf> {
+    let stem = Path::new(path.file_stem()?);
+    let hash = stem.extension()?.to_str()?;
+    if hash.len() != 8 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut logical = path.with_file_name(stem.file_stem()?);
+    if let Some(extension) = path.extension() {
+        logical.set_extension(extension);
+    }
+    Some(logical)
+}
+
+/// Lists the hashed name of every image by the name it was generated under.
+fn write_asset_manifest(options: &Options, photos: &[Photo]) -> Result<(), Error> {
+    let mut entries: Vec<String> = photos
+        .iter()
+        .flat_map(|p| {
+            [&p.thumbnail_path, &p.img_path]
+                .into_iter()
+                .chain(p.thumbnail_variants.iter().map(|(_, path)| path))
+                .chain(p.source_thumbnails())
+        })
+        .filter_map(|hashed| {
+            let logical = logical_path(hashed)?;
+            Some(format!(
+                "  \"{}\": \"{}\"",
+                escape_json(&options.relative_str(&logical)),
+                escape_json(&options.relative_str(hashed)),
+            ))
+        })
+        .collect();
+    entries.sort();
+    write_atomically(
+        &options.output_dir.join(ASSET_MANIFEST_PATH),
+        format!("{{\n{}\n}}\n", entries.join(",\n")),
+    )
+}
+
+fn read_encoder_manifest(output_dir: &Path) -> HashMap<String, String> {
+    let Ok(manifest) = fs::read_to_string(output_dir.join(ENCODER_MANIFEST_PATH)) else {
+        return HashMap::new();
+    };
+    match json::parse(&manifest) {
+        Some(json::Value::Object(encoders)) => encoders
+            .into_iter()
+            .filter_map(|(path, encoder)| Some((path, encoder.as_str()?.to_owned())))
+            .collect(),
+        _ => {
+            warn!("ignoring {ENCODER_MANIFEST_PATH}, which is not a JSON object");
+            HashMap::new()
+        }
+    }
+}
+
+/// The modification time and size of a file, which tell whether it changed
+/// since they were recorded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct FileStamp {
+    modified: SystemTime,
+    size: u64,
+}
+
+impl FileStamp {
+    fn of(metadata: &fs::Metadata) -> io::Result<Self> {
+        Ok(Self {
+            modified: metadata.modified()?,
+            size: metadata.len(),
+        })
+    }
+}
+
+/// What a photo was built from and into, see [`BUILD_MANIFEST_PATH`].
+#[derive(Debug)]
+struct Build {
+    stamp: FileStamp,
+    hash: Option<String>,
+    /// Relative to the output directory.
+    outputs: Vec<PathBuf>,
+}
+
+impl Build {
+    fn from_json(value: &json::Value) -> Option<Self> {
+        let json::Value::Object(build) = value else {
+            return None;
+        };
+        let json::Value::List(modified) = build.get("modified")? else {
+            return None;
+        };
+        let [secs, nanos] = &modified[..] else {
+            return None;
+        };
+        let modified =
+            SystemTime::UNIX_EPOCH + Duration::new(secs.as_f64()? as u64, nanos.as_f64()? as u32);
+        let json::Value::List(outputs) = build.get("outputs")? else {
+            return None;
+        };
+        Some(Self {
+            stamp: FileStamp {
+                modified,
+                size: build.get("size")?.as_f64()? as u64,
+            },
+            hash: build.get("hash")?.as_str().map(str::to_owned),
+            outputs: outputs
+                .iter()
+                .map(|output| output.as_str().map(PathBuf::from))
+                .collect::<Option<_>>()?,
+        })
+    }
+
+    fn to_json(&self) -> String {
+        let modified = self
+            .stamp
+            .modified
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        let hash = match &self.hash {
+            Some(hash) => format!("\"{}\"", escape_json(hash)),
+            None => "null".to_owned(),
+        };
+        let outputs = self
+            .outputs
+            .iter()
+            .map(|output| format!("\"{}\"", escape_json(&output.to_string_lossy())))
+            .join(", ");
+        format!(
+            "{{\"modified\": [{}, {}], \"size\": {}, \"hash\": {hash}, \"outputs\": [{outputs}]}}",
+            modified.as_secs(),
+            modified.subsec_nanos(),
+            self.stamp.size
+        )
+    }
+}
+
+fn read_build_manifest(output_dir: &Path) -> HashMap<PathBuf, Build> {
+    let Ok(manifest) = fs::read_to_string(output_dir.join(BUILD_MANIFEST_PATH)) else {
+        return HashMap::new();
+    };
+    match json::parse(&manifest) {
+        Some(json::Value::Object(builds)) => builds
+            .into_iter()
+            .filter_map(|(path, build)| Some((PathBuf::from(path), Build::from_json(&build)?)))
+            .collect(),
+        _ => {
+            warn!("ignoring {BUILD_MANIFEST_PATH}, which is not a JSON object");
+            HashMap::new()
+        }
+    }
+}
+
+/// Writes the [`BUILD_MANIFEST_PATH`] of the photos. Before that, removes the
+/// outputs recorded for photos that are gone or no longer made into them, such
+/// as the permalink pages once `--permalinks` is left out.
+fn write_build_manifest(options: &Options, photos: &[Photo]) -> Result<(), Error> {
+    let mut builds = options.builds.lock().unwrap();
+    let current = photo_outputs(photos, options);
+    for output in builds.values().flat_map(|build| &build.outputs) {
+        let path = options.output_dir.join(output);
+        if !current.contains(&path) && path.is_file() {
+            debug!("removing {}", path.display());
+            fs::remove_file(&path).at(&path)?;
+        }
+    }
+    *builds = photos
+        .iter()
+        .map(|p| {
+            let outputs = photo_outputs(std::slice::from_ref(p), options)
+                .into_iter()
+                .map(|path| options.relative_path(&path).to_owned())
+                .sorted()
+                .collect();
+            let build = Build {
+                stamp: p.stamp,
+                hash: p.hash.clone(),
+                outputs,
+            };
+            (p.original_path.clone(), build)
+        })
+        .collect();
+    let entries: Vec<String> = builds
+        .iter()
+        .map(|(path, build)| {
+            format!(
+                "  \"{}\": {}",
+                escape_json(&path.to_string_lossy()),
+                build.to_json()
+            )
+        })
+        .sorted()
+        .collect();
+    write_atomically(
+        &options.output_dir.join(BUILD_MANIFEST_PATH),
+        format!("{{\n{}\n}}\n", entries.join(",\n")),
+    )
+}
+
+/// Writes the [`ENCODER_MANIFEST_PATH`], without the images that no longer
+/// exist.
+fn write_encoder_manifest(options: &Options) -> Result<(), Error> {
+    let encoders = options.encoders.lock().unwrap();
+    let path = options.output_dir.join(ENCODER_MANIFEST_PATH);
+    if encoders.is_empty() && !path.exists() {
+        return Ok(());
+    }
+    let entries: Vec<String> = encoders
+        .iter()
+        .filter(|(image, _)| options.output_dir.join(image).exists())
+        .map(|(image, encoder)| {
+            format!("  \"{}\": \"{}\"", escape_json(image), escape_json(encoder))
+        })
+        .sorted()
+        .collect();
+    write_atomically(&path, format!("{{\n{}\n}}\n", entries.join(",\n")))
+}
+
+/// Whether `output` was generated from the current version of `input`.
+///
+/// Generated files get the modification time of their input, see
+/// [`mark_up_to_date`]. Comparing for equality instead of ordering is immune to
+/// the granularity of the file system's timestamps and to the clock, and a
+/// modified input always has a different time.
+fn is_up_to_date(output: &Path, input: &Path) -> bool {
+    let modified = |path: &Path| path.metadata().and_then(|m| m.modified()).ok();
+    modified(output).is_some_and(|time| Some(time) == modified(input))
+}
+
+/// Places the original at `output` using the `--originals` strategy.
+fn publish_original(input: &Path, output: &Path, options: &Options) -> Result<PathBuf, Error> {
+    if options.check {
+        return Ok(output.to_owned());
+    }
+    let mut strategy = options.originals;
+    // The location can only be removed from a copy.
+    if options.strip_gps && strategy != Originals::Copy && gps::has_location(input) {
+        strategy = Originals::Copy;
+    }
+    let is_symlink = output.is_symlink();
+    if is_up_to_date(output, input) && is_symlink == (strategy == Originals::Symlink) {
+        return Ok(output.to_owned());
+    }
+    // A link can't be replaced in place, and a previous copy could be a link.
+    let _ = fs::remove_file(output);
+    let linked = match strategy {
+        Originals::Copy => false,
+        Originals::Symlink => symlink(input, output).is_ok(),
+        Originals::Hardlink => fs::hard_link(input, output).is_ok(),
+    };
+    if !linked {
+        if strategy != Originals::Copy {
+            warn!("could not link {}, copying it instead", input.display());
+        }
+        fs::copy(input, output).at(output)?;
+        if options.strip_gps {
+            gps::strip(output).at(output)?;
+        }
+        mark_up_to_date(output, input)?;
+    }
+    return Ok(output.to_owned());
+
+    #[cfg(unix)]
+    fn symlink(input: &Path, output: &Path) -> io::Result<()> {
+        std::os::unix::fs::symlink(fs::canonicalize(input)?, output)
+    }
+
+    #[cfg(not(unix))]
+    fn symlink(_input: &Path, _output: &Path) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+/// A file that is written next to its destination and renamed into place by
+/// [`AtomicFile::commit`], so that it is never seen half-written.
+struct AtomicFile {
+    path: PathBuf,
+    tmp_path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl AtomicFile {
+    fn create(path: &Path) -> Result<Self, Error> {
+        let tmp_path = path.with_added_extension("tmp");
+        let writer = BufWriter::new(File::create(&tmp_path).at(&tmp_path)?);
+        Ok(Self {
+            path: path.to_owned(),
+            tmp_path,
+            writer,
+        })
+    }
+
+    /// Leaves the file untouched if its content is the same, which keeps its
+    /// modification time for tools like rsync.
+    fn commit(self) -> Result<(), Error> {
+        let file = self
+            .writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .at(&self.tmp_path)?;
+        if fs::read(&self.path).ok() == Some(fs::read(&self.tmp_path).at(&self.tmp_path)?) {
+            drop(file);
+            fs::remove_file(&self.tmp_path).at(&self.tmp_path)
+        } else {
+            // On disk before it replaces the old one, so that a crash leaves
+            // either of them whole.
+            file.sync_all().at(&self.tmp_path)?;
+            fs::rename(&self.tmp_path, &self.path).at(&self.path)
+        }
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+fn write_atomically(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), Error> {
+    let mut file = AtomicFile::create(path)?;
+    file.write_all(contents.as_ref()).at(path)?;
+    file.commit()
+}
+
+fn mark_up_to_date(output: &Path, input: &Path) -> Result<(), Error> {
+    let input_time = input.metadata().and_then(|m| m.modified()).at(input)?;
+    File::options()
+        .write(true)
+        .open(output)
+        .and_then(|file| file.set_modified(input_time))
+        .at(output)
+}
+
+fn generate_pwa(options: &Options) -> Result<(), Error> {
+    let icon = match &options.icon {
+        Some(icon) => icon.clone(),
+        None => {
+            let icon = options.output_dir.join("icon.svg");
+            if !icon.exists() {
+                fs::write(&icon, ICON_SVG).at(&icon)?;
+            }
+            icon
+        }
+    };
+    if has_svg_icon(options) {
+        let svg = options.output_dir.join("icon.svg");
+        if svg != icon && !is_up_to_date(&svg, &icon) {
+            fs::copy(&icon, &svg).at(&svg)?;
+            mark_up_to_date(&svg, &icon)?;
+        }
+    }
+
+    // SVG can't be decoded natively, so the others are made from one drawn
+    // like the placeholder instead.
+    let icon = if options.icon.is_none() {
+        let png = options.output_dir.join("icon-512.png");
+        if !png.exists() {
+            if let Err(e) = image::draw_placeholder_icon(512, &png) {
+                error!("{e}");
+            }
+        }
+        png
+    } else {
+        icon
+    };
+    for (name, size) in [
+        ("apple-touch-icon.png", 180),
+        ("icon-192.png", 192),
+        ("icon-512.png", 512),
+        ("favicon.ico", 32),
+    ] {
+        let output_path = options.output_dir.join(name);
+        if output_path == icon || is_up_to_date(&output_path, &icon) {
+            continue;
+        }
+        let conversion = image::Conversion {
+            resize: Some(image::Resize::Pad(size)),
+            filter: Some(options.resize_filter),
+            ..Default::default()
+        };
+        match options.processor.convert(&icon, &output_path, &conversion) {
+            Ok(()) => mark_up_to_date(&output_path, &icon)?,
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    let manifest =
+        format!(
+            r#"{{
+  "name": "{name}",
+  "short_name": "{name}",
+  "icons": [
+    {{ "src": "icon-192.png", "type": "image/png", "sizes": "192x192" }},
+    {{ "src": "icon-512.png", "type": "image/png", "sizes": "512x512" }}
+  ],
+  "start_url": "./{start_url}",
+  "display": "standalone",
+  "theme_color": "{theme_color}",
+  "background_color": "{theme_color}"
+}}
+"#,
+            name = escape_json(&options.title),
+            start_url = INDEX_PATH,
+            theme_color = escape_json(options.theme_color.as_deref().unwrap_or(
+                match options.theme {
+                    Theme::Light | Theme::Auto => LIGHT.background,
+                    Theme::Dark => DARK.background,
+                }
+            )),
+        );
+    write_atomically(&options.output_dir.join("site.webmanifest"), manifest)
+}
+
+fn has_svg_icon(options: &Options) -> bool {
+    match &options.icon {
+        Some(icon) => icon
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("svg")),
+        None => true,
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Breaks up `--`, so that the text can't end the comment it is put in.
+fn escape_comment(s: &str) -> String {
+    let mut escaped = s.to_owned();
+    while escaped.contains("--") {
+        escaped = escaped.replace("--", "- -");
+    }
+    escaped
+}
+
+fn escape_json(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Fills in the page template with `main` and `nav`. `head` is added to the end
+/// of the `<head>` element, and `script` to that of the `<body>`.
+fn render_page(
+    options: &Options,
+    path: &str,
+    preview: Option<&str>,
+    head: &str,
+    main: &str,
+    nav: &str,
+    script: &str,
+) -> String {
+    let root = root_of(path);
+    let theme_color = theme_color_metas(options);
+    let mut links = String::new();
+    if let Some(base_url) = &options.base_url {
+        let url = escape_html(&format!("{base_url}/{path}"));
+        links.push_str(&format!("    <link rel=\"canonical\" href=\"{url}\">\n"));
+    }
+    if let Some(preview) = preview {
+        let url = match &options.base_url {
+            Some(base_url) => format!("{base_url}/{preview}"),
+            None => format!("{root}{preview}"),
+        };
+        let url = escape_html(&url);
+        links.push_str(&format!(
+            "    <meta property=\"og:image\" content=\"{url}\">\n"
+        ));
+    }
+    if options.opds {
+        links.push_str(&format!(
+            "    <link rel=\"alternate\" type=\"{OPDS_TYPE}\" href=\"{root}{OPDS_PATH}\">\n"
+        ));
+    }
+    if options.feed.is_some() {
+        links.push_str(&format!(
+            "    <link rel=\"alternate\" type=\"application/atom+xml\" href=\"{root}{FEED_PATH}\" title=\"{}\">\n",
+            escape_html(&options.title)
+        ));
+    }
+    if options.pwa {
+        links.push_str(&format!(
+            "    <link rel=\"icon\" href=\"{root}favicon.ico\" sizes=\"any\">\n"
+        ));
+        if has_svg_icon(options) {
+            links.push_str(&format!(
+                "    <link rel=\"icon\" href=\"{root}icon.svg\" type=\"image/svg+xml\">\n"
+            ));
+        }
+        links.push_str(&format!(
+            "    <link rel=\"apple-touch-icon\" href=\"{root}apple-touch-icon.png\">\n"
+        ));
+        links.push_str(&format!(
+            "    <link rel=\"manifest\" href=\"{root}site.webmanifest\">\n"
+        ));
+    }
+    options.templates.lock().unwrap().page.render(&[
+        ("lang", &escape_html(&options.lang)),
+        ("title", &escape_html(&options.title)),
+        ("root", &root),
+        ("head", &(links + &theme_color + head)),
+        ("main", main),
+        ("nav", nav),
+        ("script", script),
+    ])
+}
+
+/// Returns the prefix leading from the page at `path` back to the output
+/// directory.
+fn root_of(path: &str) -> String {
+    match path.matches('/').count() {
+        0 => "./".to_owned(),
+        depth => "../".repeat(depth),
+    }
+}
+
+/// Like [`root_of`], but empty for pages in the output directory itself, to
+/// put before the paths relative to it.
+fn up_of(path: &str) -> String {
+    "../".repeat(path.matches('/').count())
+}
+
+/// Like [`render_page`] but only the start, and without references to any other files.
+fn standalone_html_begin(options: &Options, css: &str) -> String {
+    let title = escape_html(&options.title);
+    let lang = escape_html(&options.lang);
+    let theme_color = theme_color_metas(options);
+    format!(
+        r##"
+<!DOCTYPE html>
+<html lang="{lang}">
+
+<head>
+    <meta charset="utf-8">
+    <title>{title}</title>
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+{theme_color}<style>
+{css}</style>
+</head>
+
+"##
+    )
+}
+
+fn theme_color_metas(options: &Options) -> String {
+    match (&options.theme_color, options.theme) {
+        (Some(color), _) => theme_color_meta(&escape_html(color), None),
+        (None, Theme::Light) => theme_color_meta(LIGHT.background, None),
+        (None, Theme::Dark) => theme_color_meta(DARK.background, None),
+        (None, Theme::Auto) => {
+            theme_color_meta(LIGHT.background, Some("(prefers-color-scheme: light)"))
+                + &theme_color_meta(DARK.background, Some("(prefers-color-scheme: dark)"))
+        }
+    }
+}
+
+fn theme_color_meta(color: &str, media: Option<&str>) -> String {
+    let media = media.map(|m| format!(" media=\"{m}\"")).unwrap_or_default();
+    format!("    <meta name=\"theme-color\"{media} content=\"{color}\">\n")
+}
+
+/// The narrowest the columns of the grid get before there are fewer.
+const MIN_COLUMN_WIDTH: u32 = 300;
+
+/// The widest the page gets, as in `style.css`.
+const MAX_PAGE_WIDTH: u32 = 1600;
+
+fn generate_css(options: &Options) -> String {
+    let columns = options.columns;
+    let mut css = match options.theme {
+        Theme::Light => LIGHT.css("light"),
+        Theme::Dark => DARK.css("dark"),
+        Theme::Auto => format!(
+            "{}
+@media (prefers-color-scheme: dark) {{
+{}}}
+",
+            LIGHT.css("light dark"),
+            DARK.css("light dark")
+        ),
+    };
+    css.push('\n');
+    css.push_str(&STYLE_CSS.replace("{columns}", &columns.to_string()));
+    for c in (1..columns).rev() {
+        css.push_str(&format!(
+            "
+@media (max-width: {}px) {{
+    .masonry-grid {{
+        column-count: {c};
+    }}
+}}
+",
+            (c + 1) * MIN_COLUMN_WIDTH
+        ));
+    }
+    if options.layout == Layout::Justified {
+        css.push_str(&JUSTIFIED_CSS.replace("{narrow}", &(2 * MIN_COLUMN_WIDTH).to_string()));
+    }
+    css
+}
+
+/// Rules for `--layout justified`, after those of the columns which it
+/// overrides. On narrow screens each thumbnail takes a row of its own.
+const JUSTIFIED_CSS: &str = "
+.masonry-grid.justified {
+    column-count: auto;
+    margin: 0 -0.25em;
+}
+
+.justified .row {
+    display: flex;
+}
+
+.justified .row > div {
+    box-sizing: border-box;
+    padding: 0 0.25em;
+}
+
+@media (max-width: {narrow}px) {
+    .justified .row {
+        display: block;
+    }
+
+    .justified .row > div {
+        width: auto !important;
+    }
+}
+";
+
+/// Packs thumbnails of these aspect ratios into rows of [`MAX_PAGE_WIDTH`] as
+/// close to `row_height` high as they can be, in order. Returns the width of
+/// each thumbnail in percent of its row, which it fills except for the last.
+fn justify(aspect_ratios: &[f64], row_height: u32) -> Vec<Vec<f64>> {
+    let width = f64::from(MAX_PAGE_WIDTH);
+    let row_height = f64::from(row_height);
+    let mut rows = Vec::new();
+    let mut start = 0;
+    let mut sum = 0.0;
+    for (i, &ratio) in aspect_ratios.iter().enumerate() {
+        if sum > 0.0 && width / (sum + ratio) < row_height {
+            // Ending the row before this thumbnail stretches it less than
+            // squeezing it in would shrink it.
+            let stretched = width / sum - row_height;
+            let squeezed = row_height - width / (sum + ratio);
+            if stretched <= squeezed {
+                rows.push(
+                    aspect_ratios[start..i]
+                        .iter()
+                        .map(|r| r / sum * 100.0)
+                        .collect(),
+                );
+                start = i;
+                sum = 0.0;
+            }
+        }
+        sum += ratio;
+        if width / sum <= row_height {
+            rows.push(
+                aspect_ratios[start..=i]
+                    .iter()
+                    .map(|r| r / sum * 100.0)
+                    .collect(),
+            );
+            start = i + 1;
+            sum = 0.0;
+        }
+    }
+    if start < aspect_ratios.len() {
+        let scale = row_height / width * 100.0;
+        rows.push(aspect_ratios[start..].iter().map(|r| r * scale).collect());
+    }
+    rows
+}
+
+/// The `sizes` of the thumbnails: how wide a column is at each width of the
+/// window, following [`generate_css`] and the maximum width of the page.
+fn thumbnail_sizes(options: &Options) -> String {
+    let columns = options.columns;
+    (1..columns)
+        .map(|c| {
+            format!(
+                "(max-width: {}px) {}vw",
+                (c + 1) * MIN_COLUMN_WIDTH,
+                100 / c
+            )
+        })
+        .chain([
+            format!("(max-width: {MAX_PAGE_WIDTH}px) {}vw", 100 / columns),
+            format!("{}px", MAX_PAGE_WIDTH / columns),
+        ])
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Wall time spent in each phase of generation.
+#[derive(Default)]
+struct Timings {
+    scan: Duration,
+    metadata: Duration,
+    images: Duration,
+    html: Duration,
+}
+
+impl Timings {
+    fn report(&self) {
+        let phases = [
+            ("scanning", self.scan),
+            ("metadata", self.metadata),
+            ("images", self.images),
+            ("html", self.html),
+        ];
+        let total: Duration = phases.iter().map(|(_, d)| *d).sum();
+        let breakdown = phases
+            .iter()
+            .filter(|(_, d)| !d.is_zero())
+            .map(|(name, d)| format!("{name} {:.2}s", d.as_secs_f64()))
+            .join(", ");
+        info!("generated in {:.2}s ({breakdown})", total.as_secs_f64());
+    }
+}
+
+/// Writes the pages of a gallery from its photos, see [`generate`].
+pub trait Renderer {
+    fn render(&self, options: &Options, photos: &[Photo]) -> Result<(), Error>;
+}
+
+/// The pages as options ask for, like `photo2html` generates them.
+pub struct Html;
+
+impl Renderer for Html {
+    fn render(&self, options: &Options, photos: &[Photo]) -> Result<(), Error> {
+        render(options, photos).map(|_| ())
+    }
+}
+
+/// Reads the photos of the inputs and generates their images, creating the
+/// output directory.
+pub fn scan(options: &Options) -> Result<Vec<Photo>, Error> {
+    options.create_dirs()?;
+    process_photos(options, &mut Timings::default())
+}
+
+/// Generates the gallery, its pages being written by `renderer`. Returns the
+/// photos in it.
+pub fn generate(options: &Options, renderer: &dyn Renderer) -> Result<Vec<Photo>, Error> {
+    report::reset_error_count();
+    let mut timings = Timings::default();
+    options.create_dirs()?;
+    let photos = process_photos(options, &mut timings)?;
+    if photos.is_empty() && options.noop_on_empty {
+        info!("nothing to do");
+        return Ok(photos);
+    }
+    let start = Instant::now();
+    renderer.render(options, &photos)?;
+    timings.html = start.elapsed();
+    timings.report();
+    Ok(photos)
+}
+
+/// Goes through the generation without writing anything, and exits with an
+/// error if any photo or output would be missing or wrong.
+fn check(options: &Options) {
+    report::reset_error_count();
+    let photos = process_photos(options, &mut Timings::default()).unwrap_or_else(|e| {
+        error!("{e}");
+        process::exit(1);
+    });
+
+    let mut outputs: HashMap<&Path, &Path> = HashMap::new();
+    for p in &photos {
+        for output in [&p.thumbnail_path, &p.img_path]
+            .into_iter()
+            .chain(p.thumbnail_variants.iter().map(|(_, path)| path))
+            .chain(p.source_thumbnails())
+            .chain(&p.poster_path)
+            .chain(&p.published_path)
+        {
+            if let Some(other) = outputs.insert(output, &p.original_path) {
+                if other != p.original_path {
+                    error!(
+                        "{} and {} would both be written to {}",
+                        other.display(),
+                        p.original_path.display(),
+                        output.display()
+                    );
+                }
+            }
+        }
+    }
+
+    let photos_by_day = split_large_days(group_photos(&photos, options), options);
+    let pages = paginate(&photos_by_day, options);
+    let paginated = pages.iter().map(|page| page.len()).sum::<usize>();
+    if paginated != photos_by_day.len() {
+        error!(
+            "{paginated} of {} groups would be on the pages",
+            photos_by_day.len()
+        );
+    }
+
+    // As the dry run of removing what is stale.
+    if options.output_dir.is_dir() {
+        match stale_outputs(options, &photos, &page_paths(&pages, options)) {
+            Ok(stale) => {
+                for path in stale {
+                    info!("would remove {}", path.display());
+                }
+            }
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    let inputs = photos.iter().map(|p| &p.original_path);
+    let files = [&options.css, &options.icon, &options.copy_assets];
+    for path in inputs.chain(files.into_iter().flatten()) {
+        if !path.exists() {
+            error!("{} does not exist", path.display());
+        }
+    }
+
+    let errors = report::error_count();
+    if errors > 0 {
+        error!("{errors} problems found");
+        process::exit(1);
+    }
+    info!("checked {} photos on {} pages", photos.len(), pages.len());
+}
+
+/// Returns the input directories, and with `--recursive` their subdirectories
+/// too. Each is listed once, even if linked to, so that links to a parent
+/// don't form loops.
+fn input_dirs(options: &Options) -> Result<Vec<PathBuf>, Error> {
+    // The output directory exists by now, as it is created first.
+    let skipped: Vec<PathBuf> = iter::once(&options.output_dir)
+        .chain(&options.templates_dir)
+        .filter_map(|dir| fs::canonicalize(dir).ok())
+        .collect();
+    let mut visited = HashSet::new();
+    let mut dirs = Vec::new();
+    for input in options.inputs.iter().filter(|input| input.is_dir()) {
+        add_dirs(input, 0, options, &skipped, &mut visited, &mut dirs)?;
+    }
+    return Ok(dirs);
+
+    /// `dir` is `depth` levels below its input directory, and `skipped` are
+    /// the canonical paths of the directories not to read.
+    fn add_dirs(
+        dir: &Path,
+        depth: u32,
+        options: &Options,
+        skipped: &[PathBuf],
+        visited: &mut HashSet<PathBuf>,
+        dirs: &mut Vec<PathBuf>,
+    ) -> Result<(), Error> {
+        if !visited.insert(fs::canonicalize(dir).at(dir)?) {
+            return Ok(());
+        }
+        dirs.push(dir.to_owned());
+        if !options.recursive || options.depth.is_some_and(|max| depth >= max) {
+            return Ok(());
+        }
+        let mut subdirs: Vec<PathBuf> = fs::read_dir(dir)
+            .at(dir)?
+            .map(|e| Ok(e.at(dir)?.path()))
+            .filter_ok(|path: &PathBuf| path.is_dir())
+            .filter_ok(|path| options.include_hidden || !is_hidden(path))
+            .filter_ok(|path| !fs::canonicalize(path).is_ok_and(|path| skipped.contains(&path)))
+            .collect::<Result<_, Error>>()?;
+        subdirs.sort();
+        for subdir in subdirs {
+            add_dirs(&subdir, depth + 1, options, skipped, visited, dirs)?;
+        }
+        Ok(())
+    }
+}
+
+fn process_photos(options: &Options, timings: &mut Timings) -> Result<Vec<Photo>, Error> {
+    let start = Instant::now();
+    let mut paths: Vec<PathBuf> = options
+        .inputs
+        .iter()
+        .filter(|input| !input.is_dir())
+        .cloned()
+        .collect();
+    for dir in input_dirs(options)? {
+        let entries: Vec<PathBuf> = fs::read_dir(&dir)
+            .at(&dir)?
+            .map(|e| Ok(e.at(&dir)?.path()))
+            .collect::<Result<_, Error>>()?;
+        paths.extend(
+            entries
+                .into_iter()
+                .filter(|path| options.include_hidden || !is_hidden(path))
+                // Symlinks to files are followed.
+                .filter(|path| !path.is_dir())
+                .filter(|path| {
+                    path.extension().is_none_or(|e| {
+                        e != UNLISTED && e != ROTATE && !e.eq_ignore_ascii_case(XMP)
+                    })
+                })
+                .filter(|path| path.file_name() != Some(OsStr::new(config::FILE_NAME))),
+        );
+    }
+    remove_raw_duplicates(&mut paths);
+    // The order of directory entries is arbitrary, and decides the names of
+    // colliding photos.
+    paths.sort();
+    // Rayon picks the number of CPUs for 0.
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.jobs.map_or(0, |jobs| jobs as usize))
+        .build()
+        .unwrap();
+    if options.skip_duplicates {
+        paths = pool.install(|| remove_duplicates(paths));
+    }
+    timings.scan = start.elapsed();
+
+    let names = output_names(&paths);
+    let start = Instant::now();
+    report::start_bar(paths.len());
+    // In the order of the paths, whichever finishes first.
+    let results: Vec<(Option<Photo>, Timings)> = pool.install(|| {
+        paths
+            .into_par_iter()
+            .zip(names)
+            .map(|(path, name)| {
+                let mut timings = Timings::default();
+                let photo = load_photo(path, &name, options, &mut timings);
+                (photo, timings)
+            })
+            .collect()
+    });
+    report::finish_bar();
+    let (metadata, images) = results.iter().fold(
+        (Duration::ZERO, Duration::ZERO),
+        |(metadata, images), (_, t)| (metadata + t.metadata, images + t.images),
+    );
+    // Photos are processed at the same time, so their times add up to more
+    // than it took, which they are scaled down to.
+    let elapsed = start.elapsed();
+    let busy = metadata + images;
+    let scale = if busy > elapsed {
+        elapsed.as_secs_f64() / busy.as_secs_f64()
+    } else {
+        1.0
+    };
+    timings.metadata += metadata.mul_f64(scale);
+    timings.images += images.mul_f64(scale);
+    let mut photos: Vec<Photo> = results.into_iter().filter_map(|(photo, _)| photo).collect();
+    if let Some(bits) = options.skip_similar {
+        photos = pool.install(|| remove_similar(photos, bits));
+    }
+    trace!("photos: {photos:#?}");
+    Ok(photos)
+}
+
+/// Leaves out the files with the same contents as an earlier one, see
+/// `--skip-duplicates`. Those that can't be read are kept, to be reported when
+/// they are loaded.
+fn remove_duplicates(paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    let hashes: Vec<Option<String>> = paths
+        .par_iter()
+        .map(|path| content_hash(path).ok())
+        .collect();
+    let mut firsts: HashMap<String, usize> = HashMap::new();
+    let mut duplicates = HashSet::new();
+    for (i, hash) in hashes.into_iter().enumerate() {
+        let Some(hash) = hash else {
+            continue;
+        };
+        if let Some(&first) = firsts.get(&hash) {
+            info!(
+                "skipping {}: the same as {}",
+                paths[i].display(),
+                paths[first].display()
+            );
+            duplicates.insert(i);
+        } else {
+            firsts.insert(hash, i);
+        }
+    }
+    paths
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !duplicates.contains(i))
+        .map(|(_, path)| path)
+        .collect()
+}
+
+/// Leaves out the photos whose thumbnails differ from that of an earlier one in
+/// at most `bits` of their hashes, see `--skip-similar`. The originals are
+/// hashed instead of thumbnails that can't be read, e.g. as nothing was written
+/// with `--check`, and photos that can't be hashed either are kept.
+fn remove_similar(photos: Vec<Photo>, bits: u32) -> Vec<Photo> {
+    let hashes: Vec<Option<u64>> = photos
+        .par_iter()
+        .map(|p| {
+            image::difference_hash(&p.thumbnail_path)
+                .or_else(|_| image::difference_hash(&p.original_path))
+                .ok()
+        })
+        .collect();
+    let mut kept: Vec<(usize, u64)> = Vec::new();
+    let mut similar = HashSet::new();
+    for (i, hash) in hashes.into_iter().enumerate() {
+        let Some(hash) = hash else {
+            continue;
+        };
+        match kept
+            .iter()
+            .find(|(_, other)| (hash ^ other).count_ones() <= bits)
+        {
+            Some(&(first, _)) => {
+                info!(
+                    "skipping {}: looks like {}",
+                    photos[i].original_path.display(),
+                    photos[first].original_path.display()
+                );
+                similar.insert(i);
+            }
+            None => kept.push((i, hash)),
+        }
+    }
+    photos
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !similar.contains(i))
+        .map(|(_, photo)| photo)
+        .collect()
+}
+
+/// Returns whether the file name starts with a dot, like those of caches and
+/// editors' temporary files.
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.as_encoded_bytes().starts_with(b"."))
+}
+
+/// Returns the file names for the outputs of the photos at `paths`. These are
+/// the names of the photos, except for photos from different directories with
+/// the same name, which get the name of their directory appended. They are in
+/// UTF-8, to be linked to, with what isn't replaced.
+fn output_names(paths: &[PathBuf]) -> Vec<OsString> {
+    let lossy = |name: &OsStr| name.to_string_lossy().into_owned();
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for path in paths {
+        *counts.entry(lossy(path.file_name().unwrap())).or_default() += 1;
+    }
+    let mut names: Vec<OsString> = paths
+        .iter()
+        .map(|path| {
+            let name = lossy(path.file_name().unwrap());
+            if counts[&name] == 1 {
+                return name.into();
+            }
+            let mut name = lossy(path.file_stem().unwrap());
+            if let Some(dir) = path.parent().and_then(Path::file_name) {
+                name.push('-');
+                name.push_str(&lossy(dir));
+            }
+            if let Some(extension) = path.extension() {
+                name.push('.');
+                name.push_str(&lossy(extension));
+            }
+            name.into()
+        })
+        .collect();
+    // Directories can have the same name too.
+    let mut seen: HashMap<OsString, usize> = HashMap::new();
+    for name in &mut names {
+        let count = seen.entry(name.clone()).or_default();
+        *count += 1;
+        if *count > 1 {
+            let path = Path::new(name.as_os_str());
+            let mut numbered = path.file_stem().unwrap().to_owned();
+            numbered.push(format!("-{count}"));
+            if let Some(extension) = path.extension() {
+                numbered.push(".");
+                numbered.push(extension);
+            }
+            *name = numbered;
+        }
+    }
+    names
+}
+
+/// Like [`Photo::new`], but skips images that are incomplete or unreadable.
+fn load_photo(
+    path: PathBuf,
+    name: &OsStr,
+    options: &Options,
+    timings: &mut Timings,
+) -> Option<Photo> {
+    let mut result = check_image(&path);
+    if result.is_err() && options.watch {
+        // It may still be being copied.
+        std::thread::sleep(Duration::from_secs(1));
+        result = check_image(&path);
+    }
+    if let Err(e) = &result {
+        warn!("skipping {}: {e}", path.display());
+        report::count(report::Outcome::Failed);
+        return None;
+    }
+    let photo = match Photo::new(path.clone(), name, options, timings) {
+        Ok(Some(photo)) => photo,
+        Ok(None) => {
+            report::count(report::Outcome::Skipped);
+            return None;
+        }
+        // Unlike unreadable files, which may still be being written, its
+        // outputs couldn't be made, which fails the run.
+        Err(e) => {
+            error!("skipping {}: {e}", path.display());
+            report::count(report::Outcome::Failed);
+            return None;
+        }
+    };
+    progress!("processed {}", photo.original_path.display());
+    report::count(report::Outcome::Processed);
+    Some(photo)
+}
+
+/// Writes everything except the images, which are generated by
+/// [`process_photos`]. Returns the number of pages of photos.
+fn render(options: &Options, photos: &[Photo]) -> Result<usize, Error> {
+    let photos_by_day = group_photos(photos, options);
+    let paged = split_large_days(photos_by_day.clone(), options);
+    let pages = paginate(&paged, options);
+    let paths = page_paths(&pages, options);
+    let parts = first_parts(&pages);
+
+    trace!("pages: {pages:#?}");
+
+    if options.json {
+        generate_json(&pages, &paths, options)?;
+    }
+    if options.json_only {
+        remove_stale_outputs(options, photos, &paths)?;
+        if options.hashed_assets {
+            write_asset_manifest(options, photos)?;
+        }
+        write_encoder_manifest(options)?;
+        return Ok(pages.len());
+    }
+
+    if options.pwa {
+        generate_pwa(options)?;
+    }
+    let css = match &options.css {
+        Some(css) => fs::read_to_string(css).at(css)?,
+        None => generate_css(options),
+    };
+    write_atomically(&options.output_dir.join("css").join("style.css"), &css)?;
+
+    let page_len = |page: &[(GroupKey, Vec<&Photo>)]| page.iter().map(|(_, v)| v.len()).sum();
+    let max_page_len: usize = pages.iter().map(|page| page_len(page)).max().unwrap_or(0);
+    // `up` leads from the page the nav is on back to the output directory.
+    let nav_item = |up: &str, (index, page): (usize, &&[(GroupKey, Vec<&Photo>)])| {
+        let (first, _) = page.first().unwrap();
+        let (last, _) = page.last().unwrap();
+        // Days are shown newest first, but their ranges oldest first.
+        let (start, end) = match (first, last) {
+            (GroupKey::Date(_), GroupKey::Date(_)) => (last, first),
+            _ => (first, last),
+        };
+        let text = match (options.paginate_by, first.date()) {
+            (PaginateBy::Month, Some(date)) => options.format(&date, "%B"),
+            (PaginateBy::Year, Some(date)) => date.year().to_string(),
+            _ if start != end => format!("{}–{}", start.label(options), end.label(options)),
+            _ => start.label(options),
+        };
+        // Pages starting with the same day are told apart by its part.
+        let text = match parts[index] {
+            1 => escape_html(&text),
+            part => format!("{} ({part})", escape_html(&text)),
+        };
+        let path = &paths[index];
+        let histogram = if options.nav_histogram {
+            let len = page_len(page);
+            format!(
+                "<span class=\"histogram\" style=\"--share: {:.2}\" title=\"{}\"></span>",
+                len as f64 / max_page_len as f64,
+                escape_html(&options.phrases.photos(len))
+            )
+        } else {
+            String::new()
+        };
+        format!("<li><a href=\"{up}{path}\" class=\"page_{index}\">{text}</a>{histogram}</li>\n")
+    };
+    // The pages at `indices`. By month, the months are listed under their
+    // years, the newest of which is expanded.
+    let nav_list = |up: &str, indices: &[usize]| -> String {
+        let item = |&index: &usize| nav_item(up, (index, &pages[index]));
+        if options.paginate_by != PaginateBy::Month {
+            return indices.iter().map(item).collect();
+        }
+        let mut items = String::new();
+        let mut open = " open";
+        for (year, months) in &indices
+            .iter()
+            .chunk_by(|&&index| pages[index][0].0.date().map(|date| date.year()))
+        {
+            let months: String = months.map(item).collect();
+            match year {
+                Some(year) => {
+                    items.push_str(&format!(
+                        "<li><details{open}><summary>{year}</summary>\n<ul class=\"nav\">\n{months}</ul>\n</details></li>\n"
+                    ));
+                    open = "";
+                }
+                None => items.push_str(&months),
+            }
+        }
+        items
+    };
+    // Older pages are still generated, but only linked from the archive.
+    let archived = options
+        .max_pages
+        .map_or(0, |max| pages.len().saturating_sub(max));
+    let listed: Vec<usize> = (archived..pages.len()).rev().collect();
+    let nav_at = |up: &str| {
+        let items: String = iter::once(nav_list(up, &listed))
+            .chain((archived > 0).then(|| {
+                format!(
+                    "<li><a href=\"{up}{ARCHIVE_PATH}\" class=\"archive\">{}</a></li>\n",
+                    options.phrases.archive
+                )
+            }))
+            .chain(options.toc.then(|| {
+                format!(
+                    "<li><a href=\"{up}{TOC_PATH}\" class=\"toc\">{}</a></li>\n",
+                    options.phrases.contents
+                )
+            }))
+            .chain(options.albums.then(|| {
+                format!(
+                    "<li><a href=\"{up}{ALBUMS_PATH}\" class=\"albums\">{}</a></li>\n",
+                    options.phrases.albums
+                )
+            }))
+            .chain(options.tags.then(|| {
+                format!(
+                    "<li><a href=\"{up}{TAGS_PATH}\" class=\"tags\">{}</a></li>\n",
+                    options.phrases.tags
+                )
+            }))
+            .chain(options.map.then(|| {
+                format!(
+                    "<li><a href=\"{up}{MAP_PATH}\" class=\"map\">{}</a></li>\n",
+                    options.phrases.map
+                )
+            }))
+            .chain(options.on_this_day.then(|| {
+                format!(
+                    "<li><a href=\"{up}{ON_THIS_DAY_PATH}\" class=\"on_this_day\">{}</a></li>\n",
+                    options.phrases.on_this_day
+                )
+            }))
+            .collect();
+        options
+            .templates
+            .lock()
+            .unwrap()
+            .nav
+            .render(&[("items", &items)])
+    };
+    let nav = nav_at("");
+
+    if options.stdout {
+        let mut stdout = io::stdout().lock();
+        write_page(
+            &mut stdout,
+            &photos_by_day,
+            false,
+            options,
+            INDEX_PATH,
+            "page_0",
+            None,
+            None,
+            "",
+        )?;
+        stdout.flush().at("stdout")?;
+        return Ok(1);
+    }
+
+    for (index, photos_by_day) in pages.iter().enumerate() {
+        if options.permalinks {
+            for (_, v) in photos_by_day.iter() {
+                for (i, p) in v.iter().enumerate() {
+                    let prev = i.checked_sub(1).map(|i| v[i]);
+                    let next = v.get(i + 1).copied();
+                    generate_permalink(p, options, &paths[index], prev, next)?;
+                }
+            }
+        }
+        let sheet = options
+            .contact_sheet
+            .then(|| generate_contact_sheet(photos_by_day, options, index))
+            .transpose()?;
+        let path = &paths[index];
+        if let Some(dir) = Path::new(path).parent() {
+            let dir = options.output_dir.join(dir);
+            create_dir_all(&dir).at(&dir)?;
+        }
+        generate_page(
+            photos_by_day,
+            parts[index] > 1,
+            options,
+            path,
+            &format!("page_{index}"),
+            None,
+            sheet.as_deref(),
+            &nav_at(&up_of(path)),
+        )?;
+    }
+    remove_stale_outputs(options, photos, &paths)?;
+    // The index is a copy of the newest page, whose canonical link points to the
+    // latter. Pages of months are in the directory of their year, so the links
+    // of the one copied would lead elsewhere from the index.
+    if let Some(newest) = paths.last().filter(|path| !path.contains('/')) {
+        let newest = options.output_dir.join(newest);
+        write_atomically(
+            &options.output_dir.join(INDEX_PATH),
+            fs::read(&newest).at(&newest)?,
+        )?;
+    } else if let Some(newest) = pages.last() {
+        let index = pages.len() - 1;
+        generate_page(
+            newest,
+            parts[index] > 1,
+            options,
+            INDEX_PATH,
+            &format!("page_{index}"),
+            None,
+            None,
+            &nav,
+        )?;
+    } else {
+        generate_page(&[], false, options, INDEX_PATH, "page_0", None, None, &nav)?;
+    }
+
+    if options.toc {
+        generate_toc(&pages, &paths, options, &nav)?;
+    }
+
+    if options.albums {
+        generate_albums(&photos_by_day, options, &nav)?;
+    }
+
+    if options.tags {
+        generate_tags(&photos_by_day, options, &nav)?;
+    }
+
+    if options.map {
+        generate_map(&pages, &paths, options, &nav)?;
+    }
+
+    if options.opds {
+        generate_opds(&photos_by_day, options)?;
+    }
+
+    if let Some(len) = options.feed {
+        generate_feed(&pages, &paths, len, options)?;
+    }
+
+    if archived > 0 {
+        let archive: Vec<usize> = (0..archived).rev().collect();
+        generate_archive(options, &nav_list("", &archive), &nav)?;
+    }
+
+    if options.on_this_day {
+        let today = options.now().date_naive();
+        let on_this_day: Vec<_> = photos_by_day
+            .iter()
+            .filter(|(key, _)| {
+                key.date()
+                    .is_some_and(|date| (date.month(), date.day()) == (today.month(), today.day()))
+            })
+            .cloned()
+            .collect();
+        generate_page(
+            &on_this_day,
+            false,
+            options,
+            ON_THIS_DAY_PATH,
+            "on_this_day",
+            None,
+            None,
+            &nav,
+        )?;
+    }
+
+    if options.hashed_assets {
+        write_asset_manifest(options, photos)?;
+    }
+    write_encoder_manifest(options)?;
+    write_build_manifest(options, photos)?;
+
+    // After the pages and icons, so that the assets can replace them.
+    if let Some(dir) = &options.copy_assets {
+        copy_assets(dir, &options.output_dir)?;
+    }
+    if let Some(path) = &options.single_file {
+        generate_single_file(&photos_by_day, options, &css, path)?;
+    }
+    if let Some(path) = &options.pdf {
+        generate_pdf(&photos_by_day, options, path)?;
+    }
+    if let Some(path) = &options.archive {
+        write_archive(options, path)?;
+    }
+    // An empty gallery still has its index.
+    Ok(pages.len().max(1))
+}
+
+/// Groups the listed photos, sorted within and between the groups as shown.
+fn group_photos<'a>(photos: &'a [Photo], options: &Options) -> Vec<(GroupKey, Vec<&'a Photo>)> {
+    let mut photos_by_day: HashMap<GroupKey, Vec<&Photo>> = HashMap::new();
+
+    for p in photos.iter().filter(|p| !p.unlisted) {
+        photos_by_day
+            .entry(options.group_by.key(p, options))
+            .or_default()
+            .push(p);
+    }
+
+    for v in photos_by_day.values_mut() {
+        match options.sort {
+            Sort::Datetime => v.sort_by(|a, b| {
+                b.sort_datetime
+                    .cmp(&a.sort_datetime)
+                    .then_with(|| a.cmp_filename(b))
+            }),
+            Sort::Filename => v.sort_by(|a, b| a.cmp_filename(b)),
+        }
+    }
+
+    if options.sort == Sort::Datetime && options.group_by == GroupBy::Day {
+        validate_timestamps(&photos_by_day, options);
+    }
+
+    let mut photos_by_day: Vec<_> = photos_by_day.into_iter().collect();
+    photos_by_day.sort_by(|(a, _), (b, _)| a.display_cmp(b));
+    if options.undated_position == Position::Top
+        && photos_by_day
+            .last()
+            .is_some_and(|(key, _)| *key == GroupKey::Undated)
+    {
+        photos_by_day.rotate_right(1);
+    }
+
+    trace!("groups: {photos_by_day:#?}");
+    photos_by_day
+}
+
+/// Splits the groups into pages of about `--per-page` photos, or by
+/// `--paginate-by`, without splitting a group.
+fn paginate<'a, 'b>(
+    photos_by_day: &'a [(GroupKey, Vec<&'b Photo>)],
+    options: &Options,
+) -> Vec<&'a [(GroupKey, Vec<&'b Photo>)]> {
+    let period = |key: &GroupKey| {
+        let date = key.date()?;
+        Some((date.year(), date.month()))
+    };
+    match options.paginate_by {
+        PaginateBy::Count => {}
+        PaginateBy::Month => {
+            return photos_by_day
+                .chunk_by(|a, b| period(&a.0) == period(&b.0))
+                .rev()
+                .collect()
+        }
+        PaginateBy::Year => {
+            let year = |key: &GroupKey| period(key).map(|(year, _)| year);
+            return photos_by_day
+                .chunk_by(|a, b| year(&a.0) == year(&b.0))
+                .rev()
+                .collect();
+        }
+    }
+    let per_page = options.per_page;
+    // Pages are filled and numbered starting from the oldest photos. So adding
+    // photos, which are usually the newest, keeps the photos on existing pages
+    // the same: a change to a day only moves the boundaries of the pages with
+    // newer days.
+    let mut pages: Vec<&[(GroupKey, Vec<&Photo>)]> = Vec::new();
+    let mut end = photos_by_day.len();
+    let mut page_num_photo = 0;
+    for (i, (_, v)) in photos_by_day.iter().enumerate().rev() {
+        if page_num_photo > 0 && page_num_photo + v.len() > per_page {
+            pages.push(&photos_by_day[i + 1..end]);
+            end = i + 1;
+            page_num_photo = 0;
+        }
+        page_num_photo += v.len();
+    }
+    if end > 0 {
+        pages.push(&photos_by_day[..end]);
+    }
+    pages
+}
+
+/// Splits the groups with more than `--per-page` photos with
+/// `--split-large-days`, into parts that [`paginate`] puts on pages of their
+/// own. Like the pages, the parts are filled starting from the oldest photos.
+fn split_large_days<'a>(
+    photos_by_day: Vec<(GroupKey, Vec<&'a Photo>)>,
+    options: &Options,
+) -> Vec<(GroupKey, Vec<&'a Photo>)> {
+    if !options.split_large_days || options.paginate_by != PaginateBy::Count {
+        return photos_by_day;
+    }
+    photos_by_day
+        .into_iter()
+        .flat_map(|(key, v)| {
+            v.rchunks(options.per_page)
+                .rev()
+                .map(|part| (key.clone(), part.to_vec()))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Returns which part of its group the first group of each page is, counting
+/// from 1 in the order shown. Only groups split by [`split_large_days`] have
+/// several parts, the following of which start the next pages.
+fn first_parts(pages: &[&[(GroupKey, Vec<&Photo>)]]) -> Vec<usize> {
+    let mut parts = vec![1; pages.len()];
+    // Newer pages are shown first.
+    for index in (0..pages.len().saturating_sub(1)).rev() {
+        let newer = pages[index + 1];
+        if newer.last().unwrap().0 == pages[index][0].0 {
+            parts[index] = if newer.len() == 1 {
+                parts[index + 1] + 1
+            } else {
+                2
+            };
+        }
+    }
+    parts
+}
+
+/// Montages the thumbnails of each day, labelled with their times, into pages
+/// of a PDF file.
+fn generate_pdf(
+    photos_by_day: &[(GroupKey, Vec<&Photo>)],
+    options: &Options,
+    path: &Path,
+) -> Result<(), Error> {
+    let pages_dir = path.with_added_extension("pages.tmp");
+    if pages_dir.exists() {
+        fs::remove_dir_all(&pages_dir).at(&pages_dir)?;
+    }
+    create_dir_all(&pages_dir).at(&pages_dir)?;
+    let mut pages = Vec::new();
+    for (index, (key, v)) in photos_by_day.iter().enumerate() {
+        // A day with more photos than fit on a sheet continues on the next.
+        for (sheet, photos) in v.chunks(30).enumerate() {
+            let tiles = photos
+                .iter()
+                .map(|p| {
+                    let label = match key {
+                        GroupKey::Date(_) => p.datetime.format("%H:%M").to_string(),
+                        GroupKey::All | GroupKey::Field(_) | GroupKey::Undated => {
+                            options.format_date(&p.datetime.date())
+                        }
+                    };
+                    (Some(label), p.thumbnail_path.as_path())
+                })
+                .collect();
+            let montage = image::Montage {
+                title: Some(key.label(options)),
+                tiles,
+                columns: 5,
+                size: 256,
+                spacing: 12,
+            };
+            let page = pages_dir.join(format!("{index:05}-{sheet:03}.png"));
+            if let Err(e) = options.processor.montage(&montage, &page) {
+                error!("{e}");
+            }
+            pages.push(page);
+        }
+    }
+
+    let tmp_path = path.with_added_extension("tmp");
+    match options.processor.pdf(&pages, 150, &tmp_path) {
+        Ok(()) => fs::rename(&tmp_path, path).at(path)?,
+        Err(e) => error!("{e}"),
+    }
+    fs::remove_dir_all(&pages_dir).at(&pages_dir)
+}
+
+/// Copies the files in `dir` and its subdirectories into `output_dir`, unless
+/// they are up to date.
+fn copy_assets(dir: &Path, output_dir: &Path) -> Result<(), Error> {
+    for entry in fs::read_dir(dir).at(dir)? {
+        let path = entry.at(dir)?.path();
+        let output_path = output_dir.join(path.file_name().unwrap());
+        if path.is_dir() {
+            if !output_path.exists() {
+                create_dir_all(&output_path).at(&output_path)?;
+            }
+            copy_assets(&path, &output_path)?;
+        } else if !is_up_to_date(&output_path, &path) {
+            fs::copy(&path, &output_path).at(&output_path)?;
+            mark_up_to_date(&output_path, &path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns every file in the output directory and its subdirectories, sorted.
+fn output_files(options: &Options) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    let mut dirs = vec![options.output_dir.clone()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir).at(&dir)? {
+            let entry = entry.at(&dir)?;
+            if entry.file_type().at(entry.path())?.is_dir() {
+                dirs.push(entry.path());
+            } else {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Zips everything in the output directory, with paths relative to it.
+fn write_archive(options: &Options, path: &Path) -> Result<(), Error> {
+    let files = output_files(options)?;
+    let archive = fs::canonicalize(path).ok();
+    let tmp_path = path.with_added_extension("tmp");
+    let mut zip = zip::ZipWriter::new(BufWriter::new(File::create(&tmp_path).at(&tmp_path)?));
+    for file in files {
+        let name = file.file_name().unwrap();
+        if name == MARKER
+            || name == DEPLOY_MANIFEST_PATH
+            || file.extension().is_some_and(|e| e == "tmp")
+            || fs::canonicalize(&file).ok() == archive
+        {
+            continue;
+        }
+        let relative = file.strip_prefix(&options.output_dir).unwrap();
+        let name = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .join("/");
+        // Images are compressed already.
+        let method = match file.extension().and_then(|e| e.to_str()) {
+            Some("jpg" | "jpeg" | "webp" | "avif" | "png" | "gif" | "ico") => {
+                zip::CompressionMethod::Stored
+            }
+            _ => zip::CompressionMethod::Deflated,
+        };
+        let file_options = zip::write::SimpleFileOptions::default().compression_method(method);
+        zip.start_file(name, file_options)
+            .map_err(io::Error::from)
+            .at(&tmp_path)?;
+        // Links to the originals are followed.
+        io::copy(&mut File::open(&file).at(&file)?, &mut zip).at(&tmp_path)?;
+    }
+    zip.finish()
+        .map_err(io::Error::from)
+        .and_then(|mut writer| writer.flush())
+        .at(&tmp_path)?;
+    fs::rename(&tmp_path, path).at(path)
+}
+
+/// What the photos are grouped by, each group has its own heading.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum GroupKey {
+    Date(NaiveDate),
+    /// A single group of all photos.
+    All,
+    /// The value of a setting, see `--group-by`, unset for photos without it.
+    Field(Option<String>),
+    /// The photos without a date, see `--group-empty-as`.
+    Undated,
+}
+
+impl GroupKey {
+    fn date(&self) -> Option<NaiveDate> {
+        match self {
+            GroupKey::Date(date) => Some(*date),
+            GroupKey::All | GroupKey::Field(_) | GroupKey::Undated => None,
+        }
+    }
+
+    /// Orders the groups as shown: days newest first, values alphabetically
+    /// and those without a value last, and the undated photos after the days.
+    /// `--undated-position` is applied by [`group_photos`].
+    fn display_cmp(&self, other: &GroupKey) -> Ordering {
+        match (self, other) {
+            (GroupKey::Undated, GroupKey::Undated) => Ordering::Equal,
+            (GroupKey::Undated, _) => Ordering::Greater,
+            (_, GroupKey::Undated) => Ordering::Less,
+            (GroupKey::Date(a), GroupKey::Date(b)) => b.cmp(a),
+            (GroupKey::Field(a), GroupKey::Field(b)) => match (a, b) {
+                (Some(a), Some(b)) => natural_cmp(a, b),
+                (a, b) => b.is_some().cmp(&a.is_some()),
+            },
+            _ => Ordering::Equal,
+        }
+    }
+
+    fn heading(&self, options: &Options) -> Option<String> {
+        match self {
+            GroupKey::Date(date) => Some(options.format_date(date)),
+            GroupKey::All => None,
+            GroupKey::Undated => Some(options.phrases.undated.to_owned()),
+            GroupKey::Field(value) => Some(
+                value
+                    .clone()
+                    .unwrap_or_else(|| options.phrases.unknown.to_owned()),
+            ),
+        }
+    }
+
+    /// Returns the `id` of the group's heading with `--toc`, e.g. `2023-08-15`.
+    fn anchor(&self) -> Option<String> {
+        match self {
+            GroupKey::Date(date) => Some(date.format("%Y-%m-%d").to_string()),
+            GroupKey::All => None,
+            GroupKey::Undated => Some("undated".to_owned()),
+            GroupKey::Field(value) => Some(
+                value
+                    .as_deref()
+                    .unwrap_or("unknown")
+                    .chars()
+                    .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Returns the `<h2>` of the group, showing `text` instead of the heading
+    /// if given. Days are marked up as `<time>`.
+    fn heading_html(&self, options: &Options, text: Option<&str>) -> Option<String> {
+        let heading = self.heading(options)?;
+        let id = match self.anchor() {
+            Some(anchor) if options.toc => format!(" id=\"{}\"", escape_html(&anchor)),
+            _ => String::new(),
+        };
+        let mut html = escape_html(text.unwrap_or(&heading));
+        if let Some(date) = self.date() {
+            html = format!(
+                "<time datetime=\"{}\">{html}</time>",
+                date.format("%Y-%m-%d")
+            );
+        }
+        Some(match text {
+            Some(_) => format!("<h2{id} title=\"{}\">{html}</h2>", escape_html(&heading)),
+            None => format!("<h2{id}>{html}</h2>"),
+        })
+    }
+
+    fn label(&self, options: &Options) -> String {
+        self.heading(options)
+            .unwrap_or_else(|| options.phrases.all_photos.to_owned())
+    }
+}
+
+/// Names the day relative to today, if it is recent enough.
+fn relative_day(date: NaiveDate, options: &Options) -> Option<&'static str> {
+    let phrases = options.phrases;
+    let today = options.now().date_naive();
+    if date == today {
+        Some(phrases.today)
+    } else if today.pred_opt() == Some(date) {
+        Some(phrases.yesterday)
+    } else {
+        None
+    }
+}
+
+const INDEX_PATH: &str = "index.html";
+const ON_THIS_DAY_PATH: &str = "on-this-day.html";
+const ARCHIVE_PATH: &str = "archive.html";
+const TOC_PATH: &str = "toc.html";
+const ALBUMS_PATH: &str = "albums.html";
+const TAGS_PATH: &str = "tags.html";
+const MAP_PATH: &str = "map.html";
+const OPDS_PATH: &str = "opds.xml";
+const FEED_PATH: &str = "atom.xml";
+const JSON_PATH: &str = "gallery.json";
+const OPDS_TYPE: &str = "application/atom+xml;profile=opds-catalog;kind=acquisition";
+
+/// Warns about timestamps that are likely caused by wrong EXIF metadata, such as
+/// a wrong offset. The output is not affected.
+fn validate_timestamps(photos_by_day: &HashMap<GroupKey, Vec<&Photo>>, options: &Options) {
+    let now = Local::now().naive_local();
+    let mut seen: HashMap<NaiveDateTime, &Path> = HashMap::new();
+    for (key, photos) in photos_by_day {
+        for p in photos {
+            if p.datetime > now {
+                warn!(
+                    "{} was taken in the future ({})",
+                    p.original_path.display(),
+                    p.datetime
+                );
+            }
+            if let Some(other) = seen.insert(p.utc_datetime(), &p.original_path) {
+                warn!(
+                    "{} and {} have the same capture time ({})",
+                    other.display(),
+                    p.original_path.display(),
+                    p.datetime
+                );
+            }
+        }
+
+        // Photos are sorted by local time, but with differing offsets a single
+        // day can span more than 24 hours of real time.
+        let (earliest, latest) = photos
+            .iter()
+            .map(|p| p.utc_datetime())
+            .minmax()
+            .into_option()
+            .unwrap();
+        if latest - earliest > TimeDelta::hours(24) {
+            let offsets = photos.iter().map(|p| p.offset).unique().join(", ");
+            warn!(
+                "photos on {} span {} hours (offsets: {offsets})",
+                key.label(options),
+                (latest - earliest).num_hours()
+            );
+        }
+    }
+}
+
+/// Montages the thumbnails of a page into a single image and returns its path
+/// relative to the output directory.
+fn generate_contact_sheet(
+    photos_by_day: &[(GroupKey, Vec<&Photo>)],
+    options: &Options,
+    index: usize,
+) -> Result<String, Error> {
+    let name = format!("page_{index}-sheet.jpg");
+    let output_path = options.output_dir.join(&name);
+    let thumbnails: Vec<&Path> = photos_by_day
+        .iter()
+        .flat_map(|(_, v)| v.iter().map(|p| p.thumbnail_path.as_path()))
+        .collect();
+
+    // The thumbnails the sheet was made of are recorded next to it, as the set
+    // changes whenever the pagination does.
+    let list_path = output_path.with_extension("txt");
+    let list: String = thumbnails
+        .iter()
+        .map(|t| {
+            let modified = t.metadata().and_then(|m| m.modified()).at(t)?;
+            Ok(format!("{} {:?}\n", t.display(), modified))
+        })
+        .collect::<Result<_, Error>>()?;
+    if output_path.exists() && fs::read_to_string(&list_path).is_ok_and(|l| l == list) {
+        return Ok(name);
+    }
+
+    let montage = image::Montage {
+        title: None,
+        tiles: thumbnails.iter().map(|t| (None, *t)).collect(),
+        columns: 10,
+        size: 128,
+        spacing: 2,
+    };
+    match options.processor.montage(&montage, &output_path) {
+        Ok(()) => fs::write(&list_path, list).at(&list_path)?,
+        Err(e) => error!("{e}"),
+    }
+    Ok(name)
+}
+
+const FILM_STRIP_DIR: &str = "strip";
+const FILM_STRIP_HEIGHT: u32 = 120;
+
+/// Makes the image of a photo in a film strip from its thumbnail, see
+/// `--dense-days`, and returns its path.
+fn generate_film_strip_image(photo: &Photo, options: &Options) -> Result<PathBuf, Error> {
+    let output_path = options
+        .output_dir
+        .join(FILM_STRIP_DIR)
+        .join(photo.thumbnail_path.file_name().unwrap());
+    if !is_up_to_date(&output_path, &photo.thumbnail_path) {
+        let conversion = image::Conversion {
+            strip: true,
+            keep_icc: options.keep_icc,
+            resize: Some(image::Resize::Height(FILM_STRIP_HEIGHT)),
+            filter: Some(options.resize_filter),
+            ..Default::default()
+        };
+        match options
+            .processor
+            .convert(&photo.thumbnail_path, &output_path, &conversion)
+        {
+            Ok(()) => mark_up_to_date(&output_path, &photo.thumbnail_path)?,
+            Err(e) => error!("{e}"),
+        }
+    }
+    Ok(output_path)
+}
+
+/// Writes the page listing the pages left out of the nav by `--max-pages`,
+/// given as the items of a list.
+fn generate_archive(options: &Options, items: &str, nav: &str) -> Result<(), Error> {
+    let main = format!(
+        "<h2>{}</h2>\n<ul class=\"nav archive\">\n{items}</ul>\n",
+        options.phrases.archive
+    );
+    generate_text_page(options, ARCHIVE_PATH, "archive", "", &main, nav)
+}
+
+/// Writes the table of contents, which links every group on its page, see
+/// `--toc`. Days are listed under their year and month.
+fn generate_toc(
+    pages: &[&[(GroupKey, Vec<&Photo>)]],
+    paths: &[String],
+    options: &Options,
+    nav: &str,
+) -> Result<(), Error> {
+    let mut main = format!("<h2>{}</h2>\n", options.phrases.contents);
+    let mut month = None;
+    let mut in_list = false;
+    // The parts of a split day are listed as one, linking its first part.
+    let groups = pages
+        .iter()
+        .enumerate()
+        .rev()
+        .flat_map(|(index, page)| page.iter().map(move |(key, v)| (index, key, v.len())))
+        .coalesce(|a, b| {
+            if a.1 == b.1 {
+                Ok((a.0, a.1, a.2 + b.2))
+            } else {
+                Err((a, b))
+            }
+        });
+    for (index, key, len) in groups {
+        if let Some(date) = key.date() {
+            let year_month = (date.year(), date.month());
+            if month != Some(year_month) {
+                if in_list {
+                    main.push_str("</ul>\n");
+                    in_list = false;
+                }
+                if month.is_none_or(|(year, _)| year != date.year()) {
+                    main.push_str(&format!("<h3>{}</h3>\n", date.year()));
+                }
+                main.push_str(&format!(
+                    "<h4>{}</h4>\n",
+                    escape_html(&options.format(&date, "%B"))
+                ));
+                month = Some(year_month);
+            }
+        }
+        if !in_list {
+            main.push_str("<ul class=\"toc\">\n");
+            in_list = true;
+        }
+        let anchor = key.anchor().map(|a| format!("#{a}")).unwrap_or_default();
+        main.push_str(&format!(
+            "<li><a href=\"{}{anchor}\">{}</a> {}</li>\n",
+            escape_html(&paths[index]),
+            escape_html(&key.label(options)),
+            escape_html(&options.phrases.photos(len))
+        ));
+    }
+    if in_list {
+        main.push_str("</ul>\n");
+    }
+    generate_text_page(options, TOC_PATH, "toc", "", &main, nav)
+}
+
+/// Returns the subdirectory of its input directory that a photo is in, which
+/// is its album with `--albums`.
+fn album(photo: &Photo, options: &Options) -> Option<PathBuf> {
+    let relative = options
+        .inputs
+        .iter()
+        .find_map(|input| photo.original_path.strip_prefix(input).ok())?;
+    let dir = relative.parent()?;
+    (!dir.as_os_str().is_empty()).then(|| dir.to_owned())
+}
+
+/// Writes a page of the photos of each album, and the list of the albums with
+/// their covers, see `--albums`. The photos keep their groups and order.
+fn generate_albums(
+    photos_by_day: &[(GroupKey, Vec<&Photo>)],
+    options: &Options,
+    nav: &str,
+) -> Result<(), Error> {
+    let mut albums: BTreeMap<PathBuf, Vec<(GroupKey, Vec<&Photo>)>> = BTreeMap::new();
+    for (key, v) in photos_by_day {
+        for p in v {
+            let Some(album) = album(p, options) else {
+                continue;
+            };
+            let groups = albums.entry(album).or_default();
+            match groups.last_mut() {
+                Some((last, v)) if last == key => v.push(p),
+                _ => groups.push((key.clone(), vec![p])),
+            }
+        }
+    }
+
+    let mut paths = HashSet::new();
+    let mut main = format!(
+        "<h2>{}</h2>\n<ul class=\"albums\">\n",
+        options.phrases.albums
+    );
+    for (album, groups) in &albums {
+        let name = album.to_string_lossy();
+        // Named after the directory, told apart if that leaves them alike.
+        let slug: String = name
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '-' })
+            .collect();
+        let mut path = format!("album_{slug}.html");
+        for i in 2.. {
+            if paths.insert(path.clone()) {
+                break;
+            }
+            path = format!("album_{slug}-{i}.html");
+        }
+        generate_page(
+            groups,
+            false,
+            options,
+            &path,
+            "albums",
+            Some(&name),
+            None,
+            nav,
+        )?;
+        let photos = groups.iter().flat_map(|(_, v)| v.iter().copied());
+        let len = photos.clone().count();
+        let cover = options.album_cover.choose(photos).unwrap_or(groups[0].1[0]);
+        main.push_str(&format!(
+            "<li><a href=\"{}\"><img src=\"./{}\" alt=\"\" loading=\"lazy\"><span>{}</span></a> {}</li>\n",
+            escape_html(&path),
+            escape_html(&options.relative_str(&cover.thumbnail_path)),
+            escape_html(&name),
+            escape_html(&options.phrases.photos(len))
+        ));
+    }
+    main.push_str("</ul>\n");
+    generate_text_page(options, ALBUMS_PATH, "albums", "", &main, nav)?;
+    remove_stale_pages(options, "album_", &paths)
+}
+
+/// Returns the page of the photos with a keyword, see `--tags`. Spaces become
+/// dashes and other characters that aren't letters or digits their code, so
+/// that keywords don't share a page.
+fn tag_path(tag: &str) -> String {
+    let mut path = String::from("tag_");
+    for c in tag.chars() {
+        match c {
+            c if c.is_alphanumeric() => path.push(c),
+            ' ' => path.push('-'),
+            c => path.push_str(&format!("_{:x}_", u32::from(c))),
+        }
+    }
+    path + ".html"
+}
+
+/// Writes a page of the photos with each keyword, and the list of the keywords
+/// sized by how many photos have them, see `--tags`. The photos keep their
+/// groups and order.
+fn generate_tags(
+    photos_by_day: &[(GroupKey, Vec<&Photo>)],
+    options: &Options,
+    nav: &str,
+) -> Result<(), Error> {
+    let mut tags: BTreeMap<&str, Vec<(GroupKey, Vec<&Photo>)>> = BTreeMap::new();
+    for (key, v) in photos_by_day {
+        for p in v {
+            for tag in &p.keywords {
+                let groups = tags.entry(tag).or_default();
+                match groups.last_mut() {
+                    Some((last, v)) if last == key => v.push(p),
+                    _ => groups.push((key.clone(), vec![p])),
+                }
+            }
+        }
+    }
+
+    let count = |groups: &[(GroupKey, Vec<&Photo>)]| groups.iter().map(|(_, v)| v.len()).sum();
+    let most = tags.values().map(|groups| count(groups)).max().unwrap_or(1);
+    let mut paths = HashSet::new();
+    let mut main = format!("<h2>{}</h2>\n<ul class=\"tags\">\n", options.phrases.tags);
+    for (tag, groups) in &tags {
+        let path = tag_path(tag);
+        generate_page(groups, false, options, &path, "tags", Some(tag), None, nav)?;
+        let len: usize = count(groups);
+        main.push_str(&format!(
+            "<li style=\"--share: {:.2}\"><a href=\"{}\">{}</a> {}</li>\n",
+            len as f64 / most as f64,
+            escape_html(&path),
+            escape_html(tag),
+            escape_html(&options.phrases.photos(len))
+        ));
+        paths.insert(path);
+    }
+    main.push_str("</ul>\n");
+    generate_text_page(options, TAGS_PATH, "tags", "", &main, nav)?;
+    remove_stale_pages(options, "tag_", &paths)
+}
+
+/// Removes the pages whose names start with `prefix` other than `current`, of
+/// albums or keywords that are gone.
+fn remove_stale_pages(
+    options: &Options,
+    prefix: &str,
+    current: &HashSet<String>,
+) -> Result<(), Error> {
+    for entry in fs::read_dir(&options.output_dir).at(&options.output_dir)? {
+        let path = entry.at(&options.output_dir)?.path();
+        let name = path.file_name().unwrap().to_string_lossy();
+        if name.starts_with(prefix) && name.ends_with(".html") && !current.contains(&*name) {
+            debug!("removing {}", path.display());
+            fs::remove_file(&path).at(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the map of the photos with a location, see `--map`. The markers are
+/// embedded as JSON and placed by Leaflet, which opens each photo's thumbnail,
+/// linking to the photo and to it among the others of its day.
+fn generate_map(
+    pages: &[&[(GroupKey, Vec<&Photo>)]],
+    paths: &[String],
+    options: &Options,
+    nav: &str,
+) -> Result<(), Error> {
+    let markers = pages
+        .iter()
+        .enumerate()
+        .flat_map(|(index, page)| {
+            page.iter()
+                .flat_map(move |(key, v)| v.iter().map(move |p| (index, key, p)))
+        })
+        .filter_map(|(index, key, p)| {
+            let (lat, lon) = p.location?;
+            let href = if options.permalinks {
+                permalink_path(p)
+            } else {
+                options.relative_str(&p.img_path).into_owned()
+            };
+            Some(format!(
+                "{{\"lat\": {lat:.6}, \"lon\": {lon:.6}, \"href\": \"{}\", \"thumbnail\": \"{}\", \"label\": \"{}\", \"page\": \"{}#{}\", \"day\": \"{}\"}}",
+                escape_json(&href),
+                escape_json(&options.relative_str(&p.thumbnail_path)),
+                escape_json(&p.label),
+                escape_json(&paths[index]),
+                escape_json(&p.id),
+                escape_json(&key.label(options)),
+            ))
+        })
+        .join(",\n");
+    // Strings in a script can't contain its end tag.
+    let json = |s: &str| format!("\"{}\"", escape_json(s)).replace('<', "\\u003c");
+    let main = format!(
+        r#"<h2>{}</h2>
+<div id="map"></div>
+<script>
+const photos = [
+{}
+];
+const map = L.map("map");
+L.tileLayer({}, {{ attribution: {}, maxZoom: 19 }}).addTo(map);
+for (const photo of photos) {{
+    const link = document.createElement("a");
+    link.href = photo.href;
+    const img = document.createElement("img");
+    img.src = photo.thumbnail;
+    img.alt = photo.label;
+    img.width = 192;
+    link.append(img);
+    // Back to where the photo is among the others of its day.
+    const day = document.createElement("a");
+    day.href = photo.page;
+    day.textContent = photo.day;
+    const popup = document.createElement("div");
+    popup.append(link, document.createElement("br"), day);
+    L.marker([photo.lat, photo.lon]).bindPopup(popup).addTo(map);
+}}
+if (photos.length > 0) {{
+    map.fitBounds(photos.map((photo) => [photo.lat, photo.lon]), {{ maxZoom: 15 }});
+}} else {{
+    map.setView([0, 0], 1);
+}}
+</script>
+"#,
+        options.phrases.map,
+        markers.replace('<', "\\u003c"),
+        json(&options.map_tiles),
+        json(&options.map_attribution),
+    );
+    generate_text_page(options, MAP_PATH, "map", LEAFLET, &main, nav)
+}
+
+/// Writes the Atom feed of the newest `len` photos, see `--feed`. Each entry
+/// links the photo on its page, with Media RSS elements for its thumbnail and
+/// full image.
+fn generate_feed(
+    pages: &[&[(GroupKey, Vec<&Photo>)]],
+    paths: &[String],
+    len: usize,
+    options: &Options,
+) -> Result<(), Error> {
+    const RFC_3339: &str = "%Y-%m-%dT%H:%M:%S%:z";
+    let url = |path: &str| {
+        escape_html(&match &options.base_url {
+            Some(base_url) => format!("{base_url}/{path}"),
+            None => path.to_owned(),
+        })
+    };
+    let file_url = |path: &Path| url(&options.relative_str(path));
+    let size = |dimensions: Option<(u32, u32)>| match dimensions {
+        Some((width, height)) => format!(" width=\"{width}\" height=\"{height}\""),
+        None => String::new(),
+    };
+    let photos: Vec<(&str, &Photo)> = pages
+        .iter()
+        .zip(paths)
+        .flat_map(|(page, path)| {
+            page.iter()
+                .flat_map(move |(_, v)| v.iter().map(move |p| (path.as_str(), *p)))
+        })
+        .sorted_by_key(|(_, p)| std::cmp::Reverse(p.utc_datetime()))
+        .take(len)
+        .collect();
+    let mut entries = String::new();
+    for (page, p) in &photos {
+        let href = url(&format!("{page}#{}", p.id));
+        let id = match &options.base_url {
+            Some(_) => href.clone(),
+            None => format!("urn:photo2html:photo:{}", escape_html(&p.id)),
+        };
+        let medium = if p.poster_path.is_some() {
+            "video"
+        } else {
+            "image"
+        };
+        entries.push_str(&format!(
+            "  <entry>
+    <id>{id}</id>
+    <title>{}</title>
+    <updated>{}</updated>
+    <link rel=\"alternate\" type=\"text/html\" href=\"{href}\"/>
+    <link rel=\"enclosure\" type=\"{}\" href=\"{}\"/>
+    <media:thumbnail url=\"{}\"{}/>
+    <media:content url=\"{}\" type=\"{}\" medium=\"{medium}\"{}/>
+",
+            escape_html(&p.label),
+            p.zoned_datetime().format(RFC_3339),
+            mime_type(&p.thumbnail_path),
+            file_url(&p.thumbnail_path),
+            file_url(&p.thumbnail_path),
+            size(p.thumbnail_dimensions(options)),
+            file_url(&p.img_path),
+            mime_type(&p.img_path),
+            size(p.img_dimensions(options)),
+        ));
+        if let Some(caption) = &p.caption {
+            entries.push_str(&format!(
+                "    <summary type=\"text\">{}</summary>\n",
+                escape_html(caption)
+            ));
+        }
+        entries.push_str("  </entry>\n");
+    }
+    // The feed only changes with its photos.
+    let updated = photos
+        .first()
+        .map_or_else(|| options.now().fixed_offset(), |(_, p)| p.zoned_datetime());
+    let feed = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<feed xmlns=\"http://www.w3.org/2005/Atom\" xmlns:media=\"http://search.yahoo.com/mrss/\">
+  <id>{}</id>
+  <title>{}</title>
+  <updated>{}</updated>
+  <link rel=\"self\" href=\"{}\" type=\"application/atom+xml\"/>
+  <link rel=\"alternate\" href=\"{}\" type=\"text/html\"/>
+{entries}</feed>
+",
+        match &options.base_url {
+            Some(_) => url(FEED_PATH),
+            None => "urn:photo2html:gallery".to_owned(),
+        },
+        escape_html(&options.title),
+        updated.format(RFC_3339),
+        url(FEED_PATH),
+        url(INDEX_PATH),
+    );
+    write_atomically(&options.output_dir.join(FEED_PATH), feed)
+}
+
+/// Writes the description of the gallery for other frontends, see `--json`.
+/// The photos are listed as shown, each with the page and group it is in.
+/// Paths are relative to the output directory. Like the EXIF tags, locations
+/// are left out with `--strip-gps`, unless published by `--map` anyway.
+fn generate_json(
+    pages: &[&[(GroupKey, Vec<&Photo>)]],
+    paths: &[String],
+    options: &Options,
+) -> Result<(), Error> {
+    let string = |s: &str| format!("\"{}\"", escape_json(s));
+    let path = |path: &Path| string(&options.relative_str(path));
+    let or_null = |value: Option<String>| value.unwrap_or_else(|| "null".to_owned());
+    let image = |file: &Path, dimensions: Option<(u32, u32)>| {
+        let (width, height) = match dimensions {
+            Some((width, height)) => (width.to_string(), height.to_string()),
+            None => ("null".to_owned(), "null".to_owned()),
+        };
+        format!(
+            "{{\"path\": {}, \"width\": {width}, \"height\": {height}}}",
+            path(file)
+        )
+    };
+    let object = |fields: &[(String, String)]| {
+        let fields: Vec<String> = fields
+            .iter()
+            .map(|(key, value)| format!("{}: {}", string(key), string(value)))
+            .collect();
+        format!("{{{}}}", fields.join(", "))
+    };
+    let mut entries = Vec::new();
+    // Newest first, as in the nav.
+    for (page, page_path) in pages.iter().zip(paths).rev() {
+        for (key, v) in page.iter() {
+            for p in v {
+                let variants: Vec<String> = p
+                    .thumbnail_variants
+                    .iter()
+                    .map(|(width, file)| {
+                        format!("{{\"path\": {}, \"width\": {width}}}", path(file))
+                    })
+                    .collect();
+                let settings: Vec<(String, String)> = p
+                    .settings
+                    .iter()
+                    .map(|(label, value)| (label.to_string(), value.clone()))
+                    .collect();
+                let keywords: Vec<String> = p.keywords.iter().map(|k| string(k)).collect();
+                entries.push(format!(
+                    "    {{
+      \"id\": {},
+      \"label\": {},
+      \"page\": {},
+      \"group\": {},
+      \"datetime\": {},
+      \"width\": {},
+      \"height\": {},
+      \"thumbnail\": {},
+      \"thumbnail_variants\": [{}],
+      \"image\": {},
+      \"poster\": {},
+      \"original\": {},
+      \"caption\": {},
+      \"location\": {},
+      \"keywords\": [{}],
+      \"settings\": {},
+      \"exif\": {}
+    }}",
+                    string(&p.id),
+                    string(&p.label),
+                    string(page_path),
+                    string(&key.label(options)),
+                    string(
+                        &p.zoned_datetime()
+                            .format("%Y-%m-%dT%H:%M:%S%:z")
+                            .to_string()
+                    ),
+                    or_null(p.dimensions.map(|(width, _)| width.to_string())),
+                    or_null(p.dimensions.map(|(_, height)| height.to_string())),
+                    image(&p.thumbnail_path, p.thumbnail_dimensions(options)),
+                    variants.join(", "),
+                    image(&p.img_path, p.img_dimensions(options)),
+                    or_null(p.poster_path.as_deref().map(path)),
+                    or_null(p.published_path.as_deref().map(path)),
+                    or_null(p.caption.as_deref().map(string)),
+                    or_null(
+                        p.location
+                            .filter(|_| !options.strip_gps || options.map)
+                            .map(|(lat, lon)| format!("{{\"lat\": {lat:.6}, \"lon\": {lon:.6}}}"))
+                    ),
+                    keywords.join(", "),
+                    object(&settings),
+                    object(&p.exif_fields),
+                ));
+            }
+        }
+    }
+    let pages: Vec<String> = paths.iter().rev().map(|path| string(path)).collect();
+    write_atomically(
+        &options.output_dir.join(JSON_PATH),
+        format!(
+            "{{\n  \"title\": {},\n  \"pages\": [{}],\n  \"photos\": [\n{}\n  ]\n}}\n",
+            string(&options.title),
+            pages.join(", "),
+            entries.join(",\n")
+        ),
+    )
+}
+
+/// Writes the OPDS catalog, see `--opds`. It is an Atom feed whose entries are
+/// the groups, newest first, each acquiring the full images of its photos.
+fn generate_opds(
+    photos_by_day: &[(GroupKey, Vec<&Photo>)],
+    options: &Options,
+) -> Result<(), Error> {
+    const RFC_3339: &str = "%Y-%m-%dT%H:%M:%S%:z";
+    let url = |path: &Path| {
+        let path = options.relative_str(path);
+        escape_html(&match &options.base_url {
+            Some(base_url) => format!("{base_url}/{path}"),
+            None => path.into_owned(),
+        })
+    };
+    let id = |suffix: &str| match &options.base_url {
+        Some(base_url) => escape_html(&format!("{base_url}/{OPDS_PATH}{suffix}")),
+        None => format!("urn:photo2html:gallery{}", escape_html(suffix)),
+    };
+    let mut entries = String::new();
+    for (key, v) in photos_by_day {
+        let updated = v.iter().map(|p| p.zoned_datetime()).max().unwrap();
+        let anchor = key.anchor().unwrap_or_else(|| "all".to_owned());
+        entries.push_str(&format!(
+            "  <entry>
+    <id>{}</id>
+    <title>{}</title>
+    <updated>{}</updated>
+    <content type=\"text\">{}</content>
+",
+            id(&format!("#{anchor}")),
+            escape_html(&key.label(options)),
+            updated.format(RFC_3339),
+            escape_html(&options.phrases.photos(v.len()))
+        ));
+        let cover = options
+            .album_cover
+            .choose(v.iter().copied())
+            .unwrap_or(v[0]);
+        entries.push_str(&format!(
+            "    <link rel=\"http://opds-spec.org/image/thumbnail\" href=\"{}\" type=\"{}\"/>\n",
+            url(&cover.thumbnail_path),
+            mime_type(&cover.thumbnail_path)
+        ));
+        for p in v {
+            entries.push_str(&format!(
+                "    <link rel=\"http://opds-spec.org/acquisition\" href=\"{}\" type=\"{}\" title=\"{}\"/>\n",
+                url(&p.img_path),
+                mime_type(&p.img_path),
+                escape_html(&p.label)
+            ));
+        }
+        entries.push_str("  </entry>\n");
+    }
+    let feed = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<feed xmlns=\"http://www.w3.org/2005/Atom\" xmlns:opds=\"http://opds-spec.org/2010/catalog\">
+  <id>{}</id>
+  <title>{}</title>
+  <updated>{}</updated>
+  <link rel=\"self\" href=\"{OPDS_PATH}\" type=\"{OPDS_TYPE}\"/>
+  <link rel=\"start\" href=\"{OPDS_PATH}\" type=\"{OPDS_TYPE}\"/>
+{entries}</feed>
+",
+        id(""),
+        escape_html(&options.title),
+        options.now().format(RFC_3339),
+    );
+    write_atomically(&options.output_dir.join(OPDS_PATH), feed)
+}
+
+const LEAFLET: &str = r#"<link rel="stylesheet" href="https://unpkg.com/leaflet@1.9.4/dist/leaflet.css" integrity="sha256-p4NxAoJBhIIN+hmNHrzRCf9tD/miZyoHS5obTRR9BMY=" crossorigin="">
+<script src="https://unpkg.com/leaflet@1.9.4/dist/leaflet.js" integrity="sha256-20nQCchB9co0qIjJZRGuk2/Z9VM+kNiyxNV1lvTlZBo=" crossorigin=""></script>
+<style>
+#map {
+    height: 75vh;
+}
+</style>
+"#;
+
+/// Writes a page without photos, with `main` as its content. `head` is added
+/// to the end of the `<head>` element.
+fn generate_text_page(
+    options: &Options,
+    path: &str,
+    class: &str,
+    head: &str,
+    main: &str,
+    nav: &str,
+) -> Result<(), Error> {
+    let style = format!(
+        "<style>
+a.{class} {{
+    font-weight: bold;
+    color: var(--muted, gray);
+}}
+</style>
+"
+    );
+    write_atomically(
+        &options.output_dir.join(path),
+        render_page(options, path, None, &(style + head), main, nav, ""),
+    )
+}
+
+fn page_path(index: usize) -> String {
+    format!("page_{index}.html")
+}
+
+/// The photos without a date, with `--paginate-by` month or year.
+const UNDATED_PATH: &str = "undated.html";
+
+/// Returns the path of each page: numbered, or by `--paginate-by` named after
+/// its month like `2024/05.html` or year like `2024.html`.
+fn page_paths(pages: &[&[(GroupKey, Vec<&Photo>)]], options: &Options) -> Vec<String> {
+    pages
+        .iter()
+        .enumerate()
+        .map(
+            |(index, page)| match (options.paginate_by, page[0].0.date()) {
+                (PaginateBy::Count, _) => page_path(index),
+                (PaginateBy::Month, Some(date)) => date.format("%Y/%m.html").to_string(),
+                (PaginateBy::Year, Some(date)) => date.format("%Y.html").to_string(),
+                (_, None) => UNDATED_PATH.to_owned(),
+            },
+        )
+        .collect()
+}
+
+/// Returns the images and permalink pages of photos that are gone, or no
+/// longer included, the pages after the last one, and the film strips and
+/// contact sheets no longer shown.
+fn stale_outputs(
+    options: &Options,
+    photos: &[Photo],
+    pages: &[String],
+) -> Result<Vec<PathBuf>, Error> {
+    let mut current = photo_outputs(photos, options);
+    let film_strip_dir = options.output_dir.join(FILM_STRIP_DIR);
+    if options.dense_days.is_some() {
+        current.extend(
+            photos
+                .iter()
+                .map(|p| film_strip_dir.join(p.thumbnail_path.file_name().unwrap())),
+        );
+    }
+    // Nothing is hashed by `--check`, so the hashed outputs of the same photos
+    // are current.
+    let is_current = |path: &Path| {
+        current.contains(path)
+            || options.check && logical_path(path).is_some_and(|path| current.contains(&path))
+    };
+    let permalink_dir = options
+        .permalinks
+        .then(|| options.output_dir.join(PERMALINK_DIR));
+    // Including those of widths no longer made.
+    let variant_dirs: Vec<PathBuf> = fs::read_dir(&options.thumbnail_dir)
+        .into_iter()
+        .flatten()
+        .map(|entry| Ok(entry.at(&options.thumbnail_dir)?.path()))
+        .filter_ok(|path: &PathBuf| path.is_dir())
+        .collect::<Result<_, Error>>()?;
+    let dirs = [&options.thumbnail_dir, &options.img_dir, &film_strip_dir]
+        .into_iter()
+        .chain(&variant_dirs)
+        .chain(&options.original_dir)
+        .chain(&permalink_dir);
+    let mut stale = Vec::new();
+    for dir in dirs {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries {
+            let path = entry.at(dir)?.path();
+            // Including links to originals that are gone.
+            if !path.is_dir() && !is_current(&path) {
+                stale.push(path);
+            }
+        }
+    }
+    // Including those of months and years, or of another `--paginate-by`.
+    // Those named in other encodings aren't generated.
+    let is_stale = |path: &Path| {
+        options
+            .relative_path(path)
+            .to_str()
+            .is_some_and(|relative| {
+                is_page_path(relative) && !pages.iter().any(|page| page == relative)
+                    || contact_sheet_index(relative)
+                        .is_some_and(|index| !options.contact_sheet || index >= pages.len())
+            })
+    };
+    for entry in fs::read_dir(&options.output_dir).at(&options.output_dir)? {
+        let path = entry.at(&options.output_dir)?.path();
+        if !(path.is_dir() && is_year(path.file_name().unwrap())) {
+            if is_stale(&path) {
+                stale.push(path);
+            }
+            continue;
+        }
+        for entry in fs::read_dir(&path).at(&path)? {
+            let path = entry.at(&path)?.path();
+            if is_stale(&path) {
+                stale.push(path);
+            }
+        }
+    }
+    Ok(stale)
+}
+
+/// Removes the outputs that are stale, see [`stale_outputs`], and the
+/// directories of years left empty.
+fn remove_stale_outputs(
+    options: &Options,
+    photos: &[Photo],
+    pages: &[String],
+) -> Result<(), Error> {
+    for path in stale_outputs(options, photos, pages)? {
+        debug!("removing {}", path.display());
+        fs::remove_file(&path).at(&path)?;
+    }
+    for entry in fs::read_dir(&options.output_dir).at(&options.output_dir)? {
+        let path = entry.at(&options.output_dir)?.path();
+        if path.is_dir()
+            && is_year(path.file_name().unwrap())
+            && fs::read_dir(&path).at(&path)?.next().is_none()
+        {
+            fs::remove_dir(&path).at(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the index of the page that `path`, relative to the output
+/// directory, is the contact sheet of, or of its list of thumbnails, see
+/// [`generate_contact_sheet`].
+fn contact_sheet_index(path: &str) -> Option<usize> {
+    let stem = path
+        .strip_suffix(".jpg")
+        .or_else(|| path.strip_suffix(".txt"))?;
+    stem.strip_prefix("page_")?
+        .strip_suffix("-sheet")?
+        .parse()
+        .ok()
+}
+
+/// Whether `path`, relative to the output directory, is that of a page of
+/// photos, see [`page_paths`].
+fn is_page_path(path: &str) -> bool {
+    let Some(stem) = path.strip_suffix(".html") else {
+        return false;
+    };
+    let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    match stem.split_once('/') {
+        Some((year, month)) => is_year(OsStr::new(year)) && month.len() == 2 && is_number(month),
+        None => {
+            stem.strip_prefix("page_").is_some_and(is_number)
+                || is_year(OsStr::new(stem))
+                || path == UNDATED_PATH
+        }
+    }
+}
+
+/// Whether a file name is that of the page or directory of a year.
+fn is_year(name: &OsStr) -> bool {
+    let name = name.as_encoded_bytes();
+    name.len() == 4 && name.iter().all(u8::is_ascii_digit)
+}
+
+/// `continued` is whether the first group continues from the previous page,
+/// see [`split_large_days`]. `heading` names the page above its photos.
+#[allow(clippy::too_many_arguments)]
+fn generate_page(
+    photos_by_day: &[(GroupKey, Vec<&Photo>)],
+    continued: bool,
+    options: &Options,
+    path: &str,
+    class: &str,
+    heading: Option<&str>,
+    preview: Option<&str>,
+    nav: &str,
+) -> Result<(), Error> {
+    let mut writer = AtomicFile::create(&options.output_dir.join(path))?;
+    write_page(
+        &mut writer,
+        photos_by_day,
+        continued,
+        options,
+        path,
+        class,
+        heading,
+        preview,
+        nav,
+    )?;
+    writer.commit()
+}
+
+/// Writes the page that would be at `path`, see [`generate_page`].
+#[allow(clippy::too_many_arguments)]
+fn write_page<W: Write>(
+    writer: &mut W,
+    photos_by_day: &[(GroupKey, Vec<&Photo>)],
+    continued: bool,
+    options: &Options,
+    path: &str,
+    class: &str,
+    heading: Option<&str>,
+    preview: Option<&str>,
+    nav: &str,
+) -> Result<(), Error> {
+    let is_dense = |v: &[&Photo]| options.dense_days.is_some_and(|n| v.len() > n);
+    // Pages of months are in the directory of their year.
+    let root = root_of(path);
+    let up = up_of(path);
+    // The thumbnails shown first, of the grids that are not collapsed.
+    let eager: Vec<&Photo> = photos_by_day
+        .iter()
+        .filter(|(_, v)| !is_dense(v))
+        .flat_map(|(_, v)| stack_bursts(v, options).into_iter().map(|stack| stack[0]))
+        .take(options.preload_thumbnails.unwrap_or(0))
+        .collect();
+    let mut head = format!(
+        "<style>
+a.{class} {{
+    font-weight: bold;
+    color: var(--muted, gray);
+}}
+</style>
+"
+    );
+    for p in &eager {
+        head.push_str(&format!(
+            "<link rel=\"preload\" as=\"image\" href=\"{root}{}\">\n",
+            escape_html(&options.relative_str(&p.thumbnail_path))
+        ));
+    }
+    if options.lightbox {
+        head.push_str(LIGHTBOX_CSS);
+    }
+    let mut main = Vec::new();
+    if let Some(heading) = heading {
+        writeln!(main, "<h1>{}</h1>", escape_html(heading)).unwrap();
+    }
+    let size = options.thumbnail_size;
+    let dimensions = |p: &Photo| match p.thumbnail_dimensions(options) {
+        Some((width, height)) => format!(" width=\"{width}\" height=\"{height}\""),
+        None => String::new(),
+    };
+    let sizes = thumbnail_sizes(options);
+    let candidates = |variants: &mut dyn Iterator<Item = (u32, &Path)>| {
+        variants
+            .sorted()
+            .map(|(w, path)| format!("{root}{} {w}w", escape_html(&options.relative_str(path))))
+            .join(", ")
+    };
+    let srcset = |p: &Photo, sizes: &str| {
+        if p.thumbnail_variants.is_empty() {
+            return String::new();
+        }
+        let candidates = candidates(
+            &mut p
+                .thumbnail_variants
+                .iter()
+                .map(|(w, path)| (*w, path.as_path()))
+                .chain([(size, p.thumbnail_path.as_path())]),
+        );
+        format!(" srcset=\"{candidates}\" sizes=\"{sizes}\"")
+    };
+    // Those of `--source-formats`, which come before the `<img>` of a
+    // `<picture>` that browsers fall back to.
+    let sources = |p: &Photo, sizes: &str| {
+        p.thumbnail_sources
+            .iter()
+            .map(|(_, variants)| {
+                let mime = mime_type(&variants[0].1);
+                let candidates =
+                    candidates(&mut variants.iter().map(|(w, path)| (*w, path.as_path())));
+                format!("<source type=\"{mime}\" srcset=\"{candidates}\" sizes=\"{sizes}\">")
+            })
+            .collect::<String>()
+    };
+    let link = |p: &Photo| {
+        if options.permalinks {
+            format!("{up}{}", permalink_path(p))
+        } else {
+            format!("{up}{}", options.relative_str(&p.img_path))
+        }
+    };
+    let mut photos = photos_by_day.iter().flat_map(|(_, v)| v.iter().copied());
+    let hero = match options.hero {
+        Hero::None => None,
+        Hero::First => photos.next(),
+        Hero::Chosen => photos.clone().find(|p| p.cover).or_else(|| photos.next()),
+    };
+    if let Some(hero) = hero {
+        writeln!(
+            main,
+            "<figure class=\"hero\"><a href=\"{}\"><img src=\"{root}{}\" alt=\"{}\"></a></figure>",
+            link(hero),
+            options.relative_str(hero.poster_path.as_ref().unwrap_or(&hero.img_path)),
+            escape_html(&hero.label)
+        )
+        .unwrap();
+    }
+    // Lazily, for the photos that are only shown once expanded, and those
+    // after the preloaded ones.
+    let write_figure = |main: &mut Vec<u8>, p: &Photo, hidden: bool, sizes: &str| {
+        let is_eager = eager.iter().any(|e| std::ptr::eq(*e, p));
+        let loading = if is_eager {
+            " loading=\"eager\""
+        } else if hidden || options.preload_thumbnails.is_some() {
+            " loading=\"lazy\""
+        } else {
+            ""
+        };
+        let settings = if options.hover_settings && !p.settings.is_empty() {
+            let settings: Vec<String> = p
+                .settings
+                .iter()
+                .map(|(_, value)| escape_html(value))
+                .collect();
+            format!("<span class=\"settings\">{}</span>", settings.join(" · "))
+        } else {
+            String::new()
+        };
+        let download = match &p.published_path {
+            Some(path) => format!(
+                "<a href=\"{up}{}\" download>{}</a>",
+                escape_html(&options.relative_str(path)),
+                options.phrases.original
+            ),
+            None => String::new(),
+        };
+        // Videos are opened by the browser instead.
+        let full = if options.lightbox && p.poster_path.is_none() {
+            format!(
+                " data-full=\"{root}{}\"",
+                escape_html(&options.relative_str(&p.img_path))
+            )
+        } else {
+            String::new()
+        };
+        let tags = if options.tags && !p.keywords.is_empty() {
+            let links: Vec<String> = p
+                .keywords
+                .iter()
+                .map(|tag| {
+                    format!(
+                        "<a href=\"{up}{}\">{}</a>",
+                        escape_html(&tag_path(tag)),
+                        escape_html(tag)
+                    )
+                })
+                .collect();
+            format!("<span class=\"tags\">{}</span>", links.join(" "))
+        } else {
+            String::new()
+        };
+        // In one caption, as a figure can only have one.
+        let caption = if settings.is_empty() && download.is_empty() && tags.is_empty() {
+            String::new()
+        } else {
+            format!("<figcaption>{settings}{download}{tags}</figcaption>")
+        };
+        let exif = if p.exif_fields.is_empty() {
+            String::new()
+        } else {
+            let rows: String = p
+                .exif_fields
+                .iter()
+                .map(|(tag, value)| {
+                    format!(
+                        "<tr><th>{}</th><td>{}</td></tr>",
+                        escape_html(tag),
+                        escape_html(value)
+                    )
+                })
+                .collect();
+            format!(
+                "<details><summary>{}</summary><table>{rows}</table></details>",
+                options.phrases.exif
+            )
+        };
+        if options.verbose_html {
+            let source = options
+                .inputs
+                .iter()
+                .find_map(|input| p.original_path.strip_prefix(input).ok())
+                .filter(|path| !path.as_os_str().is_empty())
+                .unwrap_or(&p.original_path);
+            let comment = format!(
+                "src: {} {}",
+                source.display(),
+                p.zoned_datetime().format("%Y-%m-%dT%H:%M:%S%:z")
+            );
+            writeln!(main, "<!-- {} -->", escape_comment(&comment)).unwrap();
+        }
+        let src = format!("{root}{}", options.relative_str(&p.thumbnail_path));
+        let label = escape_html(&p.label);
+        let mut img = format!(
+            "<img src=\"{src}\"{} alt=\"{label}\" title=\"{label}\"{}{loading}>",
+            srcset(p, sizes),
+            dimensions(p)
+        );
+        if !p.thumbnail_sources.is_empty() {
+            img = format!("<picture>{}{img}</picture>", sources(p, sizes));
+        }
+        let class = if p.poster_path.is_some() {
+            " class=\"video\""
+        } else {
+            ""
+        };
+        let figure = options.templates.lock().unwrap().figure.render(&[
+            ("id", &p.id),
+            ("class", class),
+            ("href", &link(p)),
+            ("lightbox", &full),
+            ("img", &img),
+            ("src", &src),
+            ("label", &label),
+            ("exif", &exif),
+            ("caption", &caption),
+        ]);
+        main.write_all(figure.as_bytes()).unwrap();
+    };
+    // A burst is shown by its first photo, the others only once expanded.
+    let write_stack = |main: &mut Vec<u8>, stack: &[&Photo], dense: bool, sizes: &str| {
+        let (first, rest) = stack.split_first().unwrap();
+        write_figure(main, first, dense, sizes);
+        if rest.is_empty() {
+            return;
+        }
+        writeln!(
+            main,
+            "<details class=\"stack\"><summary>{}</summary>",
+            options.phrases.photos(stack.len())
+        )
+        .unwrap();
+        for p in rest {
+            write_figure(main, p, true, sizes);
+        }
+        main.write_all(b"</details>\n").unwrap();
+    };
+    let mut previous_section = None;
+    for (i, (key, v)) in photos_by_day.iter().enumerate() {
+        let section = key
+            .date()
+            .and_then(|date| options.section_separators.section(date, options));
+        if previous_section.is_some() && section != previous_section {
+            writeln!(
+                main,
+                "<p class=\"separator\">— {} —</p>",
+                escape_html(section.as_deref().unwrap())
+            )
+            .unwrap();
+        }
+        previous_section = section;
+        let relative = key
+            .date()
+            .filter(|_| options.relative_dates)
+            .and_then(|date| relative_day(date, options));
+        let relative = match relative {
+            _ if !continued || i > 0 => relative.map(str::to_owned),
+            Some(relative) => Some(format!("{relative} ({})", options.phrases.continued)),
+            None => key
+                .heading(options)
+                .map(|heading| format!("{heading} ({})", options.phrases.continued)),
+        };
+        if let Some(heading) = key.heading_html(options, relative.as_deref()) {
+            writeln!(main, "{heading}").unwrap();
+        }
+        let dense = is_dense(v);
+        if dense {
+            main.write_all(b"<div class=\"film-strip\">\n").unwrap();
+            for p in v {
+                writeln!(
+                    main,
+                    "<a href=\"{}\"><img src=\"{root}{}\" alt=\"{label}\" title=\"{label}\" height=\"{FILM_STRIP_HEIGHT}\"></a>",
+                    link(p),
+                    options.relative_str(&generate_film_strip_image(p, options)?),
+                    label = escape_html(&p.label)
+                )
+                .unwrap();
+            }
+            writeln!(
+                main,
+                "</div>\n<details class=\"show-all\"><summary>{} ({})</summary>",
+                options.phrases.show_all,
+                options.phrases.photos(v.len())
+            )
+            .unwrap();
+        }
+        let stacks = stack_bursts(v, options);
+        match options.layout {
+            Layout::Masonry => {
+                main.write_all(b"<div class=\"masonry-grid\">\n").unwrap();
+                for stack in stacks {
+                    write_stack(&mut main, stack, dense, &sizes);
+                }
+            }
+            Layout::Justified => {
+                main.write_all(b"<div class=\"masonry-grid justified\">\n")
+                    .unwrap();
+                // Unknown ones as squares.
+                let aspect_ratios: Vec<f64> = stacks
+                    .iter()
+                    .map(|stack| match stack[0].thumbnail_dimensions(options) {
+                        Some((width, height)) => f64::from(width) / f64::from(height),
+                        None => 1.0,
+                    })
+                    .collect();
+                let mut stacks = stacks.into_iter();
+                for row in justify(&aspect_ratios, options.row_height) {
+                    main.write_all(b"<div class=\"row\">\n").unwrap();
+                    for (width, stack) in row.into_iter().zip(&mut stacks) {
+                        // Following the CSS of the rows.
+                        let sizes = format!(
+                            "(max-width: {}px) 100vw, (max-width: {MAX_PAGE_WIDTH}px) {width:.1}vw, {:.0}px",
+                            2 * MIN_COLUMN_WIDTH,
+                            width / 100.0 * f64::from(MAX_PAGE_WIDTH)
+                        );
+                        writeln!(main, "<div style=\"width: {width:.3}%\">").unwrap();
+                        write_stack(&mut main, stack, dense, &sizes);
+                        main.write_all(b"</div>\n").unwrap();
+                    }
+                    main.write_all(b"</div>\n").unwrap();
+                }
+            }
+        }
+        main.write_all(b"</div>\n").unwrap();
+        if dense {
+            main.write_all(b"</details>\n").unwrap();
+        }
+    }
+    let script = if options.lightbox { LIGHTBOX_JS } else { "" };
+    let main = String::from_utf8(main).unwrap();
+    let page = render_page(options, path, preview, &head, &main, nav, script);
+    writer
+        .write_all(page.as_bytes())
+        .at(options.output_dir.join(path))
+}
+
+/// Splits the photos of a group into runs taken within `--stack-bursts` of
+/// each other, which are shown as one.
+fn stack_bursts<'a, 'b>(photos: &'a [&'b Photo], options: &Options) -> Vec<&'a [&'b Photo]> {
+    match options.stack_bursts {
+        Some(seconds) => {
+            let threshold = TimeDelta::seconds(seconds.into());
+            photos
+                .chunk_by(|a, b| (a.utc_datetime() - b.utc_datetime()).abs() <= threshold)
+                .collect()
+        }
+        None => photos.chunks(1).collect(),
+    }
+}
+
+const PERMALINK_DIR: &str = "photo";
+
+fn permalink_path(photo: &Photo) -> String {
+    format!("{PERMALINK_DIR}/{}.html", photo.id)
+}
+
+/// Writes the page of a single photo, `prev` and `next` being its neighbours
+/// within its day.
+fn generate_permalink(
+    photo: &Photo,
+    options: &Options,
+    page: &str,
+    prev: Option<&Photo>,
+    next: Option<&Photo>,
+) -> Result<(), Error> {
+    let path = permalink_path(photo);
+    let root = root_of(&path);
+    let thumbnail = options.relative_str(&photo.thumbnail_path);
+    let img = options.relative_str(&photo.img_path);
+    let mut main = Vec::new();
+    writeln!(
+        main,
+        "<p><a href=\"{root}{page}#{}\">{}</a></p>",
+        photo.id,
+        escape_html(options.phrases.back)
+    )
+    .unwrap();
+    match &photo.poster_path {
+        Some(poster) => writeln!(
+            main,
+            "<figure class=\"permalink\"><video src=\"{root}{img}\" poster=\"{root}{}\" controls></video>",
+            options.relative_str(poster)
+        ),
+        None => writeln!(
+            main,
+            "<figure class=\"permalink\"><a href=\"{root}{img}\"><img src=\"{root}{img}\" alt=\"{}\"></a>",
+            escape_html(&photo.label)
+        ),
+    }
+    .unwrap();
+    if let Some(caption) = &photo.caption {
+        writeln!(main, "<figcaption>{}</figcaption>", escape_html(caption)).unwrap();
+    }
+    main.write_all(b"</figure>\n<dl class=\"settings\">\n")
+        .unwrap();
+    writeln!(
+        main,
+        "<dt>{}</dt><dd><time datetime=\"{}\">{} {}</time></dd>",
+        options.phrases.taken,
+        photo.zoned_datetime().format("%Y-%m-%dT%H:%M:%S%:z"),
+        escape_html(&options.format_date(&photo.datetime.date())),
+        photo.datetime.format("%H:%M")
+    )
+    .unwrap();
+    for (label, value) in &photo.settings {
+        writeln!(main, "<dt>{label}</dt><dd>{}</dd>", escape_html(value)).unwrap();
+    }
+    main.write_all(b"</dl>\n").unwrap();
+    let mut nav = String::from("<nav>\n<ul class=\"nav\">\n");
+    let phrases = options.phrases;
+    for (text, neighbour) in [(phrases.previous, prev), (phrases.next, next)] {
+        if let Some(neighbour) = neighbour {
+            nav.push_str(&format!(
+                "<li><a href=\"{root}{}\">{text}</a></li>\n",
+                permalink_path(neighbour)
+            ));
+        }
+    }
+    nav.push_str("</ul>\n</nav>\n");
+    let main = String::from_utf8(main).unwrap();
+    write_atomically(
+        &options.output_dir.join(&path),
+        render_page(options, &path, Some(&thumbnail), "", &main, &nav, ""),
+    )
+}
+
+/// Writes all photos into one document, with the images embedded as data URIs.
+fn generate_single_file(
+    photos_by_day: &[(GroupKey, Vec<&Photo>)],
+    options: &Options,
+    css: &str,
+    path: &Path,
+) -> Result<(), Error> {
+    let mut writer = AtomicFile::create(path)?;
+    writer
+        .write_all(standalone_html_begin(options, css).as_bytes())
+        .at(path)?;
+    writer.write_all(b"<body>\n<main>\n").at(path)?;
+    for (key, v) in photos_by_day {
+        if let Some(heading) = key.heading_html(options, None) {
+            writeln!(writer, "{heading}").at(path)?;
+        }
+        writer
+            .write_all(b"<div class=\"masonry-grid\">\n")
+            .at(path)?;
+        for p in v {
+            let thumbnail = data_uri(&p.thumbnail_path)?;
+            let label = escape_html(&p.label);
+            if options.inline_images {
+                let img = data_uri(&p.img_path)?;
+                writeln!(
+                    writer,
+                    "<figure id=\"{}\"><a href=\"{img}\"><img src=\"{thumbnail}\" alt=\"{label}\" title=\"{label}\"></a></figure>",
+                    p.id
+                )
+            } else {
+                writeln!(
+                    writer,
+                    "<figure id=\"{}\"><img src=\"{thumbnail}\" alt=\"{label}\" title=\"{label}\"></figure>",
+                    p.id
+                )
+            }
+            .at(path)?;
+        }
+        writer.write_all(b"</div>\n").at(path)?;
+    }
+    writer.write_all(b"</main>\n</body>").at(path)?;
+    writer.write_all(HTML_END.as_bytes()).at(path)?;
+    writer.commit()
+}
+
+fn data_uri(path: &Path) -> Result<String, Error> {
+    let mime = mime_type(path);
+    let data = BASE64_STANDARD.encode(fs::read(path).at(path)?);
+    Ok(format!("data:{mime};base64,{data}"))
+}
+
+/// Returns the media type of an image or video, by its extension.
+fn mime_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        Some("avif") => "image/avif",
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("mp4" | "m4v") => "video/mp4",
+        Some("mov") => "video/quicktime",
+        Some("webm") => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Runs `photo2html` with the arguments of the process, exiting if it fails.
+pub fn main() {
+    let mut args = Args::parse();
+    let config = match &args.config {
+        Some(path) => Some(path.clone()),
+        None => Some(args.input_dir().join(config::FILE_NAME)).filter(|path| path.is_file()),
+    };
+    if let Some(path) = config {
+        if let Err(e) = config::apply(&path, &Args::command()) {
+            error!("{e}");
+            process::exit(1);
+        }
+        // Again, with the options of the file.
+        args = Args::parse();
+    }
+    if args.version {
+        print_version(&args.magick);
+        return;
+    }
+    report::init(args.log_format, args.verbose, args.quiet);
+    // Before any work, rather than failing on the first image.
+    let uses_magick = match args.image_backend {
+        image::Backend::Native => args.magick_fallback,
+        image::Backend::Magick => true,
+        image::Backend::None => false,
+    };
+    if uses_magick && !args.clean && !args.check {
+        if let Err(e) = image::Backend::Magick.version(&args.magick) {
+            error!("{e}. Install ImageMagick, pass its program with --magick, or use another --image-backend");
+            process::exit(1);
+        }
+    }
+    let open = args.open;
+    let clean = args.clean;
+    let options = Options::new(args).unwrap_or_else(|e| {
+        error!("{e}");
+        process::exit(1);
+    });
+    trace!("options: {options:#?}");
+    if clean {
+        if let Err(e) = clean_output(&options) {
+            error!("{e}");
+            process::exit(1);
+        }
+        return;
+    }
+    if options.check {
+        check(&options);
+        return;
+    }
+    let photos = generate(&options, &Html).unwrap_or_else(|e| {
+        error!("{e}");
+        process::exit(1);
+    });
+    // The outputs that couldn't be made are reported as they are, and the rest
+    // still written, but the run fails unless it goes on watching.
+    let errors = report::error_count();
+    if errors > 0 && !options.watch {
+        error!("{errors} problems found");
+        process::exit(1);
+    }
+    if let Some(command) = &options.deploy {
+        if let Err(e) = deploy(&options, command) {
+            error!("{e}");
+            process::exit(1);
+        }
+    }
+    let server = options.serve.as_ref().map(|addr| {
+        match serve::Server::start(addr, options.output_dir.clone(), options.hashed_assets) {
+            Ok(server) => {
+                info!("serving {} at {}", options.output_dir.display(), server.url);
+                server
+            }
+            Err(e) => {
+                error!("cannot serve at {addr}: {e}");
+                process::exit(1);
+            }
+        }
+    });
+    if open && !(photos.is_empty() && options.noop_on_empty) {
+        match &server {
+            Some(server) => open_in_browser(server.url.as_ref()),
+            None => open_in_browser(options.output_dir.join(INDEX_PATH).as_os_str()),
+        }
+    }
+    if !options.watch {
+        return;
+    }
+    watch_and_generate(&options, photos, server.as_ref());
+}
+
+const ASSET_MANIFEST_PATH: &str = "assets.json";
+
+/// Lists how each image was made, so that it is regenerated when that changes,
+/// e.g. with another --thumbnail-size or, for WebP and AVIF, by upgrading
+/// ImageMagick, although its modification time says it is up to date.
+const ENCODER_MANIFEST_PATH: &str = "encoders.json";
+
+/// Lists the modification time, size and content hash of each photo, and the
+/// files made from it. A photo that is unchanged since is not hashed again,
+/// and the files of photos that are gone are removed.
+const BUILD_MANIFEST_PATH: &str = "build.json";
+
+/// Marks a directory as generated, so that `--clean` knows it may remove files
+/// in it.
+const MARKER: &str = ".photo2html";
+
+/// Files and directories directly in the output directory that are generated,
+/// besides the pages.
+const GENERATED: &[&str] = &[
+    "thumbnail",
+    "img",
+    "original",
+    PERMALINK_DIR,
+    FILM_STRIP_DIR,
+    "css",
+    INDEX_PATH,
+    ON_THIS_DAY_PATH,
+    ARCHIVE_PATH,
+    TOC_PATH,
+    ALBUMS_PATH,
+    TAGS_PATH,
+    UNDATED_PATH,
+    MAP_PATH,
+    OPDS_PATH,
+    FEED_PATH,
+    JSON_PATH,
+    "icon.svg",
+    "apple-touch-icon.png",
+    "icon-192.png",
+    "icon-512.png",
+    "favicon.ico",
+    "site.webmanifest",
+    ASSET_MANIFEST_PATH,
+    ENCODER_MANIFEST_PATH,
+    BUILD_MANIFEST_PATH,
+    DEPLOY_MANIFEST_PATH,
+];
+
+/// Removes all generated files, refusing to if the output directory is not
+/// marked as generated.
+fn clean_output(options: &Options) -> Result<(), Error> {
+    let dir = &options.output_dir;
+    let marker = dir.join(MARKER);
+    if !marker.exists() {
+        return Err(Error::Options(format!(
+            "{} was not generated by photo2html, not cleaning it",
+            dir.display()
+        )));
+    }
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .at(dir)?
+        .map(|e| e.map(|e| e.path()).at(dir))
+        .collect::<Result<Vec<_>, Error>>()?
+        .into_iter()
+        .filter(|path| {
+            let name = path.file_name().unwrap().to_string_lossy();
+            GENERATED.contains(&&*name)
+                || name.starts_with("page_")
+                || name.starts_with("album_")
+                || name.starts_with("tag_")
+                || name.ends_with(".tmp")
+                || is_page_path(&name)
+                || (path.is_dir() && is_year(path.file_name().unwrap()))
+        })
+        // A custom icon may be kept there.
+        .filter(|path| options.icon.as_ref() != Some(path))
+        .chain(
+            [&options.single_file, &options.pdf, &options.archive]
+                .into_iter()
+                .flatten()
+                .filter(|path| path.exists())
+                .cloned(),
+        )
+        .collect();
+    // Whatever --copy-assets put there.
+    if let Some(assets) = &options.copy_assets {
+        for entry in fs::read_dir(assets).at(assets)? {
+            let path = dir.join(entry.at(assets)?.file_name());
+            if path.exists() {
+                paths.push(path);
+            }
+        }
+    }
+    paths.sort();
+    paths.dedup();
+    paths.push(marker);
+    for path in paths {
+        if path.is_dir() && !path.is_symlink() {
+            fs::remove_dir_all(&path).at(&path)?;
+        } else {
+            fs::remove_file(&path).at(&path)?;
+        }
+        info!("removed {}", path.display());
+    }
+    Ok(())
+}
+
+/// Prints what was compiled in and what was found at runtime, to tell why an
+/// option doesn't work.
+fn print_version(magick: &Path) {
+    println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    println!("image backends:");
+    for backend in image::Backend::value_variants() {
+        let name = backend.to_possible_value().unwrap();
+        let version = backend.version(magick);
+        println!(
+            "  {}: {}",
+            name.get_name(),
+            version.as_deref().unwrap_or("not found")
+        );
+    }
+}
+
+fn open_in_browser(target: &OsStr) {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg("start").arg("");
+        command
+    } else {
+        if std::env::var_os("DISPLAY").is_none() && std::env::var_os("WAYLAND_DISPLAY").is_none() {
+            warn!(
+                "no display available, not opening {}",
+                target.to_string_lossy()
+            );
+            return;
+        }
+        Command::new("xdg-open")
+    };
+    command.arg(target);
+    match command.status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => warn!("failed to open {}: {status}", target.to_string_lossy()),
+        Err(e) => warn!("failed to open {}: {e}", target.to_string_lossy()),
+    }
+}
+
+/// Lists the files as they were when `--deploy` last succeeded, by their
+/// modification times, sizes and paths.
+const DEPLOY_MANIFEST_PATH: &str = ".photo2html-deployed";
+
+/// Runs the command of `--deploy` with the shell in the output directory,
+/// writing the paths of the files new or changed since it last succeeded to its
+/// standard input, one per line. It isn't run if nothing changed.
+fn deploy(options: &Options, command: &str) -> Result<(), String> {
+    let manifest_path = options.output_dir.join(DEPLOY_MANIFEST_PATH);
+    let deployed: HashSet<String> = fs::read_to_string(&manifest_path)
+        .unwrap_or_default()
+        .lines()
+        .map(str::to_owned)
+        .collect();
+    let mut manifest = Vec::new();
+    let mut changed = String::new();
+    for path in output_files(options)? {
+        let name = path.file_name().unwrap();
+        if name == MARKER
+            || name == DEPLOY_MANIFEST_PATH
+            || path.extension().is_some_and(|e| e == "tmp")
+        {
+            continue;
+        }
+        let metadata = path.metadata().at(&path)?;
+        let modified = metadata
+            .modified()
+            .at(&path)?
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        let relative = options.relative_path(&path).to_string_lossy();
+        let entry = format!("{} {} {relative}", modified.as_nanos(), metadata.len());
+        if !deployed.contains(&entry) {
+            changed.push_str(&relative);
+            changed.push('\n');
+        }
+        manifest.push(entry);
+    }
+    // Files that were removed change the number of them.
+    if changed.is_empty() && manifest.len() == deployed.len() {
+        debug!("nothing to deploy");
+        return Ok(());
+    }
+
+    let mut shell = if cfg!(target_os = "windows") {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let mut child = shell
+        .arg(command)
+        .current_dir(&options.output_dir)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot run {command}: {e}"))?;
+    let mut stdin = child.stdin.take().unwrap();
+    // From another thread, as the command may not read them, which would
+    // otherwise block it once the pipe is full.
+    let writer = thread::spawn(move || {
+        let _ = stdin.write_all(changed.as_bytes());
+    });
+    let status = child
+        .wait()
+        .map_err(|e| format!("cannot run {command}: {e}"))?;
+    writer.join().unwrap();
+    if !status.success() {
+        return Err(format!("{command} failed: {status}"));
+    }
+    info!("deployed {}", options.output_dir.display());
+    manifest.push(String::new());
+    write_atomically(&manifest_path, manifest.join("\n"))?;
+    Ok(())
+}
+
+/// How long the files must be left alone for a rebuild, so that a burst of
+/// changes, like copying many photos, is rebuilt once.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// A watched directory, or a file within it.
+struct Watch {
+    dir: PathBuf,
+    /// `dir` as some platforms report it in events.
+    canonical: Option<PathBuf>,
+    file: Option<PathBuf>,
+}
+
+impl Watch {
+    /// Whether `path` is the watched directory itself.
+    fn is_dir(&self, path: &Path) -> bool {
+        path == self.dir || self.canonical.as_deref() == Some(path)
+    }
+
+    /// Returns the path an event is about, if it concerns this watch.
+    fn path(&self, path: &Path) -> Option<PathBuf> {
+        if !self.is_dir(path.parent()?) {
+            return None;
+        }
+        let name = path.file_name()?;
+        match &self.file {
+            Some(file) if file.file_name() == Some(name) => Some(file.clone()),
+            Some(_) => None,
+            None => Some(self.dir.join(name)),
+        }
+    }
+}
+
+/// Pages open from `server` are reloaded after every rebuild.
+fn watch_and_generate(options: &Options, mut photos: Vec<Photo>, server: Option<&serve::Server>) {
+    let (sender, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).unwrap();
+
+    // Editors often replace files instead of writing to them, so for files the
+    // directories containing them are watched instead.
+    let mut watch = |path: &Path, whole_dir: bool| {
+        let (dir, file) = if whole_dir {
+            (path, None)
+        } else {
+            let dir = match path.parent() {
+                Some(dir) if dir != Path::new("") => dir,
+                _ => Path::new("."),
+            };
+            (dir, Some(path.to_owned()))
+        };
+        watcher.watch(dir, RecursiveMode::NonRecursive).unwrap();
+        debug!("watching {}", path.display());
+        Watch {
+            dir: dir.to_owned(),
+            canonical: fs::canonicalize(dir).ok(),
+            file,
+        }
+    };
+    let dirs = input_dirs(options).unwrap_or_else(|e| {
+        error!("{e}");
+        process::exit(1);
+    });
+    let mut input_watches: Vec<_> = options
+        .inputs
+        .iter()
+        .filter(|input| !input.is_dir())
+        .map(|input| (input.clone(), false))
+        .chain(dirs.into_iter().map(|dir| (dir, true)))
+        .map(|(path, whole_dir)| watch(&path, whole_dir))
+        .collect();
+    let render_watches: Vec<_> = options
+        .css
+        .iter()
+        .map(|path| (path, false))
+        .chain(options.templates_dir.iter().map(|dir| (dir, true)))
+        .map(|(path, whole_dir)| watch(path, whole_dir))
+        .collect();
+
+    // Held while generating, so that an interrupt waits for the output to be
+    // complete before exiting.
+    static GENERATING: Mutex<()> = Mutex::new(());
+    ctrlc::set_handler(|| {
+        let _guard = GENERATING.lock();
+        info!("exiting");
+        process::exit(0);
+    })
+    .unwrap();
+
+    let mut previous = photo_stamps(&photos);
+    loop {
+        let mut batch = vec![events.recv().unwrap()];
+        while let Ok(event) = events.recv_timeout(WATCH_DEBOUNCE) {
+            batch.push(event);
+        }
+        let mut changed: Vec<PathBuf> = Vec::new();
+        // Set if it is unknown which photos changed.
+        let mut reprocess = false;
+        let mut rerender = false;
+        for event in batch {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    warn!("watching failed: {e}");
+                    reprocess = true;
+                    continue;
+                }
+            };
+            if let EventKind::Access(_) = event.kind {
+                continue;
+            }
+            // Removed directories can't be told from files otherwise.
+            let is_dir = matches!(
+                event.kind,
+                EventKind::Create(CreateKind::Folder) | EventKind::Remove(RemoveKind::Folder)
+            );
+            for path in &event.paths {
+                if render_watches.iter().any(|w| w.path(path).is_some()) {
+                    rerender = true;
+                } else if let Some(path) = input_watches.iter().find_map(|w| w.path(path)) {
+                    // Hidden files are only read if given explicitly.
+                    if !options.include_hidden
+                        && is_hidden(&path)
+                        && !options.inputs.contains(&path)
+                    {
+                        continue;
+                    }
+                    // Changes of the options need a restart.
+                    if path.file_name() == Some(OsStr::new(config::FILE_NAME)) {
+                        continue;
+                    }
+                    if is_dir || path.is_dir() || input_watches.iter().any(|w| w.is_dir(&path)) {
+                        if options.recursive {
+                            reprocess = true;
+                            if path.is_dir() {
+                                // Known directories are watched already.
+                                let known: Vec<_> =
+                                    input_watches.iter().map(|w| w.dir.clone()).collect();
+                                match input_dirs(options) {
+                                    Ok(dirs) => input_watches.extend(
+                                        dirs.into_iter()
+                                            .filter(|dir| !known.contains(dir))
+                                            .map(|dir| watch(&dir, true)),
+                                    ),
+                                    Err(e) => error!("{e}"),
+                                }
+                            }
+                        }
+                        continue;
+                    }
+                    // A sidecar changes its photo.
+                    let path = match path.extension() {
+                        Some(e) if e == UNLISTED || e == ROTATE => path.with_extension(""),
+                        // The photo's extension may have been replaced.
+                        Some(e) if e.eq_ignore_ascii_case(XMP) => {
+                            reprocess = true;
+                            continue;
+                        }
+                        _ => path,
+                    };
+                    if !changed.contains(&path) {
+                        changed.push(path);
+                    }
+                } else if input_watches.iter().any(|w| w.is_dir(path)) {
+                    reprocess = true;
+                }
+            }
+        }
+        // The names of other photos depend on whether these collide with them,
+        // and whether raw files are shown on whether their pair is.
+        if changed.iter().any(|path| {
+            is_raw(path)
+                || has_raw_pair(path)
+                || photos.iter().any(|p| {
+                    p.original_path != *path && p.original_path.file_name() == path.file_name()
+                })
+        }) {
+            reprocess = true;
+        }
+        // Which photos are duplicates depends on the others.
+        if !changed.is_empty() && (options.skip_duplicates || options.skip_similar.is_some()) {
+            reprocess = true;
+        }
+        let _guard = GENERATING.lock().unwrap();
+        let start = Instant::now();
+        let mut timings = Timings::default();
+        if reprocess {
+            match process_photos(options, &mut timings) {
+                Ok(processed) => photos = processed,
+                Err(e) => {
+                    error!("{e}");
+                    continue;
+                }
+            }
+        } else if !changed.is_empty() {
+            // Only the changed photos are read again, the others are unaffected.
+            photos.retain(|p| !changed.contains(&p.original_path));
+            photos.extend(
+                changed
+                    .into_iter()
+                    .filter(|path| path.exists())
+                    .filter_map(|path| {
+                        let name = path.file_name().unwrap().to_string_lossy();
+                        let name = OsString::from(name.into_owned());
+                        load_photo(path, &name, options, &mut timings)
+                    }),
+            );
+        } else if !rerender {
+            continue;
+        }
+        if let Some(dir) = options.templates_dir.as_ref().filter(|_| rerender) {
+            match template::Templates::load(dir) {
+                Ok(templates) => *options.templates.lock().unwrap() = templates,
+                Err(e) => {
+                    error!("{e}");
+                    continue;
+                }
+            }
+        }
+        let pages = match render(options, &photos) {
+            Ok(pages) => pages,
+            Err(e) => {
+                error!("{e}");
+                continue;
+            }
+        };
+        if let Some(server) = server {
+            server.reload();
+        }
+        if let Some(command) = &options.deploy {
+            if let Err(e) = deploy(options, command) {
+                error!("{e}");
+            }
+        }
+        let stamps = photo_stamps(&photos);
+        let [new, changed, removed] = changes(&previous, &stamps);
+        info!(
+            "rebuilt in {:.1}s — {new} new, {changed} changed, {removed} removed, {pages} {}",
+            start.elapsed().as_secs_f64(),
+            if pages == 1 { "page" } else { "pages" }
+        );
+        previous = stamps;
+    }
+}
+
+/// Returns the files generated for the photos in the output directory, see
+/// [`remove_stale_outputs`].
+fn photo_outputs(photos: &[Photo], options: &Options) -> HashSet<PathBuf> {
+    photos
+        .iter()
+        .flat_map(|p| {
+            let permalink = options
+                .permalinks
+                .then(|| options.output_dir.join(permalink_path(p)));
+            [&p.thumbnail_path, &p.img_path]
+                .into_iter()
+                .chain(p.thumbnail_variants.iter().map(|(_, path)| path))
+                .chain(p.source_thumbnails())
+                .chain(&p.poster_path)
+                .chain(&p.published_path)
+                .flat_map(|path| iter::once(path.clone()).chain(logical_path(path)))
+                .chain(permalink)
+        })
+        // Not the originals, which may be linked to instead.
+        .filter(|path| path.starts_with(&options.output_dir))
+        .collect()
+}
+
+/// The stamp of the original of each photo when it was read, to tell what
+/// changed between the rebuilds of `--watch`.
+fn photo_stamps(photos: &[Photo]) -> HashMap<PathBuf, FileStamp> {
+    photos
+        .iter()
+        .map(|p| (p.original_path.clone(), p.stamp))
+        .collect()
+}
+
+/// Counts the photos that are new, changed and removed since `before`.
+fn changes(
+    before: &HashMap<PathBuf, FileStamp>,
+    after: &HashMap<PathBuf, FileStamp>,
+) -> [usize; 3] {
+    let new = after
+        .keys()
+        .filter(|path| !before.contains_key(*path))
+        .count();
+    let changed = after
+        .iter()
+        .filter(|(path, modified)| before.get(*path).is_some_and(|m| m != *modified))
+        .count();
+    let removed = before
+        .keys()
+        .filter(|path| !after.contains_key(*path))
+        .count();
+    [new, changed, removed]
+}
+
+const STYLE_CSS: &str = r##"body {
+    margin: 0 auto;
+    padding: 0 1em;
+    max-width: 1600px;
+    font-family: sans-serif;
+    background: var(--background);
+    color: var(--text);
+}
+
+a {
+    color: inherit;
+}
+
+.masonry-grid {
+    column-count: {columns};
+    column-gap: 0.5em;
+}
+
+.masonry-grid figure {
+    position: relative;
+    margin: 0 0 0.5em;
+    break-inside: avoid;
+}
+
+.masonry-grid figure .settings {
+    display: none;
+    position: absolute;
+    top: 0;
+    left: 0;
+    right: 0;
+    padding: 0.25em 0.5em;
+    font-size: 0.8em;
+    color: white;
+    background: rgb(0 0 0 / 60%);
+    pointer-events: none;
+}
+
+.masonry-grid figure:hover .settings {
+    display: block;
+}
+
+.masonry-grid img {
+    display: block;
+    width: 100%;
+    height: auto;
+}
+
+.film-strip {
+    display: flex;
+    gap: 0.25em;
+    overflow-x: auto;
+}
+
+.film-strip img {
+    display: block;
+    height: 120px;
+    width: auto;
+}
+
+details.stack summary {
+    display: inline-block;
+    margin: -2.5em 0 1em 0.5em;
+    padding: 0.1em 0.5em;
+    position: relative;
+    border-radius: 1em;
+    font-size: 0.8em;
+    cursor: pointer;
+    background: var(--background);
+}
+
+details.stack[open] summary {
+    margin-top: 0;
+}
+
+details.show-all summary {
+    margin: 0.5em 0;
+    cursor: pointer;
+    color: var(--muted);
+}
+
+p.separator {
+    margin: 2em 0 1em;
+    text-align: center;
+    font-size: 1.25em;
+    color: var(--muted);
+}
+
+figure.hero {
+    margin: 1em 0;
+}
+
+figure.hero img {
+    display: block;
+    width: 100%;
+    max-height: 80vh;
+    object-fit: cover;
+}
+
+.masonry-grid figure details {
+    font-size: 0.8em;
+    overflow-wrap: anywhere;
+}
+
+.masonry-grid figure details th {
+    text-align: left;
+    color: var(--muted);
+}
+
+figure.permalink {
+    margin: 1em 0;
+}
+
+.masonry-grid figure.video a::after {
+    content: "▶";
+    position: absolute;
+    top: 0.25em;
+    right: 0.5em;
+    color: white;
+    text-shadow: 0 0 0.25em black;
+}
+
+figure.permalink img,
+figure.permalink video {
+    display: block;
+    max-width: 100%;
+    max-height: 90vh;
+    margin: 0 auto;
+}
+
+dl.settings {
+    display: grid;
+    grid-template-columns: max-content auto;
+    gap: 0.25em 1em;
+}
+
+dl.settings dt {
+    color: var(--muted);
+}
+
+dl.settings dd {
+    margin: 0;
+}
+
+ul.nav {
+    display: flex;
+    flex-wrap: wrap;
+    gap: 0.5em 1.5em;
+    padding: 0;
+    list-style: none;
+}
+
+ul.nav details ul.nav {
+    margin: 0.5em 0 0 1em;
+}
+
+ul.nav summary {
+    cursor: pointer;
+}
+
+ul.albums {
+    display: grid;
+    grid-template-columns: repeat(auto-fill, minmax(200px, 1fr));
+    gap: 1em;
+    padding: 0;
+    list-style: none;
+    color: var(--muted);
+}
+
+ul.albums img {
+    display: block;
+    width: 100%;
+    aspect-ratio: 1;
+    object-fit: cover;
+}
+
+ul.tags {
+    display: flex;
+    flex-wrap: wrap;
+    align-items: baseline;
+    gap: 0.5em 1.5em;
+    padding: 0;
+    list-style: none;
+    color: var(--muted);
+}
+
+ul.tags a {
+    font-size: calc(1em + var(--share) * 0.75em);
+}
+
+.masonry-grid figcaption .tags a {
+    margin-right: 0.5em;
+    font-size: 0.8em;
+}
+
+ul.nav .histogram {
+    display: inline-block;
+    width: calc(var(--share) * 3em);
+    min-width: 1px;
+    height: 0.5em;
+    margin-left: 0.5em;
+    background: var(--muted);
+}
+"##;
+
+const ICON_SVG: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 64 64">
+<rect width="64" height="64" rx="12" fill="#444"/>
+<circle cx="22" cy="22" r="7" fill="#fff"/>
+<path d="M8 52 26 32l10 11 8-8 12 17z" fill="#fff"/>
+</svg>
+"##;
+
+/// Styles the overlay of the full images, see `--no-lightbox`, in the pages so
+/// that it works with `--css` too.
+const LIGHTBOX_CSS: &str = r#"<style>
+.lightbox {
+    position: fixed;
+    inset: 0;
+    z-index: 10;
+    display: flex;
+    align-items: center;
+    justify-content: center;
+    background: rgb(0 0 0 / 90%);
+    cursor: zoom-out;
+}
+
+.lightbox[hidden] {
+    display: none;
+}
+
+.lightbox img {
+    max-width: 100%;
+    max-height: 100%;
+    object-fit: contain;
+}
+
+.lightbox button {
+    position: absolute;
+    top: 50%;
+    transform: translateY(-50%);
+    padding: 1rem;
+    border: none;
+    background: none;
+    color: #fff;
+    font-size: 3rem;
+    cursor: pointer;
+}
+
+.lightbox .previous {
+    left: 0;
+}
+
+.lightbox .next {
+    right: 0;
+}
+
+.lightbox button[hidden] {
+    display: none;
+}
+</style>
+"#;
+
+/// Opens the full image of each thumbnail, from its `data-full`, over the page.
+/// The arrows, arrow keys and swipes stop at the first and last photo, and
+/// Escape or a click elsewhere closes it. Clicks with modifiers still follow the link.
+const LIGHTBOX_JS: &str = r#"<script>
+(() => {
+    const links = [...document.querySelectorAll("a[data-full]")];
+    if (links.length === 0) {
+        return;
+    }
+    const box = document.createElement("div");
+    box.className = "lightbox";
+    box.hidden = true;
+    const img = document.createElement("img");
+    const arrow = (className, label, text) => {
+        const button = document.createElement("button");
+        button.type = "button";
+        button.className = className;
+        button.setAttribute("aria-label", label);
+        button.textContent = text;
+        return button;
+    };
+    const previous = arrow("previous", "Previous", "\u2039");
+    const next = arrow("next", "Next", "\u203a");
+    box.append(previous, img, next);
+    document.body.append(box);
+    let current = -1;
+    const show = (index) => {
+        current = index;
+        img.src = links[index].dataset.full;
+        img.alt = links[index].querySelector("img").alt;
+        previous.hidden = index === 0;
+        next.hidden = index === links.length - 1;
+        box.hidden = false;
+    };
+    const close = () => {
+        box.hidden = true;
+        img.removeAttribute("src");
+        links[current].focus();
+        current = -1;
+    };
+    const move = (step) => {
+        const index = current + step;
+        if (index >= 0 && index < links.length) {
+            show(index);
+        }
+    };
+    links.forEach((link, index) => {
+        link.addEventListener("click", (event) => {
+            if (event.button !== 0 || event.ctrlKey || event.metaKey || event.shiftKey || event.altKey) {
+                return;
+            }
+            event.preventDefault();
+            show(index);
+        });
+    });
+    box.addEventListener("click", close);
+    previous.addEventListener("click", (event) => {
+        event.stopPropagation();
+        move(-1);
+    });
+    next.addEventListener("click", (event) => {
+        event.stopPropagation();
+        move(1);
+    });
+    document.addEventListener("keydown", (event) => {
+        if (box.hidden) {
+            return;
+        }
+        if (event.key === "Escape") {
+            close();
+        } else if (event.key === "ArrowLeft") {
+            move(-1);
+        } else if (event.key === "ArrowRight") {
+            move(1);
+        } else {
+            return;
+        }
+        event.preventDefault();
+    });
+    let start = 0;
+    box.addEventListener("touchstart", (event) => {
+        start = event.changedTouches[0].clientX;
+    }, { passive: true });
+    box.addEventListener("touchend", (event) => {
+        const distance = event.changedTouches[0].clientX - start;
+        if (Math.abs(distance) > 50) {
+            // Not a tap, which would close it.
+            event.preventDefault();
+            move(distance < 0 ? 1 : -1);
+        }
+    });
+})();
+</script>
+"#;
+
+const HTML_END: &str = r##"
+
+
+</html>
+
+"##;
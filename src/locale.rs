@@ -0,0 +1,282 @@
+use chrono::Locale;
+
+/// The texts of the pages in one language.
+#[derive(Debug)]
+pub struct Phrases {
+    pub all_photos: &'static str,
+    pub today: &'static str,
+    pub yesterday: &'static str,
+    pub back: &'static str,
+    /// Expands a day shown as a film strip.
+    pub show_all: &'static str,
+    /// Lists the older pages.
+    pub archive: &'static str,
+    /// Heads the table of contents.
+    pub contents: &'static str,
+    /// Heads the list of albums, see `--albums`.
+    pub albums: &'static str,
+    /// Heads the list of keywords, see `--tags`.
+    pub tags: &'static str,
+    /// Links the map of the geotagged photos.
+    pub map: &'static str,
+    /// Follows the heading of a day split across pages.
+    pub continued: &'static str,
+    /// Heads the photos without the value they are grouped by.
+    pub unknown: &'static str,
+    /// Heads the photos without a date, see `--group-empty-as`.
+    pub undated: &'static str,
+    /// Links the photos taken on today's date in earlier years.
+    pub on_this_day: &'static str,
+    /// Downloads the published original, see `--originals`.
+    pub original: &'static str,
+    /// Expands the EXIF fields of a photo.
+    pub exif: &'static str,
+    /// Labels when a photo was taken, on its permalink page.
+    pub taken: &'static str,
+    /// Link to the permalink pages of the photos before and after.
+    pub previous: &'static str,
+    pub next: &'static str,
+    /// Counted noun, singular first.
+    photos: [&'static str; 2],
+}
+
+impl Phrases {
+    /// Returns e.g. "3 photos".
+    pub fn photos(&self, count: usize) -> String {
+        let noun = self.photos[usize::from(count != 1)];
+        format!("{count} {noun}")
+    }
+}
+
+const EN: Phrases = Phrases {
+    all_photos: "All photos",
+    today: "Today",
+    yesterday: "Yesterday",
+    back: "Back to all photos",
+    show_all: "Show all",
+    archive: "Archive",
+    contents: "Contents",
+    albums: "Albums",
+    tags: "Tags",
+    map: "Map",
+    continued: "continued",
+    unknown: "Unknown",
+    undated: "Undated",
+    on_this_day: "On this day",
+    original: "Original",
+    exif: "EXIF",
+    taken: "Taken",
+    previous: "Previous",
+    next: "Next",
+    photos: ["photo", "photos"],
+};
+
+/// Languages other than English, by ISO 639-1 code.
+const PHRASES: &[(&str, Phrases)] = &[
+    (
+        "de",
+        Phrases {
+            all_photos: "Alle Fotos",
+            today: "Heute",
+            yesterday: "Gestern",
+            back: "Zurück zu allen Fotos",
+            show_all: "Alle anzeigen",
+            archive: "Archiv",
+            contents: "Inhalt",
+            albums: "Alben",
+            tags: "Schlagwörter",
+            map: "Karte",
+            continued: "Fortsetzung",
+            unknown: "Unbekannt",
+            undated: "Ohne Datum",
+            on_this_day: "An diesem Tag",
+            original: "Original",
+            exif: "EXIF-Daten",
+            taken: "Aufgenommen",
+            previous: "Vorheriges",
+            next: "Nächstes",
+            photos: ["Foto", "Fotos"],
+        },
+    ),
+    (
+        "es",
+        Phrases {
+            all_photos: "Todas las fotos",
+            today: "Hoy",
+            yesterday: "Ayer",
+            back: "Volver a todas las fotos",
+            show_all: "Mostrar todas",
+            archive: "Archivo",
+            contents: "Índice",
+            albums: "Álbumes",
+            tags: "Etiquetas",
+            map: "Mapa",
+            continued: "continuación",
+            unknown: "Desconocido",
+            undated: "Sin fecha",
+            on_this_day: "Tal día como hoy",
+            original: "Original",
+            exif: "Datos EXIF",
+            taken: "Tomada",
+            previous: "Anterior",
+            next: "Siguiente",
+            photos: ["foto", "fotos"],
+        },
+    ),
+    (
+        "fr",
+        Phrases {
+            all_photos: "Toutes les photos",
+            today: "Aujourd’hui",
+            yesterday: "Hier",
+            back: "Retour à toutes les photos",
+            show_all: "Tout afficher",
+            archive: "Archives",
+            contents: "Sommaire",
+            albums: "Albums",
+            tags: "Mots-clés",
+            map: "Carte",
+            continued: "suite",
+            unknown: "Inconnu",
+            undated: "Sans date",
+            on_this_day: "Ce jour-là",
+            original: "Original",
+            exif: "Données EXIF",
+            taken: "Prise",
+            previous: "Précédente",
+            next: "Suivante",
+            photos: ["photo", "photos"],
+        },
+    ),
+    (
+        "it",
+        Phrases {
+            all_photos: "Tutte le foto",
+            today: "Oggi",
+            yesterday: "Ieri",
+            back: "Torna a tutte le foto",
+            show_all: "Mostra tutte",
+            archive: "Archivio",
+            contents: "Indice",
+            albums: "Album",
+            tags: "Tag",
+            map: "Mappa",
+            continued: "continua",
+            unknown: "Sconosciuto",
+            undated: "Senza data",
+            on_this_day: "Accadde oggi",
+            original: "Originale",
+            exif: "Dati EXIF",
+            taken: "Scattata",
+            previous: "Precedente",
+            next: "Successiva",
+            photos: ["foto", "foto"],
+        },
+    ),
+    (
+        "ja",
+        Phrases {
+            all_photos: "すべての写真",
+            today: "今日",
+            yesterday: "昨日",
+            back: "すべての写真に戻る",
+            show_all: "すべて表示",
+            archive: "アーカイブ",
+            contents: "目次",
+            albums: "アルバム",
+            tags: "タグ",
+            map: "地図",
+            continued: "続き",
+            unknown: "不明",
+            undated: "日付なし",
+            on_this_day: "この日の写真",
+            original: "オリジナル",
+            exif: "Exif 情報",
+            taken: "撮影日時",
+            previous: "前へ",
+            next: "次へ",
+            photos: ["枚", "枚"],
+        },
+    ),
+    (
+        "nl",
+        Phrases {
+            all_photos: "Alle foto’s",
+            today: "Vandaag",
+            yesterday: "Gisteren",
+            back: "Terug naar alle foto’s",
+            show_all: "Alles tonen",
+            archive: "Archief",
+            contents: "Inhoud",
+            albums: "Albums",
+            tags: "Trefwoorden",
+            map: "Kaart",
+            continued: "vervolg",
+            unknown: "Onbekend",
+            undated: "Zonder datum",
+            on_this_day: "Op deze dag",
+            original: "Origineel",
+            exif: "EXIF-gegevens",
+            taken: "Gemaakt",
+            previous: "Vorige",
+            next: "Volgende",
+            photos: ["foto", "foto’s"],
+        },
+    ),
+    (
+        "zh",
+        Phrases {
+            all_photos: "所有照片",
+            today: "今天",
+            yesterday: "昨天",
+            back: "返回所有照片",
+            show_all: "显示全部",
+            archive: "归档",
+            contents: "目录",
+            albums: "相册",
+            tags: "标签",
+            map: "地图",
+            continued: "续",
+            unknown: "未知",
+            undated: "无日期",
+            on_this_day: "那年今日",
+            original: "原图",
+            exif: "EXIF 信息",
+            taken: "拍摄时间",
+            previous: "上一张",
+            next: "下一张",
+            photos: ["张照片", "张照片"],
+        },
+    ),
+];
+
+/// Returns the language of a tag like `de-DE`.
+pub fn language(tag: &str) -> &str {
+    tag.split(['-', '_']).next().unwrap()
+}
+
+/// Returns the texts in the language of `tag`, English if there are none.
+pub fn phrases(tag: &str) -> &'static Phrases {
+    let language = language(tag).to_ascii_lowercase();
+    PHRASES
+        .iter()
+        .find(|(code, _)| *code == language)
+        .map_or(&EN, |(_, phrases)| phrases)
+}
+
+/// Finds the names of months and days for a tag like `de-DE`, or just `de`,
+/// which is taken to mean `de_DE`.
+pub fn parse(tag: &str) -> Option<Locale> {
+    let language = language(tag).to_ascii_lowercase();
+    let region = match tag[language.len()..].strip_prefix(['-', '_']) {
+        Some(region) => region.to_ascii_uppercase(),
+        None => match language.as_str() {
+            "en" => "US".to_owned(),
+            "ja" => "JP".to_owned(),
+            "zh" => "CN".to_owned(),
+            _ => language.to_ascii_uppercase(),
+        },
+    };
+    let name = format!("{language}_{region}");
+    Locale::try_from(name.as_str()).ok()
+}
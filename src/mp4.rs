@@ -0,0 +1,66 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use chrono::{DateTime, Utc};
+
+/// Seconds from 1904-01-01, the epoch of MP4 and QuickTime, to 1970-01-01.
+const EPOCH: i64 = 2_082_844_800;
+
+/// Returns the creation time in the `mvhd` box of an MP4 or QuickTime file,
+/// which cameras set to when the recording started. Only the headers of the
+/// boxes before it are read, not the media data.
+pub fn creation_time(path: &Path) -> Option<DateTime<Utc>> {
+    let mut file = File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    let (start, end) = find_box(&mut file, 0, len, b"moov")?;
+    let (start, _) = find_box(&mut file, start, end, b"mvhd")?;
+    file.seek(SeekFrom::Start(start)).ok()?;
+    let mut version = [0; 4];
+    file.read_exact(&mut version).ok()?;
+    let seconds = if version[0] == 1 {
+        let mut seconds = [0; 8];
+        file.read_exact(&mut seconds).ok()?;
+        u64::from_be_bytes(seconds)
+    } else {
+        let mut seconds = [0; 4];
+        file.read_exact(&mut seconds).ok()?;
+        u32::from_be_bytes(seconds).into()
+    };
+    // Left unset by some encoders.
+    if seconds == 0 {
+        return None;
+    }
+    DateTime::from_timestamp(i64::try_from(seconds).ok()? - EPOCH, 0)
+}
+
+/// Finds the box of `kind` among those from `start` to `end`, and returns where
+/// its contents start and end.
+fn find_box(file: &mut File, mut start: u64, end: u64, kind: &[u8; 4]) -> Option<(u64, u64)> {
+    while start + 8 <= end {
+        file.seek(SeekFrom::Start(start)).ok()?;
+        let mut header = [0; 8];
+        file.read_exact(&mut header).ok()?;
+        let (contents, size) = match u32::from_be_bytes(header[..4].try_into().unwrap()) {
+            // To the end of the file.
+            0 => (start + 8, end - start),
+            1 => {
+                let mut size = [0; 8];
+                file.read_exact(&mut size).ok()?;
+                (start + 16, u64::from_be_bytes(size))
+            }
+            size => (start + 8, size.into()),
+        };
+        let box_end = start.checked_add(size)?;
+        if size < contents - start || box_end > end {
+            return None;
+        }
+        if &header[4..] == kind {
+            return Some((contents, box_end));
+        }
+        start = box_end;
+    }
+    None
+}
@@ -0,0 +1,59 @@
+use std::{fs, path::Path};
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Returns the text of the `Creation Time` keyword of a PNG file, from a
+/// `tEXt` or uncompressed `iTXt` chunk. Its format is not fixed, but is
+/// usually RFC 2822 or ISO 8601.
+pub fn creation_time(path: &Path) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    let mut rest = bytes.strip_prefix(SIGNATURE)?;
+    while rest.len() >= 12 {
+        let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let kind = &rest[4..8];
+        let data = rest.get(8..8 + len)?;
+        if let Some(text) = text(kind, data) {
+            return Some(text);
+        }
+        if kind == b"IEND" {
+            break;
+        }
+        // Skip the CRC too.
+        rest = rest.get(12 + len..)?;
+    }
+    None
+}
+
+/// Reads the width and height from the `IHDR` chunk, which comes first.
+pub fn dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let rest = bytes.strip_prefix(SIGNATURE)?;
+    if rest.get(4..8)? != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(rest.get(8..12)?.try_into().unwrap());
+    let height = u32::from_be_bytes(rest.get(12..16)?.try_into().unwrap());
+    Some((width, height))
+}
+
+fn text(kind: &[u8], data: &[u8]) -> Option<String> {
+    let (keyword, text) = data.split_at(data.iter().position(|&b| b == 0)?);
+    if keyword != b"Creation Time" {
+        return None;
+    }
+    let text = &text[1..];
+    match kind {
+        // Latin-1, which maps to the first 256 code points.
+        b"tEXt" => Some(text.iter().map(|&b| char::from(b)).collect()),
+        b"iTXt" => {
+            let ([compressed, _], text) = text.split_first_chunk::<2>()?;
+            if *compressed != 0 {
+                return None;
+            }
+            // Skip the language tag and the translated keyword.
+            let mut parts = text.splitn(3, |&b| b == 0);
+            let text = parts.nth(2)?;
+            String::from_utf8(text.to_vec()).ok()
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,55 @@
+use exif::{Exif, In, Tag};
+
+/// A JPEG embedded in the metadata, e.g. by cameras into their raw files.
+#[derive(Clone, Copy, Debug)]
+pub struct Preview<'a> {
+    pub jpeg: &'a [u8],
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Returns the largest JPEG referenced by the EXIF metadata, if any.
+pub fn largest(exif: &Exif) -> Option<Preview<'_>> {
+    [In::PRIMARY, In::THUMBNAIL]
+        .into_iter()
+        .filter_map(|ifd| {
+            let offset = exif
+                .get_field(Tag::JPEGInterchangeFormat, ifd)?
+                .value
+                .get_uint(0)?;
+            let len = exif
+                .get_field(Tag::JPEGInterchangeFormatLength, ifd)?
+                .value
+                .get_uint(0)?;
+            let jpeg = exif
+                .buf()
+                .get(offset as usize..(offset as usize).checked_add(len as usize)?)?;
+            let (width, height) = dimensions(jpeg)?;
+            Some(Preview {
+                jpeg,
+                width,
+                height,
+            })
+        })
+        .max_by_key(|p| u64::from(p.width) * u64::from(p.height))
+}
+
+/// Reads the width and height from the frame header of a JPEG.
+pub fn dimensions(jpeg: &[u8]) -> Option<(u32, u32)> {
+    if !jpeg.starts_with(&[0xff, 0xd8]) {
+        return None;
+    }
+    let mut i = 2;
+    while i + 9 <= jpeg.len() && jpeg[i] == 0xff {
+        let marker = jpeg[i + 1];
+        // Start of frame, except for DHT, JPG and DAC, which share the range.
+        if (0xc0..=0xcf).contains(&marker) && ![0xc4, 0xc8, 0xcc].contains(&marker) {
+            let height = u16::from_be_bytes([jpeg[i + 5], jpeg[i + 6]]);
+            let width = u16::from_be_bytes([jpeg[i + 7], jpeg[i + 8]]);
+            return Some((width.into(), height.into()));
+        }
+        let len = u16::from_be_bytes([jpeg[i + 2], jpeg[i + 3]]) as usize;
+        i += 2 + len;
+    }
+    None
+}
@@ -0,0 +1,220 @@
+use std::{
+    fmt::Display,
+    io::{self, IsTerminal as _, Write as _},
+    sync::{
+        atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Messages for people, with the processed photos unlabeled
+    Human,
+    /// One line per photo processed or skipped and per message
+    Concise,
+    /// Like concise, but as newline-delimited JSON records
+    Json,
+}
+
+static FORMAT: Mutex<LogFormat> = Mutex::new(LogFormat::Human);
+static VERBOSITY: AtomicU8 = AtomicU8::new(0);
+static QUIET: AtomicBool = AtomicBool::new(false);
+static ERRORS: AtomicUsize = AtomicUsize::new(0);
+static BAR: Mutex<Option<Bar>> = Mutex::new(None);
+
+/// Sets the format of all further messages, how many `--verbose` flags were
+/// given, and whether only warnings and errors are shown with `--quiet`. A
+/// later call replaces what an earlier one set.
+pub fn init(format: LogFormat, verbosity: u8, quiet: bool) {
+    *FORMAT.lock().unwrap() = format;
+    VERBOSITY.store(verbosity, Ordering::Relaxed);
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn format() -> LogFormat {
+    *FORMAT.lock().unwrap()
+}
+
+pub fn info(message: impl Display) {
+    if !QUIET.load(Ordering::Relaxed) {
+        write("info", message);
+    }
+}
+
+pub fn warning(message: impl Display) {
+    write("warning", message);
+}
+
+pub fn error(message: impl Display) {
+    ERRORS.fetch_add(1, Ordering::Relaxed);
+    write("error", message);
+}
+
+/// Returns how many errors were reported so far.
+pub fn error_count() -> usize {
+    ERRORS.load(Ordering::Relaxed)
+}
+
+/// Starts counting errors from zero, for a new run.
+pub fn reset_error_count() {
+    ERRORS.store(0, Ordering::Relaxed);
+}
+
+/// Reports a step of the generation, e.g. a processed photo. Left to the bar
+/// while there is one.
+pub fn progress(message: impl Display) {
+    if QUIET.load(Ordering::Relaxed) {
+        return;
+    }
+    match format() {
+        LogFormat::Human => {
+            let bar = BAR.lock().unwrap();
+            if bar.is_none() {
+                eprintln!("{message}");
+            }
+        }
+        LogFormat::Concise | LogFormat::Json => write("info", message),
+    }
+}
+
+/// What became of a photo, as counted by the bar.
+pub enum Outcome {
+    Processed,
+    Skipped,
+    Failed,
+}
+
+/// The photos processed of `total`, shown on the last line of a terminal.
+struct Bar {
+    total: usize,
+    processed: usize,
+    skipped: usize,
+    failed: usize,
+}
+
+impl Bar {
+    fn draw(&self) {
+        const WIDTH: usize = 30;
+        let done = self.processed + self.skipped + self.failed;
+        let filled = (done * WIDTH).checked_div(self.total).unwrap_or(WIDTH);
+        eprint!(
+            "\r\x1b[K[{}{}] {done}/{} photos, {} skipped, {} failed",
+            "#".repeat(filled),
+            " ".repeat(WIDTH - filled),
+            self.total,
+            self.skipped,
+            self.failed
+        );
+        let _ = io::stderr().flush();
+    }
+}
+
+/// Shows a bar of the progress of `total` photos instead of a line for each,
+/// if messages are for people in a terminal.
+pub fn start_bar(total: usize) {
+    if format() != LogFormat::Human || QUIET.load(Ordering::Relaxed) || !io::stderr().is_terminal()
+    {
+        return;
+    }
+    let bar = Bar {
+        total,
+        processed: 0,
+        skipped: 0,
+        failed: 0,
+    };
+    bar.draw();
+    *BAR.lock().unwrap() = Some(bar);
+}
+
+/// Counts a photo of the bar, if there is one.
+pub fn count(outcome: Outcome) {
+    if let Some(bar) = BAR.lock().unwrap().as_mut() {
+        match outcome {
+            Outcome::Processed => bar.processed += 1,
+            Outcome::Skipped => bar.skipped += 1,
+            Outcome::Failed => bar.failed += 1,
+        }
+        bar.draw();
+    }
+}
+
+/// Replaces the bar with a line of its counts.
+pub fn finish_bar() {
+    if let Some(bar) = BAR.lock().unwrap().take() {
+        eprintln!(
+            "\r\x1b[K{} photos, {} skipped, {} failed",
+            bar.processed, bar.skipped, bar.failed
+        );
+    }
+}
+
+/// Reports details with `--verbose`.
+pub fn debug(message: impl Display) {
+    if VERBOSITY.load(Ordering::Relaxed) >= 1 {
+        write("debug", message);
+    }
+}
+
+/// Reports the internal state, e.g. every photo, with `-vv`.
+pub fn trace(message: impl Display) {
+    if VERBOSITY.load(Ordering::Relaxed) >= 2 {
+        write("trace", message);
+    }
+}
+
+fn write(level: &str, message: impl Display) {
+    // Above the bar, which is drawn again below.
+    let bar = BAR.lock().unwrap();
+    if bar.is_some() {
+        eprint!("\r\x1b[K");
+    }
+    match format() {
+        LogFormat::Human | LogFormat::Concise => eprintln!("{level}: {message}"),
+        LogFormat::Json => eprintln!(
+            "{{\"level\":\"{level}\",\"message\":\"{}\"}}",
+            crate::escape_json(&message.to_string())
+        ),
+    }
+    if let Some(bar) = &*bar {
+        bar.draw();
+    }
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::report::info(format_args!($($arg)*))
+    };
+}
+
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::report::warning(format_args!($($arg)*))
+    };
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::report::error(format_args!($($arg)*))
+    };
+}
+
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        $crate::report::progress(format_args!($($arg)*))
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::report::debug(format_args!($($arg)*))
+    };
+}
+
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        $crate::report::trace(format_args!($($arg)*))
+    };
+}
@@ -0,0 +1,274 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    net::{TcpListener, TcpStream},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use chrono::{DateTime, Utc};
+
+/// Where pages listen for reloads, as server-sent events.
+const RELOAD_PATH: &str = "/_photo2html/reload";
+
+/// Added to the end of every page served, to reload it when the gallery is
+/// regenerated.
+const RELOAD_SCRIPT: &str = r#"<script>
+new EventSource("/_photo2html/reload").onmessage = () => location.reload();
+</script>
+"#;
+
+const HTTP_DATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Serves the files of a directory over HTTP, for previewing the gallery.
+pub struct Server {
+    /// The address listened on, with the port chosen if it was 0.
+    pub url: String,
+    /// The pages waiting to be reloaded.
+    listeners: Arc<Mutex<Vec<TcpStream>>>,
+}
+
+impl Server {
+    /// Starts serving `root` at `addr` in the background. With `hashed_assets`,
+    /// files with a hash in their name are cached for good.
+    pub fn start(addr: &str, root: PathBuf, hashed_assets: bool) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let url = format!("http://{}/", listener.local_addr()?);
+        let listeners = Arc::new(Mutex::new(Vec::new()));
+        let server = Server {
+            url,
+            listeners: listeners.clone(),
+        };
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let root = root.clone();
+                let listeners = listeners.clone();
+                thread::spawn(move || {
+                    if let Err(e) = respond(stream, &root, hashed_assets, &listeners) {
+                        debug!("serving a request failed: {e}");
+                    }
+                });
+            }
+        });
+        Ok(server)
+    }
+
+    /// Tells every open page to reload.
+    pub fn reload(&self) {
+        let mut listeners = self.listeners.lock().unwrap();
+        // Those of closed pages fail.
+        listeners.retain_mut(|stream| stream.write_all(b"data: reload\n\n").is_ok());
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    range: Option<String>,
+    if_modified_since: Option<String>,
+}
+
+fn read_request(stream: &TcpStream) -> io::Result<Option<Request>> {
+    let mut reader = BufReader::new(stream.take(16 << 10));
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Ok(None);
+    };
+    let target = target.split(['?', '#']).next().unwrap_or_default();
+    let Some(path) = percent_decode(target) else {
+        return Ok(None);
+    };
+    let mut request = Request {
+        method: method.to_owned(),
+        path,
+        range: None,
+        if_modified_since: None,
+    };
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = Some(value.trim().to_owned());
+        if name.eq_ignore_ascii_case("range") {
+            request.range = value;
+        } else if name.eq_ignore_ascii_case("if-modified-since") {
+            request.if_modified_since = value;
+        }
+    }
+    Ok(Some(request))
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn respond(
+    mut stream: TcpStream,
+    root: &Path,
+    hashed_assets: bool,
+    listeners: &Mutex<Vec<TcpStream>>,
+) -> io::Result<()> {
+    let Some(request) = read_request(&stream)? else {
+        return status(&mut stream, "400 Bad Request");
+    };
+    if request.method != "GET" && request.method != "HEAD" {
+        return status(&mut stream, "405 Method Not Allowed");
+    }
+    if request.path == RELOAD_PATH {
+        // Locked before answering, so that no reload is missed in between.
+        let mut listeners = listeners.lock().unwrap();
+        stream.write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\r\n",
+        )?;
+        listeners.push(stream);
+        return Ok(());
+    }
+    // Only paths within the root.
+    let relative = Path::new(request.path.trim_start_matches('/'));
+    if !relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return status(&mut stream, "404 Not Found");
+    }
+    let mut path = root.join(relative);
+    if path.is_dir() {
+        path.push(crate::INDEX_PATH);
+    }
+    let Ok(mut file) = File::open(&path) else {
+        return status(&mut stream, "404 Not Found");
+    };
+    let metadata = file.metadata()?;
+    let modified: Option<DateTime<Utc>> = metadata.modified().ok().map(Into::into);
+    let last_modified = modified.map(|m| m.format(HTTP_DATE).to_string());
+    let not_modified = modified
+        .zip(request.if_modified_since.as_deref())
+        .and_then(|(modified, since)| {
+            let since = DateTime::parse_from_rfc2822(since).ok()?;
+            Some(modified.timestamp() <= since.timestamp())
+        })
+        .unwrap_or(false);
+    let content_type = content_type(&path);
+    // Hashed names change with their contents, and the rest is checked with
+    // If-Modified-Since.
+    let cache_control = if hashed_assets && crate::logical_path(&path).is_some() {
+        "public, max-age=31536000, immutable"
+    } else {
+        "no-cache"
+    };
+    let mut headers = format!("Content-Type: {content_type}\r\nCache-Control: {cache_control}\r\n");
+    if let Some(last_modified) = &last_modified {
+        headers.push_str(&format!("Last-Modified: {last_modified}\r\n"));
+    }
+    if not_modified {
+        return write_head(&mut stream, "304 Not Modified", &headers);
+    }
+    let with_body = request.method == "GET";
+    if content_type.starts_with("text/html") {
+        let mut page = fs::read_to_string(&path)?;
+        let end = page.rfind("</body>").unwrap_or(page.len());
+        page.insert_str(end, RELOAD_SCRIPT);
+        headers.push_str(&format!("Content-Length: {}\r\n", page.len()));
+        write_head(&mut stream, "200 OK", &headers)?;
+        if with_body {
+            stream.write_all(page.as_bytes())?;
+        }
+        return Ok(());
+    }
+    let len = metadata.len();
+    headers.push_str("Accept-Ranges: bytes\r\n");
+    let (code, start, end) = match request.range.as_deref().map(|r| parse_range(r, len)) {
+        Some(Ok(Some((start, end)))) => {
+            headers.push_str(&format!("Content-Range: bytes {start}-{end}/{len}\r\n"));
+            ("206 Partial Content", start, end + 1)
+        }
+        Some(Err(())) => {
+            headers.push_str(&format!(
+                "Content-Range: bytes */{len}\r\nContent-Length: 0\r\n"
+            ));
+            return write_head(&mut stream, "416 Range Not Satisfiable", &headers);
+        }
+        // Several ranges are answered with the whole file.
+        Some(Ok(None)) | None => ("200 OK", 0, len),
+    };
+    headers.push_str(&format!("Content-Length: {}\r\n", end - start));
+    write_head(&mut stream, code, &headers)?;
+    if with_body {
+        file.seek(SeekFrom::Start(start))?;
+        io::copy(&mut file.take(end - start), &mut stream)?;
+    }
+    Ok(())
+}
+
+/// Parses a `Range` header of a file of `len` bytes into the first and last
+/// byte wanted. Returns `None` for several ranges, which aren't supported, and
+/// an error if the range is outside the file.
+fn parse_range(range: &str, len: u64) -> Result<Option<(u64, u64)>, ()> {
+    let Some(range) = range.strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    if range.contains(',') {
+        return Ok(None);
+    }
+    let (start, end) = range.trim().split_once('-').ok_or(())?;
+    let (start, end) = match (start.parse::<u64>(), end.parse::<u64>()) {
+        (Ok(start), Ok(end)) => (start, end.min(len.saturating_sub(1))),
+        (Ok(start), Err(_)) if end.is_empty() => (start, len.saturating_sub(1)),
+        // The last this many bytes.
+        (Err(_), Ok(suffix)) if start.is_empty() && suffix > 0 => {
+            (len.saturating_sub(suffix), len.saturating_sub(1))
+        }
+        _ => return Err(()),
+    };
+    if start > end || start >= len {
+        return Err(());
+    }
+    Ok(Some((start, end)))
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("webmanifest") => "application/manifest+json",
+        Some("xml") => "application/xml",
+        Some("svg") => "image/svg+xml",
+        Some("ico") => "image/x-icon",
+        Some("pdf") => "application/pdf",
+        Some("zip") => "application/zip",
+        Some("txt") => "text/plain; charset=utf-8",
+        _ => crate::mime_type(path),
+    }
+}
+
+fn write_head(stream: &mut TcpStream, status: &str, headers: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\n{headers}Connection: close\r\n\r\n"
+    )
+}
+
+fn status(stream: &mut TcpStream, status: &str) -> io::Result<()> {
+    write_head(stream, status, "Content-Length: 0\r\n")
+}
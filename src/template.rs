@@ -0,0 +1,141 @@
+use std::{fs, io, path::Path};
+
+/// Text in which `{{name}}` is replaced by a value, as HTML.
+#[derive(Debug)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug)]
+enum Part {
+    Text(String),
+    Value(&'static str),
+}
+
+impl Template {
+    /// Parses `s`, in which only `names` may be used.
+    pub fn parse(s: &str, names: &[&'static str]) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = s;
+        while let Some(start) = rest.find("{{") {
+            parts.push(Part::Text(rest[..start].to_owned()));
+            let end = rest[start..]
+                .find("}}")
+                .ok_or_else(|| format!("unclosed {{{{ at byte {}", s.len() - rest.len() + start))?;
+            let name = rest[start + 2..start + end].trim();
+            let name = names.iter().find(|n| **n == name).ok_or_else(|| {
+                format!(
+                    "unknown value {{{{{name}}}}}, expected one of {}",
+                    names.join(", ")
+                )
+            })?;
+            parts.push(Part::Value(name));
+            rest = &rest[start + end + 2..];
+        }
+        parts.push(Part::Text(rest.to_owned()));
+        Ok(Template { parts })
+    }
+
+    /// Fills in the values by their names, leaving out those not given.
+    pub fn render(&self, values: &[(&str, &str)]) -> String {
+        let mut s = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => s.push_str(text),
+                Part::Value(name) => {
+                    if let Some((_, value)) = values.iter().find(|(n, _)| n == name) {
+                        s.push_str(value);
+                    }
+                }
+            }
+        }
+        s
+    }
+}
+
+/// The templates of the parts of a page. Each is read from a file of its name
+/// in the `--templates` directory, or else is built in.
+#[derive(Debug)]
+pub struct Templates {
+    /// `page.html`, the whole document.
+    pub page: Template,
+    /// `figure.html`, a thumbnail in the grid.
+    pub figure: Template,
+    /// `nav.html`, the links to the pages.
+    pub nav: Template,
+}
+
+/// The directory of templates looked for in the input directory without
+/// `--templates`.
+pub const DIR_NAME: &str = "templates";
+
+pub const PAGE_NAMES: &[&str] = &["lang", "title", "root", "head", "main", "nav", "script"];
+pub const FIGURE_NAMES: &[&str] = &[
+    "id", "class", "href", "lightbox", "img", "src", "label", "exif", "caption",
+];
+pub const NAV_NAMES: &[&str] = &["items"];
+
+const PAGE: &str = r##"
+<!DOCTYPE html>
+<html lang="{{lang}}">
+
+<head>
+    <meta charset="utf-8">
+    <title>{{title}}</title>
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <link rel="stylesheet" type="text/css" href="{{root}}css/style.css">
+{{head}}</head>
+
+<body>
+<main>
+{{main}}</main>
+{{nav}}{{script}}</body>
+
+
+</html>
+
+"##;
+
+const FIGURE: &str = r#"<figure id="{{id}}"{{class}}><a href="{{href}}"{{lightbox}}>{{img}}</a>{{exif}}{{caption}}</figure>
+"#;
+
+const NAV: &str = r#"<hr>
+<nav>
+<ul class="nav">
+{{items}}</ul>
+</nav>
+"#;
+
+impl Default for Templates {
+    fn default() -> Self {
+        Templates {
+            page: Template::parse(PAGE, PAGE_NAMES).unwrap(),
+            figure: Template::parse(FIGURE, FIGURE_NAMES).unwrap(),
+            nav: Template::parse(NAV, NAV_NAMES).unwrap(),
+        }
+    }
+}
+
+impl Templates {
+    /// Reads the templates in `dir`, keeping the built-in ones of those that
+    /// are missing.
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        let read = |name: &str, default: &str, names: &[&'static str]| {
+            let path = dir.join(name);
+            let s = match fs::read_to_string(&path) {
+                Ok(s) => s,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => default.to_owned(),
+                Err(e) => return Err(format!("{}: {e}", path.display())),
+            };
+            Template::parse(&s, names).map_err(|e| format!("{}: {e}", path.display()))
+        };
+        if !dir.is_dir() {
+            return Err(format!("{} is not a directory", dir.display()));
+        }
+        Ok(Templates {
+            page: read("page.html", PAGE, PAGE_NAMES)?,
+            figure: read("figure.html", FIGURE, FIGURE_NAMES)?,
+            nav: read("nav.html", NAV, NAV_NAMES)?,
+        })
+    }
+}
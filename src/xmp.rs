@@ -0,0 +1,106 @@
+use std::{fs, path::Path};
+
+/// Extracts the XMP packet embedded in a file, if any.
+pub fn read_embedded(path: &Path) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    let start = find(&bytes, b"<x:xmpmeta")?;
+    const END: &[u8] = b"</x:xmpmeta>";
+    let end = start + find(&bytes[start..], END)? + END.len();
+    String::from_utf8(bytes[start..end].to_vec()).ok()
+}
+
+/// Returns the items of an RDF container property like `dc:subject`.
+pub fn list(xmp: &str, property: &str) -> Vec<String> {
+    let Some((container, _)) = element(xmp, property) else {
+        return Vec::new();
+    };
+    let mut items = Vec::new();
+    let mut rest = container;
+    while let Some((content, end)) = element(rest, "rdf:li") {
+        let item = unescape(content.trim());
+        if !item.is_empty() {
+            items.push(item);
+        }
+        rest = &rest[end..];
+    }
+    items
+}
+
+/// Returns the keywords (`dc:subject`) of an XMP packet.
+pub fn keywords(xmp: &str) -> Vec<String> {
+    list(xmp, "dc:subject")
+}
+
+/// Returns the face regions of the Metadata Working Group's `mwg-rs:Regions`,
+/// as written by digiKam, with the name of the person if known.
+pub fn faces(xmp: &str) -> Vec<Option<String>> {
+    let Some((regions, _)) = element(xmp, "mwg-rs:RegionList") else {
+        return Vec::new();
+    };
+    let mut faces = Vec::new();
+    let mut rest = regions;
+    while let Some((region, end)) = element(rest, "rdf:li") {
+        if property(region, "mwg-rs:Type").as_deref() == Some("Face") {
+            faces.push(property(region, "mwg-rs:Name").filter(|name| !name.is_empty()));
+        }
+        rest = &rest[end..];
+    }
+    faces
+}
+
+/// Returns a simple property, written either as an attribute like
+/// `xmp:Rating="5"` or as an element like `<xmp:Rating>5</xmp:Rating>`.
+pub fn property(xmp: &str, name: &str) -> Option<String> {
+    let mut from = 0;
+    while let Some(i) = xmp[from..].find(name) {
+        let start = from + i;
+        from = start + name.len();
+        let preceded_by_space = xmp[..start].ends_with(char::is_whitespace);
+        let rest = xmp[from..].trim_start();
+        if let (true, Some(rest)) = (preceded_by_space, rest.strip_prefix('=')) {
+            let rest = rest.trim_start();
+            let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+            let value = &rest[1..];
+            return Some(unescape(&value[..value.find(quote)?]));
+        }
+    }
+    let (content, _) = element(xmp, name)?;
+    Some(unescape(content.trim())).filter(|value| !value.contains('<'))
+}
+
+/// Finds the first `<name ...>...</name>` element, returning its content and
+/// the index just past it.
+fn element<'a>(xml: &'a str, name: &str) -> Option<(&'a str, usize)> {
+    let open = format!("<{name}");
+    let close = format!("</{name}>");
+    let mut from = 0;
+    loop {
+        let start = from + xml[from..].find(&open)? + open.len();
+        // Make sure it is not just a prefix of another element's name.
+        if !matches!(
+            xml[start..].chars().next(),
+            Some('>' | '/' | ' ' | '\t' | '\r' | '\n')
+        ) {
+            from = start;
+            continue;
+        }
+        let content_start = start + xml[start..].find('>')? + 1;
+        if xml[..content_start].ends_with("/>") {
+            return Some(("", content_start));
+        }
+        let end = content_start + xml[content_start..].find(&close)?;
+        return Some((&xml[content_start..end], end + close.len()));
+    }
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
@@ -0,0 +1,2403 @@
+//! Runs photo2html on the photos in tests/fixtures, 8x8 JPEGs with known
+//! capture times, and checks the generated files. Without ImageMagick, the
+//! images are left empty.
+
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read, Write},
+    iter,
+    net::TcpStream,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use tempfile::TempDir;
+
+/// Taken at 2023-08-15 14:22:10+09:00.
+const A: &str = "a.jpg";
+/// Taken at 2023-08-16 10:00:00+02:00.
+const B: &str = "b.jpg";
+/// Taken at 2023-08-16 18:30:00+02:00.
+const C: &str = "c.jpg";
+/// Taken at 2023-07-01 12:00:00+00:00 with an "Alpha 7" and a "50mm F1.8".
+const D: &str = "d.jpg";
+/// Taken at 2023-07-02 12:00:00+00:00 with an "Alpha 7" and a "24-70mm F2.8".
+const E: &str = "e.jpg";
+/// A screenshot created at 2023-09-01 09:30:00+02:00, as a PNG `tEXt` chunk.
+const F: &str = "f.png";
+/// Taken at 2023-08-17 09:00:00+02:00 at 52°31'12" N, 13°24'36" W.
+const G: &str = "g.jpg";
+/// Taken at 2023-08-14 10:00:00+02:00, stored 16x8 with orientation 6, so
+/// shown 8x16.
+const H: &str = "h.jpg";
+
+struct Gallery {
+    dir: TempDir,
+}
+
+impl Gallery {
+    fn new(fixtures: &[&str]) -> Self {
+        let gallery = Self {
+            dir: TempDir::new().unwrap(),
+        };
+        fs::create_dir(gallery.input()).unwrap();
+        for name in fixtures {
+            gallery.add(name, &fixture(name));
+        }
+        gallery
+    }
+
+    fn input(&self) -> PathBuf {
+        self.dir.path().join("in")
+    }
+
+    fn output(&self) -> PathBuf {
+        self.dir.path().join("out")
+    }
+
+    fn add(&self, name: &str, bytes: &[u8]) {
+        fs::write(self.input().join(name), bytes).unwrap();
+    }
+
+    /// Generates the gallery, asserting that it succeeds.
+    fn run(&self, args: &[&str]) -> Output {
+        let output = self.try_run(args);
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output
+    }
+
+    fn try_run(&self, args: &[&str]) -> Output {
+        self.command(args).output().unwrap()
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_photo2html"));
+        if !has_magick() {
+            command.arg("--image-backend").arg("none");
+        }
+        command
+            .arg("--output-dir")
+            .arg(self.output())
+            .args(args)
+            .arg(self.input());
+        command
+    }
+
+    fn page(&self, name: &str) -> String {
+        fs::read_to_string(self.output().join(name)).unwrap()
+    }
+}
+
+fn has_magick() -> bool {
+    Command::new("magick")
+        .arg("-version")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+fn fixture(name: &str) -> Vec<u8> {
+    fs::read(
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name),
+    )
+    .unwrap()
+}
+
+/// Returns a copy of fixture A taken on another day, given as `YYYY:MM:DD`.
+fn taken_on(day: &str) -> Vec<u8> {
+    let mut bytes = fixture(A);
+    let at = bytes.windows(10).position(|w| w == b"2023:08:15").unwrap();
+    bytes[at..at + 10].copy_from_slice(day.as_bytes());
+    bytes
+}
+
+/// Returns the thumbnails on a page, in order.
+fn thumbnails(page: &str) -> Vec<&str> {
+    page.match_indices("<img src=\"./thumbnail/")
+        .map(|(i, prefix)| {
+            let rest = &page[i + prefix.len()..];
+            &rest[..rest.find('"').unwrap()]
+        })
+        .collect()
+}
+
+#[test]
+fn photos_are_grouped_by_day_newest_first() {
+    let gallery = Gallery::new(&[A, B, C]);
+    gallery.run(&[]);
+    let index = gallery.page("index.html");
+    assert_eq!(thumbnails(&index), [C, B, A]);
+    assert_eq!(
+        headings(&index),
+        [
+            "<time datetime=\"2023-08-16\">2023-08-16</time>",
+            "<time datetime=\"2023-08-15\">2023-08-15</time>"
+        ]
+    );
+    assert_eq!(index, gallery.page("page_0.html"));
+    assert!(!gallery.output().join("page_1.html").exists());
+}
+
+/// Returns the contents of the `<h2>` headings.
+fn headings(page: &str) -> Vec<&str> {
+    page.match_indices("<h2>")
+        .map(|(i, prefix)| {
+            let rest = &page[i + prefix.len()..];
+            &rest[..rest.find("</h2>").unwrap()]
+        })
+        .collect()
+}
+
+#[test]
+fn day_headings_keep_the_date_for_machines() {
+    let gallery = Gallery::new(&[A]);
+    gallery.run(&["--date-format", "%A, %e. %B %Y", "--locale", "de"]);
+    assert_eq!(
+        headings(&gallery.page("index.html")),
+        ["<time datetime=\"2023-08-15\">Dienstag, 15. August 2023</time>"]
+    );
+}
+
+#[test]
+fn pages_are_in_the_language_of_the_locale() {
+    // Neighbours on the day of B.
+    let gallery = Gallery::new(&[B, C]);
+    gallery.run(&[
+        "--locale",
+        "de",
+        "--on-this-day",
+        "--full-exif",
+        "--originals",
+        "copy",
+        "--permalinks",
+    ]);
+    let index = gallery.page("index.html");
+    assert!(index.contains("class=\"on_this_day\">An diesem Tag</a>"));
+    assert!(index.contains("download>Original</a>"));
+    assert!(index.contains("<summary>EXIF-Daten</summary>"));
+    let permalinks: String = fs::read_dir(gallery.output().join("photo"))
+        .unwrap()
+        .map(|e| fs::read_to_string(e.unwrap().path()).unwrap())
+        .collect();
+    assert!(permalinks.contains("<dt>Aufgenommen</dt>"));
+    assert!(permalinks.contains(">Vorheriges</a>"));
+    assert!(permalinks.contains(">Nächstes</a>"));
+}
+
+#[test]
+fn photos_can_be_grouped_by_lens() {
+    let gallery = Gallery::new(&[A, B, D, E]);
+    gallery.run(&["--group-by", "lens"]);
+    let index = gallery.page("index.html");
+    assert_eq!(headings(&index), ["24-70mm F2.8", "50mm F1.8", "Unknown"]);
+    assert_eq!(thumbnails(&index), [E, D, B, A]);
+}
+
+#[test]
+fn images_are_named_after_photos() {
+    let gallery = Gallery::new(&[A, B, C]);
+    gallery.run(&[]);
+    for name in [A, B, C] {
+        assert!(gallery.output().join("thumbnail").join(name).is_file());
+        assert!(gallery.output().join("img").join(name).is_file());
+    }
+}
+
+#[test]
+fn nav_is_inside_body() {
+    let gallery = Gallery::new(&[A]);
+    gallery.run(&[]);
+    let index = gallery.page("index.html");
+    let nav = index.find("<nav>").unwrap();
+    assert!(index.find("</main>").unwrap() < nav);
+    assert!(nav < index.find("</body>").unwrap());
+}
+
+#[test]
+fn truncated_photos_are_skipped() {
+    let gallery = Gallery::new(&[A, B]);
+    let bytes = fixture(C);
+    gallery.add(C, &bytes[..bytes.len() - 2]);
+    let output = gallery.run(&[]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("skipping"));
+    assert_eq!(thumbnails(&gallery.page("index.html")), [B, A]);
+}
+
+#[test]
+fn justified_rows_fill_the_page() {
+    let widths = |index: &str| -> Vec<Vec<String>> {
+        index
+            .split("<div class=\"row\">\n")
+            .skip(1)
+            .map(|row| {
+                row.match_indices("<div style=\"width: ")
+                    .map(|(i, prefix)| {
+                        let rest = &row[i + prefix.len()..];
+                        rest[..rest.find('"').unwrap()].to_owned()
+                    })
+                    .collect()
+            })
+            .collect()
+    };
+    let args = [
+        "--layout",
+        "justified",
+        "--row-height",
+        "400",
+        "--sort",
+        "filename",
+    ];
+    // Square, apart from H which is upright.
+    let gallery = Gallery::new(&[A, B, C, D, E, H]);
+    gallery.run(&args);
+    let index = gallery.page("index.html");
+    // Four fill 1600px at 400px, and the last row is left as high.
+    assert_eq!(
+        widths(&index),
+        [vec!["25.000%"; 4], vec!["25.000%", "12.500%"]]
+    );
+    assert!(
+        index.contains(" sizes=\"(max-width: 600px) 100vw, (max-width: 1600px) 12.5vw, 200px\"")
+    );
+    assert!(gallery.page("css/style.css").contains(".justified .row {"));
+
+    // Cropped to squares, H too, though its dimensions are known for the JSON.
+    let gallery = Gallery::new(&[A, B, C, D, E, H]);
+    gallery.run(&[&args[..], &["--thumbnail-fit", "cover", "--json"]].concat());
+    assert_eq!(
+        widths(&gallery.page("index.html")),
+        [vec!["25.000%"; 4], vec!["25.000%"; 2]]
+    );
+}
+
+#[test]
+fn newer_photos_keep_older_pages() {
+    let gallery = Gallery::new(&[]);
+    for month in 5..=7 {
+        for day in 1..=20 {
+            gallery.add(
+                &format!("{month}-{day}.jpg"),
+                &taken_on(&format!("2023:{month:02}:{day:02}")),
+            );
+        }
+    }
+    gallery.run(&[]);
+    let oldest = gallery.page("page_0.html");
+    assert_eq!(thumbnails(&oldest).len(), 50);
+    assert_eq!(thumbnails(&gallery.page("page_1.html")).len(), 10);
+
+    gallery.add(A, &fixture(A));
+    gallery.run(&[]);
+    assert_eq!(
+        thumbnails(&gallery.page("page_0.html")),
+        thumbnails(&oldest)
+    );
+    assert_eq!(thumbnails(&gallery.page("index.html"))[0], A);
+    assert!(!gallery.output().join("page_2.html").exists());
+}
+
+#[test]
+fn assets_are_copied_with_their_paths() {
+    let gallery = Gallery::new(&[A]);
+    let assets = gallery.dir.path().join("assets");
+    fs::create_dir_all(assets.join(".well-known")).unwrap();
+    fs::write(assets.join("CNAME"), "photos.example.com\n").unwrap();
+    fs::write(assets.join(".well-known/security.txt"), "Contact: x\n").unwrap();
+    gallery.run(&["--copy-assets", assets.to_str().unwrap()]);
+    let output = gallery.output();
+    assert_eq!(
+        fs::read_to_string(output.join("CNAME")).unwrap(),
+        "photos.example.com\n"
+    );
+    assert!(output.join(".well-known/security.txt").is_file());
+
+    gallery.run(&["--copy-assets", assets.to_str().unwrap(), "--clean"]);
+    assert!(!output.join("CNAME").exists());
+    assert!(!output.join(".well-known").exists());
+}
+
+#[test]
+fn clean_fails_without_panicking() {
+    let gallery = Gallery::new(&[A]);
+    fs::create_dir_all(gallery.output()).unwrap();
+    fs::write(gallery.output().join("index.html"), "mine").unwrap();
+    let output = gallery.try_run(&["--clean"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("was not generated by photo2html"),
+        "{stderr}"
+    );
+    assert_eq!(gallery.page("index.html"), "mine");
+
+    fs::remove_file(gallery.output().join("index.html")).unwrap();
+    gallery.run(&[]);
+    let missing = gallery.dir.path().join("assets");
+    let output = gallery.try_run(&["--copy-assets", missing.to_str().unwrap(), "--clean"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(!stderr.contains("panicked"), "{stderr}");
+    assert!(stderr.contains("assets"), "{stderr}");
+}
+
+#[test]
+fn symlinks_to_photos_are_followed_and_loops_skipped() {
+    let gallery = Gallery::new(&[A]);
+    let elsewhere = gallery.dir.path().join("elsewhere");
+    fs::create_dir(&elsewhere).unwrap();
+    fs::write(elsewhere.join(B), fixture(B)).unwrap();
+    std::os::unix::fs::symlink(elsewhere.join(B), gallery.input().join(B)).unwrap();
+    std::os::unix::fs::symlink(".", gallery.input().join("loop")).unwrap();
+    let output = gallery.run(&[]);
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("loop"));
+    assert_eq!(thumbnails(&gallery.page("index.html")), [B, A]);
+}
+
+#[test]
+fn hashed_assets_are_listed_in_the_manifest() {
+    let gallery = Gallery::new(&[A]);
+    gallery.run(&["--hashed-assets"]);
+    let index = gallery.page("index.html");
+    let [thumbnail] = thumbnails(&index)[..] else {
+        panic!("{index}");
+    };
+    assert!(thumbnail.starts_with("a.") && thumbnail.ends_with(".jpg"));
+    assert_eq!(thumbnail.len(), "a.12345678.jpg".len());
+    assert!(gallery.output().join("thumbnail").join(thumbnail).is_file());
+    let manifest = gallery.page("assets.json");
+    assert!(manifest.contains(&format!("\"thumbnail/a.jpg\": \"thumbnail/{thumbnail}\"")));
+    assert!(manifest.contains("\"img/a.jpg\": \"img/a."));
+}
+
+#[test]
+fn asset_manifest_lists_the_images_that_are_shown() {
+    let gallery = Gallery::new(&[]);
+    for day in 1..=20 {
+        gallery.add(
+            &format!("{day}.jpg"),
+            &taken_on(&format!("2023:05:{day:02}")),
+        );
+    }
+    gallery.run(&["--hashed-assets"]);
+    // A changed photo gets a new name.
+    gallery.add("20.jpg", &taken_on("2023:06:20"));
+    gallery.run(&["--hashed-assets"]);
+    let manifest = gallery.page("assets.json");
+    let entries: Vec<(&str, &str)> = manifest
+        .lines()
+        .filter_map(|line| {
+            let (logical, hashed) = line.trim().trim_end_matches(',').split_once(": ")?;
+            Some((logical.trim_matches('"'), hashed.trim_matches('"')))
+        })
+        .collect();
+    // A full image and three widths of thumbnails each.
+    assert_eq!(entries.len(), 80, "{manifest}");
+    for (logical, hashed) in &entries {
+        assert!(
+            gallery.output().join(hashed).is_file(),
+            "{logical}: {hashed}"
+        );
+    }
+    for thumbnail in thumbnails(&gallery.page("index.html")) {
+        let hashed = format!("thumbnail/{thumbnail}");
+        assert!(entries.iter().any(|(_, h)| *h == hashed), "{hashed}");
+    }
+    let leftovers: Vec<_> = ["", "thumbnail", "img"]
+        .into_iter()
+        .flat_map(|dir| fs::read_dir(gallery.output().join(dir)).unwrap())
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "tmp"))
+        .collect();
+    assert!(leftovers.is_empty(), "{leftovers:?}");
+}
+
+#[test]
+fn similar_photos_are_skipped() {
+    let gallery = Gallery::new(&[]);
+    // Getting darker to the right, which each 8 pixels of the hash tell.
+    let gradient = image::RgbImage::from_fn(64, 64, |x, _| image::Rgb([255 - 3 * x as u8; 3]));
+    gradient
+        .save(gallery.input().join("1-original.png"))
+        .unwrap();
+    gradient
+        .save(gallery.input().join("2-reencoded.jpg"))
+        .unwrap();
+    let mut brighter = gradient.clone();
+    for pixel in brighter.pixels_mut() {
+        pixel.0 = pixel.0.map(|value| value.saturating_add(10));
+    }
+    brighter
+        .save(gallery.input().join("3-brighter.png"))
+        .unwrap();
+    let mut mirrored = gradient.clone();
+    image::imageops::flip_horizontal_in_place(&mut mirrored);
+    mirrored
+        .save(gallery.input().join("4-mirrored.png"))
+        .unwrap();
+
+    let output = gallery.run(&["--skip-similar", "5"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    for name in ["2-reencoded.jpg", "3-brighter.png"] {
+        assert!(stderr.contains(&format!("{name}: looks like")), "{stderr}");
+    }
+    let index = gallery.page("index.html");
+    let mut kept = thumbnails(&index);
+    kept.sort();
+    assert_eq!(kept, ["1-original.jpg", "4-mirrored.jpg"]);
+}
+
+#[test]
+fn excluded_days_are_left_out() {
+    let gallery = Gallery::new(&[A, B, C]);
+    gallery.run(&["--exclude-date", "2023-08-16"]);
+    assert_eq!(thumbnails(&gallery.page("index.html")), [A]);
+    assert!(!gallery.output().join("thumbnail").join(B).exists());
+}
+
+#[test]
+fn spans_reaching_before_all_time_include_everything() {
+    let gallery = Gallery::new(&[A, B, C]);
+    gallery.run(&["--last", "1d"]);
+    assert!(thumbnails(&gallery.page("index.html")).is_empty());
+    for span in ["300000y", "4000000000d"] {
+        gallery.run(&["--last", span]);
+        assert_eq!(thumbnails(&gallery.page("index.html")), [C, B, A], "{span}");
+    }
+}
+
+#[test]
+fn excluded_ranges_leave_no_empty_pages() {
+    let gallery = Gallery::new(&[]);
+    for month in 5..=7 {
+        for day in 1..=20 {
+            gallery.add(
+                &format!("{month}-{day}.jpg"),
+                &taken_on(&format!("2023:{month:02}:{day:02}")),
+            );
+        }
+    }
+    gallery.run(&["--exclude-range", "2023-06-01..2023-06-30"]);
+    assert_eq!(thumbnails(&gallery.page("page_0.html")).len(), 40);
+    assert!(!gallery.output().join("page_1.html").exists());
+}
+
+#[test]
+fn thumbnails_and_full_images_can_differ_in_format() {
+    let gallery = Gallery::new(&[A]);
+    gallery.run(&["--format", "webp", "--image-format", "jpeg"]);
+    let index = gallery.page("index.html");
+    assert_eq!(thumbnails(&index), ["a.webp"]);
+    assert!(index.contains("href=\"img/a.jpg\""));
+    assert!(gallery.output().join("img/a.jpg").is_file());
+}
+
+#[test]
+fn thumbnails_can_come_in_several_formats() {
+    let gallery = Gallery::new(&[A]);
+    gallery.run(&[
+        "--thumbnail-widths",
+        "256",
+        "--source-formats",
+        "avif,webp,jpeg",
+    ]);
+    let index = gallery.page("index.html");
+    let sizes = index
+        .split(" sizes=\"")
+        .nth(1)
+        .unwrap()
+        .split('"')
+        .next()
+        .unwrap();
+    let sources = format!(
+        "<picture>\
+         <source type=\"image/avif\" srcset=\"./thumbnail/256/a.avif 256w, ./thumbnail/a.avif 512w\" sizes=\"{sizes}\">\
+         <source type=\"image/webp\" srcset=\"./thumbnail/256/a.webp 256w, ./thumbnail/a.webp 512w\" sizes=\"{sizes}\">\
+         <img src=\"./thumbnail/a.jpg\""
+    );
+    assert!(index.contains(&sources), "{index}");
+    assert!(index.contains("</picture>"));
+    for path in ["thumbnail/a.avif", "thumbnail/256/a.webp"] {
+        assert!(gallery.output().join(path).is_file(), "{path}");
+    }
+
+    gallery.run(&["--thumbnail-widths", "256"]);
+    assert!(!gallery.page("index.html").contains("<picture>"));
+    assert!(!gallery.output().join("thumbnail/a.avif").exists());
+    assert!(!gallery.output().join("thumbnail/256/a.webp").exists());
+}
+
+#[test]
+fn photos_can_be_sorted_by_filename_with_numbers_by_value() {
+    let gallery = Gallery::new(&[]);
+    for name in ["img10", "img2", "IMG_1a", "img02", "img1b"] {
+        gallery.add(&format!("{name}.jpg"), &fixture(A));
+    }
+    gallery.run(&["--sort", "filename"]);
+    let index = gallery.page("index.html");
+    let names: Vec<_> = thumbnails(&index)
+        .iter()
+        .map(|name| name.strip_suffix(".jpg").unwrap())
+        .collect();
+    // Zero padding only breaks ties, and case is not folded.
+    assert_eq!(names, ["IMG_1a", "img1b", "img02", "img2", "img10"]);
+}
+
+#[test]
+fn check_writes_nothing() {
+    let gallery = Gallery::new(&[A, B, C]);
+    gallery.run(&["--check"]);
+    assert!(!gallery.output().exists());
+}
+
+#[test]
+fn check_fails_without_capture_times() {
+    let gallery = Gallery::new(&[A]);
+    gallery.add(B, &taken_on("2023:13:45"));
+    let output = gallery.try_run(&["--check"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("b.jpg has no capture time"), "{stderr}");
+}
+
+#[test]
+fn names_in_other_encodings_are_linked_in_utf_8() {
+    use std::os::unix::ffi::OsStrExt as _;
+
+    let gallery = Gallery::new(&[A]);
+    let name = std::ffi::OsStr::from_bytes(b"caf\xe9.jpg");
+    fs::write(gallery.input().join(name), fixture(B)).unwrap();
+    gallery.run(&["--hashed-assets", "--json", "--permalinks"]);
+    let index = gallery.page("index.html");
+    assert!(index.contains("./img/caf\u{fffd}."), "{index}");
+    let assets = gallery.page("assets.json");
+    assert!(assets.contains("\"img/caf\u{fffd}.jpg\""), "{assets}");
+    assert!(gallery.page("encoders.json").contains("caf\u{fffd}"));
+}
+
+#[test]
+fn rotation_sidecars_are_not_photos() {
+    let gallery = Gallery::new(&[A]);
+    gallery.add("a.jpg.rotate", b"90\n");
+    let output = gallery.run(&[]);
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("skipping"));
+    assert_eq!(thumbnails(&gallery.page("index.html")), [A]);
+}
+
+#[test]
+fn dense_days_become_film_strips() {
+    let gallery = Gallery::new(&[A, B, C]);
+    gallery.run(&["--dense-days", "1"]);
+    let index = gallery.page("index.html");
+    let strip = index.find("<div class=\"film-strip\">").unwrap();
+    assert_eq!(index.matches("<div class=\"film-strip\">").count(), 1);
+    assert!(index[..strip].contains("2023-08-16"));
+    assert!(index[strip..].contains("<summary>Show all (2 photos)</summary>"));
+    assert!(gallery.output().join("strip").join(B).is_file());
+    assert_eq!(thumbnails(&index), [C, B, A]);
+}
+
+#[test]
+fn duplicates_are_skipped() {
+    let gallery = Gallery::new(&[A]);
+    gallery.add("copy.jpg", &fixture(A));
+    // Which looks the same, but was taken on another day.
+    gallery.add("later.jpg", &taken_on("2023:08:20"));
+    let output = gallery.run(&["--skip-duplicates"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("copy.jpg: the same as"), "{stderr}");
+    assert_eq!(thumbnails(&gallery.page("index.html")), ["later.jpg", A]);
+
+    let output = gallery.run(&["--skip-duplicates", "--skip-similar", "0"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("later.jpg: looks like"), "{stderr}");
+    assert_eq!(thumbnails(&gallery.page("index.html")), [A]);
+    assert!(!gallery
+        .output()
+        .join("thumbnail")
+        .join("later.jpg")
+        .exists());
+}
+
+#[test]
+fn bursts_are_stacked() {
+    let gallery = Gallery::new(&[A, B, C]);
+    gallery.add("a-2.jpg", &fixture(A));
+    gallery.run(&["--stack-bursts", "2"]);
+    let index = gallery.page("index.html");
+    assert_eq!(thumbnails(&index), [C, B, "a-2.jpg", A]);
+    let stack = index
+        .find("<details class=\"stack\"><summary>2 photos</summary>")
+        .unwrap();
+    assert!(index[..stack].contains("a-2.jpg"));
+    assert!(index[stack..].contains(A));
+    assert_eq!(index.matches("class=\"stack\"").count(), 1);
+}
+
+#[test]
+fn png_creation_times_are_read() {
+    let gallery = Gallery::new(&[A, F]);
+    gallery.run(&[]);
+    let index = gallery.page("index.html");
+    assert_eq!(thumbnails(&index), ["f.jpg", A]);
+    assert_eq!(
+        headings(&index)[0],
+        "<time datetime=\"2023-09-01\">2023-09-01</time>"
+    );
+}
+
+#[test]
+fn older_pages_are_linked_from_the_archive() {
+    let gallery = Gallery::new(&[]);
+    for month in 5..=7 {
+        for day in 1..=20 {
+            gallery.add(
+                &format!("{month}-{day}.jpg"),
+                &taken_on(&format!("2023:{month:02}:{day:02}")),
+            );
+        }
+    }
+    gallery.run(&["--max-pages", "1"]);
+    let index = gallery.page("index.html");
+    assert!(index.contains("href=\"page_1.html\""));
+    assert!(!index.contains("href=\"page_0.html\""));
+    assert!(index.contains("href=\"archive.html\""));
+    let archive = gallery.page("archive.html");
+    let list = &archive[..archive.find("</main>").unwrap()];
+    assert!(list.contains("href=\"page_0.html\""));
+    assert!(!list.contains("href=\"page_1.html\""));
+    assert_eq!(thumbnails(&gallery.page("page_0.html")).len(), 50);
+}
+
+#[test]
+fn toc_links_every_day() {
+    let gallery = Gallery::new(&[A, B, C, D]);
+    gallery.run(&["--toc"]);
+    let index = gallery.page("index.html");
+    assert!(index.contains("<a href=\"toc.html\" class=\"toc\">"));
+    assert!(index.contains("<h2 id=\"2023-08-16\">"));
+    let toc = gallery.page("toc.html");
+    let main = &toc[toc.find("<main>").unwrap()..toc.find("</main>").unwrap()];
+    assert_eq!(main.matches("<h3>2023</h3>").count(), 1);
+    let august = main.find("<h4>August</h4>").unwrap();
+    let july = main.find("<h4>July</h4>").unwrap();
+    assert!(august < july);
+    assert!(
+        main[august..july].contains("<a href=\"page_0.html#2023-08-16\">2023-08-16</a> 2 photos")
+    );
+    assert!(main[july..].contains("<a href=\"page_0.html#2023-07-01\">2023-07-01</a> 1 photo"));
+}
+
+/// Returns the contents of every file in `dir`, by path.
+fn tree(dir: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files.extend(tree(&path));
+        } else {
+            files.push((path.clone(), fs::read(path).unwrap()));
+        }
+    }
+    files.sort();
+    files
+}
+
+#[test]
+fn deterministic_runs_are_identical() {
+    let gallery = Gallery::new(&[A, B, C, D, E]);
+    let args = [
+        "--deterministic",
+        "--relative-dates",
+        "--on-this-day",
+        "--toc",
+        "--hashed-assets",
+    ];
+    gallery.run(&args);
+    let first = tree(&gallery.output());
+    fs::remove_dir_all(gallery.output()).unwrap();
+    gallery.run(&args);
+    assert_eq!(tree(&gallery.output()), first);
+}
+
+#[test]
+fn photos_can_be_limited_to_those_with_people() {
+    let gallery = Gallery::new(&[A, B]);
+    gallery.add(
+        "a.jpg.xmp",
+        br#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF><rdf:Description>
+<mwg-rs:Regions rdf:parseType="Resource"><mwg-rs:RegionList><rdf:Bag>
+<rdf:li><rdf:Description mwg-rs:Name="Alice" mwg-rs:Type="Face"/></rdf:li>
+<rdf:li><rdf:Description mwg-rs:Name="Tree" mwg-rs:Type="Focus"/></rdf:li>
+</rdf:Bag></mwg-rs:RegionList></mwg-rs:Regions>
+</rdf:Description></rdf:RDF></x:xmpmeta>"#,
+    );
+    gallery.run(&["--only-with-people", "--people-in-caption", "--permalinks"]);
+    assert_eq!(thumbnails(&gallery.page("index.html")), [A]);
+    let permalink = fs::read_dir(gallery.output().join("photo"))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let permalink = fs::read_to_string(permalink).unwrap();
+    assert!(permalink.contains("<figcaption>Alice</figcaption>"));
+}
+
+/// Returns an XMP sidecar with the keywords.
+fn keywords_xmp(keywords: &[&str]) -> Vec<u8> {
+    let items: String = keywords
+        .iter()
+        .map(|k| format!("<rdf:li>{k}</rdf:li>"))
+        .collect();
+    format!(
+        r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF><rdf:Description>
+<dc:subject><rdf:Bag>{items}</rdf:Bag></dc:subject>
+</rdf:Description></rdf:RDF></x:xmpmeta>"#
+    )
+    .into_bytes()
+}
+
+#[test]
+fn keywords_get_pages() {
+    let gallery = Gallery::new(&[A, B, C]);
+    gallery.add("a.jpg.xmp", &keywords_xmp(&["New York", "cover"]));
+    gallery.add("b.jpg.xmp", &keywords_xmp(&["New York", "Food"]));
+    gallery.run(&["--tags"]);
+
+    let tags = gallery.page("tags.html");
+    assert!(
+        tags.contains("<a href=\"tag_Food.html\">Food</a> 1 photo"),
+        "{tags}"
+    );
+    assert!(tags.contains("<a href=\"tag_New-York.html\">New York</a> 2 photos"));
+    assert!(!tags.contains("cover"));
+    assert_eq!(thumbnails(&gallery.page("tag_New-York.html")), [B, A]);
+    assert_eq!(thumbnails(&gallery.page("tag_Food.html")), [B]);
+    let index = gallery.page("index.html");
+    assert!(index.contains("<a href=\"tags.html\" class=\"tags\">Tags</a>"));
+    assert!(index.contains("<span class=\"tags\"><a href=\"tag_New-York.html\">New York</a> <a href=\"tag_Food.html\">Food</a></span>"));
+
+    gallery.add("b.jpg.xmp", &keywords_xmp(&["New York"]));
+    gallery.run(&["--tags"]);
+    assert!(!gallery.output().join("tag_Food.html").exists());
+}
+
+#[test]
+fn pages_can_be_of_months() {
+    let gallery = Gallery::new(&[A, D, E]);
+    gallery.add("old.jpg", &taken_on("2022:12:31"));
+    gallery.run(&["--paginate-by", "month"]);
+
+    let august = gallery.page("2023/08.html");
+    assert!(
+        august.contains("<img src=\"../thumbnail/a.jpg\""),
+        "{august}"
+    );
+    assert!(august.contains("<a href=\"../img/a.jpg\""));
+    assert!(!august.contains("thumbnail/d.jpg"));
+    assert!(gallery.page("2023/07.html").contains("thumbnail/d.jpg"));
+    assert!(gallery.output().join("2022/12.html").exists());
+    // The years of the nav, of which the newest is expanded.
+    assert!(august.contains("<li><details open><summary>2023</summary>"));
+    assert!(august.contains("<li><details><summary>2022</summary>"));
+    assert!(august.contains("<a href=\"../2023/07.html\" class=\"page_1\">July</a>"));
+    let index = gallery.page("index.html");
+    assert_eq!(thumbnails(&index), [A]);
+    assert!(index.contains("<a href=\"2023/07.html\" class=\"page_1\">July</a>"));
+
+    // A new month leaves the pages of the others as they were.
+    gallery.add("new.jpg", &taken_on("2023:09:01"));
+    gallery.run(&["--paginate-by", "month"]);
+    assert_eq!(thumbnails(&gallery.page("index.html")), ["new.jpg"]);
+    assert!(gallery.page("2023/08.html").contains("thumbnail/a.jpg"));
+
+    gallery.run(&[]);
+    assert!(!gallery.output().join("2023").exists());
+    assert!(gallery.output().join("page_0.html").exists());
+
+    let output = gallery.try_run(&["--paginate-by", "year", "--group-by", "camera"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn version_lists_the_image_backends() {
+    let gallery = Gallery::new(&[A]);
+    let output = gallery.run(&["--version"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with(concat!("photo2html ", env!("CARGO_PKG_VERSION"))));
+    assert!(stdout.contains("  none: built in"));
+    assert!(!gallery.output().exists());
+}
+
+#[test]
+fn locations_are_only_listed_if_not_stripped() {
+    let gallery = Gallery::new(&[G]);
+    gallery.run(&["--full-exif"]);
+    let page = gallery.page("index.html");
+    assert!(page.contains("DateTimeOriginal"), "{page}");
+    assert!(!page.contains("GPSLatitude"), "{page}");
+
+    gallery.run(&["--full-exif", "--strip-gps", "false"]);
+    assert!(gallery.page("index.html").contains("GPSLatitude"));
+}
+
+#[test]
+fn map_places_the_geotagged_photos() {
+    let gallery = Gallery::new(&[A, G]);
+    gallery.run(&["--map"]);
+    let map = gallery.page("map.html");
+    assert_eq!(map.matches("\"lat\": ").count(), 1);
+    assert!(map.contains("\"lat\": 52.520000, \"lon\": -13.410000, \"href\": \"img/g.jpg\""));
+    assert!(map.contains("leaflet.js"));
+    let (_, id) = map.split_once("\"page\": \"page_0.html#").unwrap();
+    let (id, day) = id.split_once("\", \"day\": ").unwrap();
+    assert!(day.starts_with("\"2023-08-17\""));
+    assert!(gallery
+        .page("page_0.html")
+        .contains(&format!("<figure id=\"{id}\"")));
+    assert!(gallery.page("index.html").contains("<a href=\"map.html\""));
+}
+
+#[test]
+fn large_days_can_be_split_across_pages() {
+    let gallery = Gallery::new(&[B]);
+    for i in 0..120 {
+        gallery.add(&format!("{i:03}.jpg"), &fixture(A));
+    }
+    gallery.run(&["--split-large-days"]);
+    let index = gallery.page("index.html");
+    assert_eq!(thumbnails(&index).len(), 21);
+    for (page, len) in [("page_1.html", 50), ("page_0.html", 50)] {
+        let page = gallery.page(page);
+        assert_eq!(thumbnails(&page).len(), len);
+        assert!(page.contains(">2023-08-15 (continued)</time></h2>"));
+    }
+    assert!(index.contains(">2023-08-15 (2)</a>"));
+    assert!(index.contains(">2023-08-15 (3)</a>"));
+}
+
+#[test]
+fn metadata_can_be_read_with_exiftool() {
+    let gallery = Gallery::new(&[A, B]);
+    // Answers for A only, like for a file whose tags it doesn't know.
+    let bin = gallery.dir.path().join("bin");
+    fs::create_dir(&bin).unwrap();
+    let exiftool = bin.join("exiftool");
+    fs::write(
+        &exiftool,
+        r#"#!/bin/sh
+for file; do :; done
+case "$file" in
+*a.jpg) echo '[{"SourceFile": "a.jpg", "DateTimeOriginal": "2022:01:02 03:04:05", "OffsetTimeOriginal": "+01:00"}]' ;;
+*) echo '[{"SourceFile": "other"}]' ;;
+esac
+"#,
+    )
+    .unwrap();
+    fs::set_permissions(&exiftool, fs::Permissions::from_mode(0o755)).unwrap();
+    let path =
+        env::join_paths(iter::once(bin).chain(env::split_paths(&env::var_os("PATH").unwrap())))
+            .unwrap();
+    let output = gallery
+        .command(&["--metadata-backend", "exiftool"])
+        .env("PATH", path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        headings(&gallery.page("index.html")),
+        [
+            "<time datetime=\"2023-08-16\">2023-08-16</time>",
+            "<time datetime=\"2022-01-02\">2022-01-02</time>"
+        ]
+    );
+}
+
+#[test]
+fn first_thumbnails_can_be_preloaded() {
+    let gallery = Gallery::new(&[A, B, C]);
+    gallery.run(&["--preload-thumbnails", "2"]);
+    let index = gallery.page("index.html");
+    let head = &index[..index.find("</head>").unwrap()];
+    assert_eq!(
+        head.matches("<link rel=\"preload\" as=\"image\"").count(),
+        2
+    );
+    assert!(head.contains(&format!("href=\"./thumbnail/{C}\"")));
+    assert_eq!(index.matches("loading=\"eager\"").count(), 2);
+    assert_eq!(index.matches("loading=\"lazy\"").count(), 1);
+}
+
+#[test]
+fn empty_input_gives_an_empty_gallery() {
+    let gallery = Gallery::new(&[]);
+    gallery.run(&["--noop-on-empty"]);
+    assert!(!gallery.output().join("index.html").exists());
+
+    gallery.run(&[]);
+    let index = gallery.page("index.html");
+    assert!(thumbnails(&index).is_empty());
+    assert!(index.contains("</html>"));
+}
+
+#[test]
+fn images_are_regenerated_by_another_encoder() {
+    let gallery = Gallery::new(&[A]);
+    gallery.run(&["--format", "webp"]);
+    let manifest = gallery.output().join("encoders.json");
+    let encoders = fs::read_to_string(&manifest).unwrap();
+    assert!(encoders.contains("\"thumbnail/a.webp\": "));
+    assert!(encoders.contains("\"img/a.webp\": "));
+
+    let old = encoders.replace(", Webp,", ", Webp 0.1,");
+    fs::write(&manifest, &old).unwrap();
+    gallery.run(&["--format", "webp"]);
+    assert_eq!(fs::read_to_string(&manifest).unwrap(), encoders);
+}
+
+#[test]
+fn unchanged_photos_are_not_hashed_again() {
+    let gallery = Gallery::new(&[A]);
+    gallery.run(&["--permalinks"]);
+    let manifest = gallery.output().join("build.json");
+    let builds = fs::read_to_string(&manifest).unwrap();
+    let hash = gallery
+        .page("index.html")
+        .split("<figure id=\"")
+        .nth(1)
+        .unwrap()[..16]
+        .to_owned();
+    assert!(builds.contains(&format!("\"hash\": \"{hash}\"")));
+    for output in [
+        "img/a.jpg",
+        "thumbnail/a.jpg",
+        &format!("photo/{hash}.html"),
+    ] {
+        assert!(builds.contains(&format!("\"{output}\"")), "{output}");
+    }
+
+    let recorded = builds.replace(&format!("\"hash\": \"{hash}\""), "\"hash\": \"recorded\"");
+    fs::write(&manifest, recorded).unwrap();
+    gallery.run(&[]);
+    assert!(gallery
+        .page("index.html")
+        .contains("<figure id=\"recorded\""));
+    // Left out without --permalinks.
+    assert!(!gallery.output().join(format!("photo/{hash}.html")).exists());
+
+    fs::write(gallery.input().join(A), fixture(A)).unwrap();
+    gallery.run(&[]);
+    assert!(gallery
+        .page("index.html")
+        .contains(&format!("<figure id=\"{hash}\"")));
+}
+
+#[test]
+fn undated_photos_can_be_grouped_apart() {
+    let gallery = Gallery::new(&[A, B]);
+    let mut undated = fixture(A);
+    let tag = undated
+        .windows(4)
+        .position(|w| w == b"\x03\x90\x02\x00")
+        .unwrap();
+    // DateTimeOriginal becomes an unknown tag.
+    undated[tag] = 0x05;
+    gallery.add("undated.jpg", &undated);
+    gallery.run(&["--group-empty-as", "unknown"]);
+    let index = gallery.page("index.html");
+    assert_eq!(
+        headings(&index),
+        [
+            "<time datetime=\"2023-08-16\">2023-08-16</time>",
+            "<time datetime=\"2023-08-15\">2023-08-15</time>",
+            "Undated"
+        ]
+    );
+    assert_eq!(thumbnails(&index).last(), Some(&"undated.jpg"));
+
+    gallery.run(&["--group-empty-as", "unknown", "--undated-position", "top"]);
+    assert_eq!(headings(&gallery.page("index.html"))[0], "Undated");
+}
+
+#[test]
+fn opds_catalog_lists_the_days() {
+    let gallery = Gallery::new(&[A, B, C]);
+    gallery.run(&["--opds", "--base-url", "https://photos.example.com"]);
+    let opds = gallery.page("opds.xml");
+    assert_eq!(opds.matches("<entry>").count(), 2);
+    assert_eq!(
+        opds.matches("rel=\"http://opds-spec.org/acquisition\"")
+            .count(),
+        3
+    );
+    assert!(opds.contains("href=\"https://photos.example.com/img/a.jpg\" type=\"image/jpeg\""));
+    assert!(gallery.page("index.html").contains("href=\"./opds.xml\""));
+}
+
+#[test]
+fn opds_covers_can_be_chosen() {
+    // Taken on the day of B and C, before both.
+    let gallery = Gallery::new(&[B, C]);
+    gallery.add("x.jpg", &taken_on("2023:08:16"));
+    gallery.add(
+        "b.jpg.xmp",
+        br#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF><rdf:Description>
+<dc:subject><rdf:Bag><rdf:li>cover</rdf:li></rdf:Bag></dc:subject>
+</rdf:Description></rdf:RDF></x:xmpmeta>"#,
+    );
+    gallery.add(
+        "x.jpg.xmp",
+        br#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF><rdf:Description xmp:Rating="5"/></rdf:RDF></x:xmpmeta>"#,
+    );
+    for (cover, expected) in [
+        ("pinned", B),
+        ("newest", C),
+        ("oldest", "x.jpg"),
+        ("top-rated", "x.jpg"),
+    ] {
+        gallery.run(&["--opds", "--album-cover", cover]);
+        let opds = gallery.page("opds.xml");
+        let link = format!(
+            "<link rel=\"http://opds-spec.org/image/thumbnail\" href=\"thumbnail/{expected}\""
+        );
+        assert!(opds.contains(&link), "{cover}: {opds}");
+    }
+}
+
+#[test]
+fn gallery_json_describes_the_photos() {
+    let gallery = Gallery::new(&[A, B, H]);
+    gallery.add("b.jpg.xmp", &keywords_xmp(&["Food"]));
+    gallery.run(&["--json", "--per-page", "1", "--thumbnail-size", "100"]);
+    let json = gallery.page("gallery.json");
+    assert!(
+        json.contains("\"pages\": [\"page_2.html\", \"page_1.html\", \"page_0.html\"]"),
+        "{json}"
+    );
+    let b = &json[json.find("\"label\": \"b.jpg\"").unwrap()..];
+    let b = &b[..b.find('}').unwrap()];
+    assert!(b.contains("\"page\": \"page_2.html\""), "{b}");
+    assert!(b.contains("\"datetime\": \"2023-08-16T10:00:00+02:00\""));
+    assert!(json.contains("\"keywords\": [\"Food\"]"));
+    assert!(json.contains("\"width\": 8,\n      \"height\": 16"));
+    assert!(json.contains(
+        "\"thumbnail\": {\"path\": \"thumbnail/h.jpg\", \"width\": 100, \"height\": 200}"
+    ));
+    assert!(gallery.output().join("index.html").exists());
+
+    let gallery = Gallery::new(&[G]);
+    gallery.run(&["--json-only"]);
+    assert!(gallery.page("gallery.json").contains("\"location\": null"));
+    assert!(gallery.output().join("gallery.json").exists());
+    assert!(!gallery.output().join("index.html").exists());
+}
+
+#[test]
+fn feed_lists_the_newest_photos() {
+    let gallery = Gallery::new(&[A, B, C, H]);
+    gallery.run(&[
+        "--feed",
+        "2",
+        "--base-url",
+        "https://photos.example.com",
+        "--thumbnail-size",
+        "100",
+    ]);
+    let feed = gallery.page("atom.xml");
+    assert_eq!(feed.matches("<entry>").count(), 2);
+    let c = feed.find("<title>c.jpg</title>").unwrap();
+    let b = feed.find("<title>b.jpg</title>").unwrap();
+    assert!(c < b, "{feed}");
+    assert!(!feed.contains("a.jpg"));
+    assert!(feed.contains(
+        "<link rel=\"alternate\" type=\"text/html\" href=\"https://photos.example.com/page_0.html#"
+    ));
+    assert!(feed.contains(
+        "<media:thumbnail url=\"https://photos.example.com/thumbnail/c.jpg\" width=\"100\" height=\"100\"/>"
+    ));
+    assert!(feed.contains("medium=\"image\" width=\"8\" height=\"8\"/>"));
+    assert!(gallery
+        .page("index.html")
+        .contains("type=\"application/atom+xml\" href=\"./atom.xml\""));
+
+    // Shown rotated.
+    gallery.run(&["--feed", "10", "--thumbnail-size", "100"]);
+    assert!(gallery
+        .page("atom.xml")
+        .contains("width=\"100\" height=\"200\"/>"));
+}
+
+#[test]
+fn large_images_are_reencoded_to_fit() {
+    let gallery = Gallery::new(&[A]);
+    // Writes 100 bytes per point of quality.
+    let bin = gallery.dir.path().join("bin");
+    fs::create_dir(&bin).unwrap();
+    let magick = bin.join("magick");
+    fs::write(
+        &magick,
+        r#"#!/bin/sh
+[ "$1" = -version ] && exit
+quality=92
+while [ $# -gt 1 ]; do
+    [ "$1" = -quality ] && quality=${2%\%}
+    shift
+done
+head -c $((quality * 100)) /dev/zero > "$1"
+"#,
+    )
+    .unwrap();
+    fs::set_permissions(&magick, fs::Permissions::from_mode(0o755)).unwrap();
+    let path =
+        env::join_paths(iter::once(bin).chain(env::split_paths(&env::var_os("PATH").unwrap())))
+            .unwrap();
+    let run = |max_bytes: &str| {
+        // Not `gallery.command`, which may choose the none backend.
+        let output = Command::new(env!("CARGO_BIN_EXE_photo2html"))
+            .args(["--image-backend", "magick", "--regenerate", "*"])
+            .arg("--max-bytes")
+            .arg(max_bytes)
+            .arg("--output-dir")
+            .arg(gallery.output())
+            .arg(gallery.input())
+            .env("PATH", &path)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let size = fs::metadata(gallery.output().join("img").join(A))
+            .unwrap()
+            .len();
+        (size, String::from_utf8(output.stderr).unwrap())
+    };
+    assert_eq!(run("5050").0, 5000);
+    let (size, log) = run("500");
+    assert_eq!(size, 1000);
+    assert!(log.contains("even at quality 10"), "{log}");
+}
+
+#[test]
+fn odd_capture_times_are_read() {
+    for datetime in [
+        "2023:08:15 14:22",
+        "2023-08-15 14:22:10",
+        "2023.08.15 14:22:10",
+        "2023/08/15 14:22:10",
+        "2023:08:15",
+    ] {
+        let mut bytes = fixture(A);
+        let at = bytes
+            .windows(19)
+            .position(|w| w == b"2023:08:15 14:22:10")
+            .unwrap();
+        // Padded, as the length of the tag is fixed.
+        let mut field = datetime.as_bytes().to_vec();
+        field.resize(19, 0);
+        bytes[at..at + 19].copy_from_slice(&field);
+        let gallery = Gallery::new(&[]);
+        gallery.add(A, &bytes);
+        gallery.run(&[]);
+        assert_eq!(
+            headings(&gallery.page("index.html")),
+            ["<time datetime=\"2023-08-15\">2023-08-15</time>"],
+            "{datetime}"
+        );
+    }
+}
+
+#[test]
+fn capture_times_may_lack_an_offset() {
+    let gallery = Gallery::new(&[]);
+    let mut bytes = fixture(A);
+    let tag = bytes
+        .windows(4)
+        .position(|w| w == b"\x11\x90\x02\x00")
+        .unwrap();
+    // OffsetTimeOriginal becomes an unknown tag.
+    bytes[tag] = 0x05;
+    gallery.add(A, &bytes);
+    gallery.run(&[]);
+    assert_eq!(
+        headings(&gallery.page("index.html")),
+        ["<time datetime=\"2023-08-15\">2023-08-15</time>"]
+    );
+
+    gallery.run(&["--timezone", "+09:00", "--verbose-html"]);
+    assert!(gallery
+        .page("index.html")
+        .contains("<!-- src: a.jpg 2023-08-15T14:22:10+09:00 -->"));
+}
+
+#[test]
+fn capture_times_may_be_stored_as_undefined() {
+    let gallery = Gallery::new(&[]);
+    let mut bytes = fixture(A);
+    // DateTimeOriginal and OffsetTimeOriginal, with their type changed from
+    // Ascii to Undefined.
+    for entry in [b"\x03\x90\x02\x00", b"\x11\x90\x02\x00"] {
+        let tag = bytes.windows(4).position(|w| w == entry).unwrap();
+        bytes[tag + 2] = 0x07;
+    }
+    gallery.add(A, &bytes);
+    gallery.run(&["--verbose-html"]);
+    let index = gallery.page("index.html");
+    assert_eq!(
+        headings(&index),
+        ["<time datetime=\"2023-08-15\">2023-08-15</time>"]
+    );
+    assert!(
+        index.contains("<!-- src: a.jpg 2023-08-15T14:22:10+09:00 -->"),
+        "{index}"
+    );
+}
+
+#[test]
+fn photos_without_capture_times_are_dated_by_modification() {
+    let gallery = Gallery::new(&[B]);
+    let mut bytes = fixture(A);
+    let tag = bytes
+        .windows(4)
+        .position(|w| w == b"\x03\x90\x02\x00")
+        .unwrap();
+    bytes[tag] = 0x05;
+    gallery.add(A, &bytes);
+    // Noon UTC, which is the same day in most time zones.
+    File::options()
+        .write(true)
+        .open(gallery.input().join(A))
+        .unwrap()
+        .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(1_592_222_400))
+        .unwrap();
+    let output = gallery.run(&[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("a.jpg has no capture time"), "{stderr}");
+    assert_eq!(
+        headings(&gallery.page("index.html")),
+        [
+            "<time datetime=\"2023-08-16\">2023-08-16</time>",
+            "<time datetime=\"2020-06-15\">2020-06-15</time>"
+        ]
+    );
+}
+
+#[test]
+fn jobs_do_not_change_the_output() {
+    // Each into its own output, so that nothing is left from the other.
+    let outputs = ["1", "4"].map(|jobs| {
+        let gallery = Gallery::new(&[A, B, C, D, E]);
+        gallery.run(&["--jobs", jobs, "--hashed-assets"]);
+        ["index.html", "assets.json"].map(|name| gallery.page(name))
+    });
+    assert_eq!(outputs[0], outputs[1]);
+}
+
+#[test]
+fn subdirectories_are_read_recursively() {
+    let gallery = Gallery::new(&[A]);
+    for dir in ["2023/summer", "2024"] {
+        fs::create_dir_all(gallery.input().join(dir)).unwrap();
+        fs::write(gallery.input().join(dir).join(B), fixture(B)).unwrap();
+    }
+    std::os::unix::fs::symlink("..", gallery.input().join("2024/loop")).unwrap();
+    gallery.run(&[]);
+    assert_eq!(thumbnails(&gallery.page("index.html")), [A]);
+
+    gallery.run(&["--recursive"]);
+    let index = gallery.page("index.html");
+    let mut names = thumbnails(&index);
+    names.sort();
+    assert_eq!(names, [A, "b-2024.jpg", "b-summer.jpg"]);
+
+    // The output directory is not an input, even inside one.
+    let output = gallery.input().join("site");
+    for _ in 0..2 {
+        let run = Command::new(env!("CARGO_BIN_EXE_photo2html"))
+            .args(["--image-backend", "none", "--log-format", "concise"])
+            .args(["--recursive", "--output-dir"])
+            .arg(&output)
+            .arg(gallery.input())
+            .output()
+            .unwrap();
+        assert!(run.status.success());
+        let stderr = String::from_utf8_lossy(&run.stderr);
+        assert!(!stderr.contains("site/"), "{stderr}");
+    }
+    let index = fs::read_to_string(output.join("index.html")).unwrap();
+    assert_eq!(thumbnails(&index).len(), 3);
+}
+
+#[test]
+fn images_are_turned_by_their_orientation() {
+    if !has_magick() {
+        return;
+    }
+    let gallery = Gallery::new(&[H]);
+    gallery.run(&[]);
+    for dir in ["img", "thumbnail"] {
+        let output = Command::new("magick")
+            .args(["identify", "-format", "%w %h"])
+            .arg(gallery.output().join(dir).join(H))
+            .output()
+            .unwrap();
+        let output = String::from_utf8(output.stdout).unwrap();
+        let (width, height) = output.split_once(' ').unwrap();
+        let (width, height): (u32, u32) = (width.parse().unwrap(), height.parse().unwrap());
+        assert!(height > width, "{dir}: {width}x{height}");
+    }
+}
+
+#[test]
+fn thumbnails_are_regenerated_in_another_size() {
+    let gallery = Gallery::new(&[A]);
+    gallery.run(&[]);
+    // Up to date by its modification time, but recognizable.
+    let thumbnail = gallery.output().join("thumbnail").join(A);
+    fs::write(&thumbnail, "stale").unwrap();
+    let modified = fs::metadata(gallery.input().join(A))
+        .unwrap()
+        .modified()
+        .unwrap();
+    File::options()
+        .write(true)
+        .open(&thumbnail)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    gallery.run(&[]);
+    assert_eq!(fs::read(&thumbnail).unwrap(), b"stale");
+
+    gallery.run(&["--thumbnail-size", "256"]);
+    assert_ne!(fs::read(&thumbnail).unwrap(), b"stale");
+    assert!(gallery.page("encoders.json").contains("256px Contain"));
+}
+
+#[test]
+fn outputs_of_removed_photos_are_deleted() {
+    let gallery = Gallery::new(&[]);
+    for day in 1..=30 {
+        for photo in 0..2 {
+            gallery.add(
+                &format!("{day}-{photo}.jpg"),
+                &taken_on(&format!("2023:05:{day:02}")),
+            );
+        }
+    }
+    gallery.run(&[]);
+    assert!(gallery.output().join("page_1.html").exists());
+
+    for day in 1..=30 {
+        fs::remove_file(gallery.input().join(format!("{day}-1.jpg"))).unwrap();
+    }
+    gallery.run(&[]);
+    assert!(!gallery.output().join("page_1.html").exists());
+    for dir in ["thumbnail", "thumbnail/256", "img"] {
+        let names: Vec<_> = fs::read_dir(gallery.output().join(dir))
+            .unwrap()
+            .map(|e| e.unwrap())
+            .filter(|e| e.file_type().unwrap().is_file())
+            .map(|e| e.file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(names.len(), 30);
+        assert!(names.iter().all(|name| name.ends_with("-0.jpg")));
+    }
+}
+
+#[test]
+fn check_lists_what_would_be_removed() {
+    let gallery = Gallery::new(&[A, B, C]);
+    gallery.run(&["--dense-days", "1"]);
+    let strip = gallery.output().join("strip").join(B);
+    assert!(strip.is_file());
+
+    fs::remove_file(gallery.input().join(B)).unwrap();
+    let output = gallery.run(&["--check"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    for path in [gallery.output().join("img").join(B), strip.clone()] {
+        assert!(
+            stderr.contains(&format!("would remove {}", path.display())),
+            "{stderr}"
+        );
+        assert!(path.is_file());
+    }
+    assert!(!stderr.contains(&format!(
+        "would remove {}",
+        gallery.output().join("img").join(A).display()
+    )));
+
+    gallery.run(&[]);
+    assert!(!strip.exists());
+    assert!(!gallery.output().join("img").join(B).exists());
+}
+
+#[test]
+fn thumbnails_are_offered_in_several_widths() {
+    let gallery = Gallery::new(&[A]);
+    gallery.run(&["--thumbnail-widths", "256,512,1024"]);
+    for dir in ["thumbnail/256", "thumbnail/1024"] {
+        assert!(gallery.output().join(dir).join(A).is_file());
+    }
+    let index = gallery.page("index.html");
+    assert!(index.contains(&format!(
+        " srcset=\"./thumbnail/256/{A} 256w, ./thumbnail/{A} 512w, ./thumbnail/1024/{A} 1024w\""
+    )));
+    assert!(index.contains(" sizes=\"(max-width: 600px) 100vw, "));
+
+    gallery.run(&["--thumbnail-widths", "512"]);
+    assert!(!gallery.page("index.html").contains(" srcset="));
+    assert!(!gallery.output().join("thumbnail/256").join(A).exists());
+}
+
+#[test]
+fn thumbnails_have_the_dimensions_they_are_shown_in() {
+    let gallery = Gallery::new(&[H]);
+    gallery.run(&[]);
+    // Turned to portrait by its orientation.
+    assert!(gallery
+        .page("index.html")
+        .contains(" width=\"512\" height=\"1024\""));
+}
+
+#[test]
+fn details_are_reported_with_verbose() {
+    let gallery = Gallery::new(&[A]);
+    let output = gallery.command(&[]).output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(&format!("processed {}", gallery.input().join(A).display())));
+    assert!(!stderr.contains("original_path"), "{stderr}");
+
+    let output = gallery.command(&["-vv"]).output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stderr).contains("original_path"));
+}
+
+#[test]
+fn only_problems_are_reported_with_quiet() {
+    let gallery = Gallery::new(&[A]);
+    gallery.add("broken.jpg", b"not a photo");
+    let output = gallery.command(&["--quiet"]).output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("processed"), "{stderr}");
+    assert!(!stderr.contains("info:"), "{stderr}");
+    assert!(stderr.contains("warning: "), "{stderr}");
+    assert!(gallery.output().join("index.html").exists());
+}
+
+#[test]
+fn magick_failures_name_the_file() {
+    let gallery = Gallery::new(&[A]);
+    let run = |magick: &Path| {
+        // Not `gallery.command`, which may choose the none backend.
+        Command::new(env!("CARGO_BIN_EXE_photo2html"))
+            .args(["--image-backend", "magick", "--log-format", "concise"])
+            .arg("--magick")
+            .arg(magick)
+            .arg("--output-dir")
+            .arg(gallery.output())
+            .arg(gallery.input())
+            .output()
+            .unwrap()
+    };
+    let output = run(&gallery.dir.path().join("missing"));
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--magick"), "{stderr}");
+    assert!(!gallery.output().join("index.html").exists());
+
+    let magick = gallery.dir.path().join("magick");
+    fs::write(
+        &magick,
+        "#!/bin/sh\n[ \"$1\" = -version ] && exit\necho 'no decode delegate' >&2\nexit 1\n",
+    )
+    .unwrap();
+    fs::set_permissions(&magick, fs::Permissions::from_mode(0o755)).unwrap();
+    let output = run(&magick);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(&format!(
+            "failed on {}: no decode delegate",
+            gallery.input().join(A).display()
+        )),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn settings_can_be_shown_over_thumbnails() {
+    let gallery = Gallery::new(&[A, D]);
+    gallery.run(&["--hover-settings"]);
+    let index = gallery.page("index.html");
+    // Without the fields the photo lacks, and none for A, which has none.
+    assert!(index
+        .contains("<figcaption><span class=\"settings\">Alpha 7 · 50mm F1.8</span></figcaption>"));
+    assert_eq!(index.matches("class=\"settings\"").count(), 1);
+
+    gallery.run(&[]);
+    assert!(!gallery.page("index.html").contains("class=\"settings\""));
+}
+
+#[test]
+fn lightbox_opens_the_full_images() {
+    let gallery = Gallery::new(&[A, B]);
+    gallery.run(&["--permalinks"]);
+    let index = gallery.page("index.html");
+    // The links still lead to the permalinks without scripts.
+    assert!(index.contains(&format!("data-full=\"./img/{A}\"><img")));
+    assert_eq!(index.matches(" data-full=").count(), 2);
+    assert!(index.contains("<script>\n(() => {"));
+    assert!(index.contains(".lightbox {"));
+    assert!(index.contains(".lightbox .next {"));
+
+    gallery.run(&["--no-lightbox"]);
+    let index = gallery.page("index.html");
+    assert!(!index.contains("<script>"));
+    assert!(!index.contains(" data-full="));
+}
+
+#[test]
+fn lightbox_arrows_are_on_by_default() {
+    let gallery = Gallery::new(&[A, B, C]);
+    gallery.run(&[]);
+    let index = gallery.page("index.html");
+    assert_eq!(index.matches(" data-full=").count(), 3);
+    for arrow in ["arrow(\"previous\", ", "arrow(\"next\", "] {
+        assert!(index.contains(arrow), "{arrow}");
+    }
+    assert!(index.contains("\"ArrowLeft\""));
+    assert!(index.contains("\"ArrowRight\""));
+    assert!(index.contains(".lightbox .previous"));
+
+    gallery.run(&["--no-lightbox"]);
+    let index = gallery.page("index.html");
+    assert!(!index.contains("<script>"));
+    assert!(!index.contains("lightbox"));
+    assert!(!index.contains(" data-full="));
+}
+
+#[test]
+fn pages_hold_whole_days_of_about_per_page_photos() {
+    let gallery = Gallery::new(&[]);
+    for (day, count) in [(1, 1), (2, 2), (3, 7), (4, 1), (5, 1)] {
+        for photo in 0..count {
+            gallery.add(
+                &format!("{day}-{photo}.jpg"),
+                &taken_on(&format!("2023:05:{day:02}")),
+            );
+        }
+    }
+    gallery.run(&["--per-page", "4"]);
+    // The day larger than a page gets its own.
+    for (page, len) in [("page_0.html", 3), ("page_1.html", 7), ("page_2.html", 2)] {
+        assert_eq!(thumbnails(&gallery.page(page)).len(), len, "{page}");
+    }
+    assert_eq!(headings(&gallery.page("page_1.html")).len(), 1);
+    assert!(!gallery.output().join("page_3.html").exists());
+}
+
+#[test]
+fn subdirectories_become_albums() {
+    let gallery = Gallery::new(&[A]);
+    for (dir, name) in [("trip", B), ("trip/day 2", C), ("trip/day 2/deeper", D)] {
+        fs::create_dir_all(gallery.input().join(dir)).unwrap();
+        fs::write(gallery.input().join(dir).join(name), fixture(name)).unwrap();
+    }
+    gallery.run(&["--albums"]);
+    let albums = gallery.page("albums.html");
+    assert!(albums.contains("<a href=\"album_trip.html\"><img src=\"./thumbnail/b.jpg\""));
+    assert!(albums.contains("<span>trip/day 2</span></a> 1 photo</li>"));
+    assert_eq!(thumbnails(&gallery.page("album_trip-day-2.html")), [C]);
+    assert!(gallery.page("album_trip.html").contains("<h1>trip</h1>"));
+    assert!(gallery.page("index.html").contains("href=\"albums.html\""));
+    assert_eq!(thumbnails(&gallery.page("index.html")).len(), 4);
+
+    gallery.run(&["--albums", "--depth", "1"]);
+    assert_eq!(thumbnails(&gallery.page("index.html")), [B, A]);
+    assert!(gallery.output().join("album_trip.html").exists());
+    assert!(!gallery.output().join("album_trip-day-2.html").exists());
+}
+
+#[test]
+fn album_covers_can_be_chosen() {
+    let gallery = Gallery::new(&[A]);
+    let trip = gallery.input().join("trip");
+    fs::create_dir(&trip).unwrap();
+    for name in [B, C, D, E] {
+        fs::write(trip.join(name), fixture(name)).unwrap();
+    }
+    fs::write(
+        trip.join("e.jpg.xmp"),
+        r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF><rdf:Description>
+<dc:subject><rdf:Bag><rdf:li>cover</rdf:li></rdf:Bag></dc:subject>
+</rdf:Description></rdf:RDF></x:xmpmeta>"#,
+    )
+    .unwrap();
+    fs::write(
+        trip.join("b.jpg.xmp"),
+        r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF><rdf:Description xmp:Rating="5"/></rdf:RDF></x:xmpmeta>"#,
+    )
+    .unwrap();
+    for (cover, expected) in [
+        ("pinned", E),
+        ("newest", C),
+        ("oldest", D),
+        ("top-rated", B),
+    ] {
+        gallery.run(&["--albums", "--album-cover", cover]);
+        let albums = gallery.page("albums.html");
+        let img = format!("<a href=\"album_trip.html\"><img src=\"./thumbnail/{expected}\"");
+        assert!(albums.contains(&img), "{cover}: {albums}");
+    }
+}
+
+#[test]
+fn images_are_made_natively() {
+    let gallery = Gallery::new(&[H]);
+    let run = |args: &[&str]| {
+        // Not `gallery.command`, which may choose the none backend.
+        Command::new(env!("CARGO_BIN_EXE_photo2html"))
+            .args(["--image-backend", "native", "--log-format", "concise"])
+            .args(["--thumbnail-size", "4", "--thumbnail-widths", "4"])
+            .args(args)
+            .arg("--output-dir")
+            .arg(gallery.output())
+            .arg(gallery.input())
+            .output()
+            .unwrap()
+    };
+    assert!(run(&[]).status.success());
+    // Turned upright by its orientation.
+    let dimensions =
+        |dir: &str| image::image_dimensions(gallery.output().join(dir).join(H)).unwrap();
+    assert_eq!(dimensions("img"), (8, 16));
+    assert_eq!(dimensions("thumbnail"), (4, 8));
+
+    // Without ImageMagick to fall back to, what can't be made fails the run.
+    let missing = gallery.dir.path().join("magick");
+    let missing = missing.to_str().unwrap();
+    let svg = gallery.dir.path().join("logo.svg");
+    fs::write(&svg, "<svg xmlns=\"http://www.w3.org/2000/svg\"/>").unwrap();
+    let icon = ["--pwa", "--icon", svg.to_str().unwrap()];
+    for args in [&["--format", "webp"][..], &icon, &["--contact-sheet"]] {
+        let output = run(&[&["--magick", missing], args].concat());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success(), "{args:?}: {stderr}");
+        assert!(stderr.contains("cannot be run, see --magick"), "{stderr}");
+    }
+    assert!(!run(&["--magick", missing, "--magick-fallback"])
+        .status
+        .success());
+}
+
+#[test]
+fn pwa_icons_are_made_natively() {
+    let gallery = Gallery::new(&[A]);
+    let missing = gallery.dir.path().join("magick");
+    let output = Command::new(env!("CARGO_BIN_EXE_photo2html"))
+        .args(["--image-backend", "native", "--pwa", "--magick"])
+        .arg(&missing)
+        .arg("--output-dir")
+        .arg(gallery.output())
+        .arg(gallery.input())
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    for (name, size) in [
+        ("apple-touch-icon.png", 180),
+        ("icon-192.png", 192),
+        ("icon-512.png", 512),
+        ("favicon.ico", 32),
+    ] {
+        let dimensions = image::image_dimensions(gallery.output().join(name)).unwrap();
+        assert_eq!(dimensions, (size, size), "{name}");
+    }
+    assert!(gallery.page("icon.svg").starts_with("<svg"));
+}
+
+#[test]
+fn explicit_options_take_precedence_over_the_preset() {
+    let gallery = Gallery::new(&[A]);
+    gallery.run(&["--preset", "web", "--format", "jpeg"]);
+    assert!(gallery.page("encoders.json").contains("at most 2048px"));
+    gallery.run(&["--preset", "web", "--format", "jpeg", "--no-max-dimension"]);
+    assert!(!gallery.page("encoders.json").contains("at most"));
+
+    for (args, kept) in [
+        (&[][..], true),
+        (&["--no-keep-exif"], false),
+        (&["--no-keep-exif", "--keep-exif"], true),
+    ] {
+        let gallery = Gallery::new(&[A]);
+        let output = Command::new(env!("CARGO_BIN_EXE_photo2html"))
+            .args(["--image-backend", "native", "--preset", "print"])
+            .args(args)
+            .arg("--output-dir")
+            .arg(gallery.output())
+            .arg(gallery.input())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{args:?}");
+        let image = fs::read(gallery.output().join("img").join(A)).unwrap();
+        let has_exif = image.windows(6).any(|w| w == b"Exif\0\0");
+        assert_eq!(has_exif, kept, "{args:?}");
+    }
+}
+
+/// Returns a 16x8 JPEG of four shades, one in each quarter, to tell how it was
+/// turned, taken to be of `orientation`.
+fn oriented_jpeg(orientation: u8) -> Vec<u8> {
+    let image = image::GrayImage::from_fn(16, 8, |x, y| {
+        image::Luma([if x < 8 { 0 } else { 160 } + if y < 4 { 0 } else { 80 }])
+    });
+    let mut jpeg = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, 100)
+        .encode_image(&image)
+        .unwrap();
+    // An IFD of only the orientation, in an APP1 segment after the SOI.
+    let mut exif = b"Exif\0\0II*\0\x08\0\0\0\x01\0\x12\x01\x03\0\x01\0\0\0".to_vec();
+    exif.extend([orientation, 0, 0, 0, 0, 0, 0, 0]);
+    let len = u16::try_from(exif.len() + 2).unwrap();
+    let segment = [&[0xff, 0xe1][..], &len.to_be_bytes(), &exif].concat();
+    jpeg.splice(2..2, segment);
+    jpeg
+}
+
+#[test]
+fn every_orientation_is_applied_natively() {
+    for orientation in 1..=8 {
+        let gallery = Gallery::new(&[]);
+        gallery.add(H, &oriented_jpeg(orientation));
+        let output = Command::new(env!("CARGO_BIN_EXE_photo2html"))
+            .args(["--image-backend", "native", "--log-format", "concise"])
+            .args(["--thumbnail-size", "4", "--thumbnail-widths", "4"])
+            .arg("--output-dir")
+            .arg(gallery.output())
+            .arg(gallery.input())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{orientation}");
+        let dimensions =
+            |dir: &str| image::image_dimensions(gallery.output().join(dir).join(H)).unwrap();
+        // Turned by a quarter for 5 to 8.
+        let (img, thumbnail) = if orientation >= 5 {
+            ((8, 16), (4, 8))
+        } else {
+            ((16, 8), (4, 2))
+        };
+        assert_eq!(dimensions("img"), img, "{orientation}");
+        assert_eq!(dimensions("thumbnail"), thumbnail, "{orientation}");
+
+        // And flipped for 2, 4, 5 and 7, like no other orientation would be.
+        let turned = image::open(gallery.output().join("img").join(H))
+            .unwrap()
+            .to_luma8();
+        let distance = |other: u8| {
+            let mut expected = image::load_from_memory(&oriented_jpeg(1)).unwrap();
+            expected.apply_orientation(image::metadata::Orientation::from_exif(other).unwrap());
+            let expected = expected.to_luma8();
+            (expected.dimensions() == turned.dimensions()).then(|| {
+                expected
+                    .pixels()
+                    .zip(turned.pixels())
+                    .map(|(a, b)| u32::from(a[0].abs_diff(b[0])))
+                    .sum::<u32>()
+            })
+        };
+        let closest = (1..=8).min_by_key(|&other| distance(other).unwrap_or(u32::MAX));
+        assert_eq!(closest, Some(orientation));
+    }
+}
+
+/// Returns a 32x32 black JPEG with a 16x16 white one embedded as its preview.
+fn jpeg_with_preview() -> Vec<u8> {
+    let encode = |size: u32, shade: u8| {
+        let mut jpeg = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, 100)
+            .encode_image(&image::GrayImage::from_pixel(
+                size,
+                size,
+                image::Luma([shade]),
+            ))
+            .unwrap();
+        jpeg
+    };
+    let mut jpeg = encode(32, 0);
+    let preview = encode(16, 255);
+    // An IFD of JPEGInterchangeFormat and its length, followed by the preview
+    // at 38, which is after the IFD.
+    let mut exif = b"Exif\0\0II*\0\x08\0\0\0\x02\0".to_vec();
+    exif.extend(b"\x01\x02\x04\0\x01\0\0\0\x26\0\0\0");
+    exif.extend(b"\x02\x02\x04\0\x01\0\0\0");
+    exif.extend(u32::try_from(preview.len()).unwrap().to_le_bytes());
+    exif.extend([0; 4]);
+    exif.extend(&preview);
+    let len = u16::try_from(exif.len() + 2).unwrap();
+    let segment = [&[0xff, 0xe1][..], &len.to_be_bytes(), &exif].concat();
+    jpeg.splice(2..2, segment);
+    jpeg
+}
+
+#[test]
+fn embedded_previews_can_be_preferred() {
+    let shade = |args: &[&str]| {
+        let gallery = Gallery::new(&[]);
+        gallery.add(A, &jpeg_with_preview());
+        let output = Command::new(env!("CARGO_BIN_EXE_photo2html"))
+            .args(["--image-backend", "native"])
+            .args(["--thumbnail-size", "8", "--thumbnail-widths", "8"])
+            .args(args)
+            .arg("--output-dir")
+            .arg(gallery.output())
+            .arg(gallery.input())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let thumbnail = image::open(gallery.output().join("thumbnail").join(A)).unwrap();
+        assert_eq!(thumbnail.width(), 8);
+        thumbnail.to_luma8().get_pixel(4, 4)[0]
+    };
+    assert!(shade(&[]) < 16);
+    assert!(shade(&["--prefer-embedded-preview"]) > 240);
+}
+
+#[test]
+fn resize_filters_are_applied_natively() {
+    // Each other pixel black, which only Point keeps apart when scaled down.
+    let checkerboard = image::GrayImage::from_fn(16, 16, |x, y| {
+        image::Luma([if (x + y) % 2 == 0 { 0 } else { 255 }])
+    });
+    let shade = |filter: &str| {
+        let gallery = Gallery::new(&[]);
+        checkerboard.save(gallery.input().join("c.png")).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_photo2html"))
+            .args(["--image-backend", "native", "--resize-filter", filter])
+            .args(["--thumbnail-size", "4", "--thumbnail-widths", "4"])
+            .arg("--output-dir")
+            .arg(gallery.output())
+            .arg(gallery.input())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{filter}");
+        let thumbnail = image::open(gallery.output().join("thumbnail/c.jpg")).unwrap();
+        thumbnail.to_luma8().get_pixel(1, 1)[0]
+    };
+    let point = shade("point");
+    assert!(!(32..224).contains(&point), "{point}");
+    for filter in ["lanczos", "triangle"] {
+        let smooth = shade(filter);
+        assert!((96..160).contains(&smooth), "{filter}: {smooth}");
+    }
+}
+
+#[test]
+fn transparent_thumbnails_are_put_onto_the_background() {
+    let transparent = image::RgbaImage::new(8, 8);
+    let color = |args: &[&str]| {
+        let gallery = Gallery::new(&[]);
+        transparent.save(gallery.input().join("t.png")).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_photo2html"))
+            .args(["--image-backend", "native"])
+            .args(["--thumbnail-size", "4", "--thumbnail-widths", "4"])
+            .args(args)
+            .arg("--output-dir")
+            .arg(gallery.output())
+            .arg(gallery.input())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{args:?}");
+        let thumbnail = image::open(gallery.output().join("thumbnail/t.jpg")).unwrap();
+        thumbnail.to_rgb8().get_pixel(2, 2).0
+    };
+    let close = |actual: [u8; 3], expected: [u8; 3]| {
+        actual.iter().zip(expected).all(|(a, e)| a.abs_diff(e) < 8)
+    };
+    let white = color(&[]);
+    assert!(close(white, [255; 3]), "{white:?}");
+    let red = color(&["--thumbnail-background", "#f00"]);
+    assert!(close(red, [255, 0, 0]), "{red:?}");
+}
+
+#[test]
+fn color_profiles_can_be_kept_natively() {
+    // Not a valid profile, but nothing reads more of it than the segment.
+    let profile = b"an ICC profile".repeat(8);
+    let mut jpeg = fixture(A);
+    let segment = [&b"ICC_PROFILE\0\x01\x01"[..], &profile].concat();
+    let len = u16::try_from(segment.len() + 2).unwrap();
+    jpeg.splice(
+        2..2,
+        [&[0xff, 0xe2][..], &len.to_be_bytes(), &segment].concat(),
+    );
+    let has_profile = |args: &[&str]| {
+        let gallery = Gallery::new(&[]);
+        gallery.add(A, &jpeg);
+        let output = Command::new(env!("CARGO_BIN_EXE_photo2html"))
+            .args(["--image-backend", "native"])
+            .args(["--thumbnail-size", "4", "--thumbnail-widths", "4"])
+            .args(args)
+            .arg("--output-dir")
+            .arg(gallery.output())
+            .arg(gallery.input())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        ["img", "thumbnail"].map(|dir| {
+            let image = fs::read(gallery.output().join(dir).join(A)).unwrap();
+            image.windows(profile.len()).any(|window| window == profile)
+        })
+    };
+    assert_eq!(has_profile(&[]), [false, false]);
+    assert_eq!(has_profile(&["--keep-icc"]), [true, true]);
+}
+
+#[test]
+fn pages_are_laid_out_by_templates() {
+    let gallery = Gallery::new(&[A]);
+    let templates = gallery.input().with_file_name("templates");
+    fs::create_dir(&templates).unwrap();
+    fs::write(
+        templates.join("page.html"),
+        "<title>{{ title }}</title><script src=\"{{root}}stats.js\"></script><main>{{main}}</main>{{nav}}",
+    )
+    .unwrap();
+    fs::write(
+        templates.join("figure.html"),
+        "<div class=\"photo\"><a href=\"{{href}}\">{{img}}</a></div>\n",
+    )
+    .unwrap();
+    gallery.run(&[
+        "--templates",
+        templates.to_str().unwrap(),
+        "--title",
+        "Trip",
+    ]);
+    let page = gallery.page("index.html");
+    assert!(page.starts_with("<title>Trip</title><script src=\"./stats.js\"></script><main>"));
+    assert!(
+        page.contains("<div class=\"photo\"><a href=\"img/a.jpg\"><img src=\"./thumbnail/a.jpg\"")
+    );
+    // The built-in one, as there is no nav.html.
+    assert!(page.contains("<nav>\n<ul class=\"nav\">\n"));
+
+    fs::write(templates.join("nav.html"), "{{pages}}").unwrap();
+    let output = gallery.try_run(&["--templates", templates.to_str().unwrap()]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("nav.html: unknown value {{pages}}"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn templates_are_found_in_the_input_directory() {
+    let gallery = Gallery::new(&[A]);
+    let templates = gallery.input().join("templates");
+    fs::create_dir(&templates).unwrap();
+    fs::write(
+        templates.join("figure.html"),
+        "<div class=\"photo\">{{img}}</div>\n",
+    )
+    .unwrap();
+    // Used by the templates, not a photo of the gallery.
+    fs::write(templates.join(B), fixture(B)).unwrap();
+    gallery.run(&["--recursive"]);
+    let page = gallery.page("index.html");
+    assert!(page.contains("<div class=\"photo\"><img src=\"./thumbnail/a.jpg\""));
+    assert_eq!(thumbnails(&page), [A]);
+}
+
+#[test]
+fn raw_files_are_shown_by_their_preview_unless_paired() {
+    // A 16x8 JPEG, in a TIFF with orientation 6 like the raw files of many
+    // cameras.
+    let mut jpeg = Vec::new();
+    image::DynamicImage::new_rgb8(16, 8)
+        .write_to(&mut io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+        .unwrap();
+    let mut raw = b"II*\0\x08\0\0\0\x03\0".to_vec();
+    let entry = |tag: u16, kind: u16, value: u32| {
+        [
+            &tag.to_le_bytes()[..],
+            &kind.to_le_bytes(),
+            &1u32.to_le_bytes(),
+            &value.to_le_bytes(),
+        ]
+        .concat()
+    };
+    raw.extend(entry(0x0112, 3, 6));
+    raw.extend(entry(0x0201, 4, 50));
+    raw.extend(entry(0x0202, 4, jpeg.len() as u32));
+    raw.extend(0u32.to_le_bytes());
+    raw.extend(&jpeg);
+
+    let gallery = Gallery::new(&[A]);
+    gallery.add("h.dng", &raw);
+    let output = Command::new(env!("CARGO_BIN_EXE_photo2html"))
+        .args(["--image-backend", "native"])
+        .arg("--output-dir")
+        .arg(gallery.output())
+        .arg(gallery.input())
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let dimensions = image::image_dimensions(gallery.output().join("img/h.jpg")).unwrap();
+    assert_eq!(dimensions, (8, 16));
+
+    // The camera's JPEG is shown instead.
+    gallery.add("a.nef", &raw);
+    gallery.run(&[]);
+    // Dated by when it was written, without a capture time.
+    assert_eq!(thumbnails(&gallery.page("index.html")), ["h.jpg", "a.jpg"]);
+}
+
+#[test]
+fn videos_are_shown_by_their_posters() {
+    let gallery = Gallery::new(&[A]);
+    // Created at 2023-08-20 12:00:00 UTC, in seconds since 1904.
+    let created: u32 = 1_692_532_800 + 2_082_844_800;
+    let mut mp4 = b"\0\0\0\x10ftypisom\0\0\0\0\0\0\0\x1cmoov\0\0\0\x14mvhd\0\0\0\0".to_vec();
+    mp4.extend(created.to_be_bytes());
+    mp4.extend(created.to_be_bytes());
+    gallery.add("clip.mp4", &mp4);
+    // Takes the photo as the first frame.
+    let ffmpeg = gallery.dir.path().join("ffmpeg");
+    fs::write(
+        &ffmpeg,
+        format!(
+            "#!/bin/sh\nfor last; do :; done\ncp '{}' \"$last\"\n",
+            gallery.input().join(A).display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&ffmpeg, fs::Permissions::from_mode(0o755)).unwrap();
+    let run = |ffmpeg: &Path| {
+        // Not `gallery.command`, which may choose the none backend.
+        Command::new(env!("CARGO_BIN_EXE_photo2html"))
+            .args(["--image-backend", "native", "--timezone", "+02:00"])
+            .args(["--verbose-html", "--permalinks", "--regenerate", "*"])
+            .arg("--ffmpeg")
+            .arg(ffmpeg)
+            .arg("--output-dir")
+            .arg(gallery.output())
+            .arg(gallery.input())
+            .output()
+            .unwrap()
+    };
+    let output = run(&ffmpeg);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let index = gallery.page("index.html");
+    assert_eq!(thumbnails(&index), ["clip.mp4.jpg", "a.jpg"]);
+    assert!(index.contains("<!-- src: clip.mp4 2023-08-20T14:00:00+02:00 -->"));
+    assert!(index.contains("class=\"video\""));
+    assert!(gallery.output().join("img/clip.mp4").is_file());
+    let permalinks: String = fs::read_dir(gallery.output().join("photo"))
+        .unwrap()
+        .map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap())
+        .collect();
+    assert!(permalinks.contains(
+        "<video src=\"../img/clip.mp4\" poster=\"../img/clip.mp4.jpg\" controls></video>"
+    ));
+
+    let output = run(Path::new("/nonexistent/ffmpeg"));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("skipping"), "{stderr}");
+    assert!(stderr.contains("/nonexistent/ffmpeg"), "{stderr}");
+}
+
+#[test]
+fn pages_are_served_and_reloaded() {
+    let gallery = Gallery::new(&[A]);
+    let mut server = gallery
+        .command(&["--serve", "127.0.0.1:0", "--verbose"])
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = BufReader::new(server.stderr.take().unwrap());
+    let mut next_line = || {
+        let mut line = String::new();
+        assert_ne!(stderr.read_line(&mut line).unwrap(), 0, "exited");
+        line
+    };
+    let url = loop {
+        if let Some((_, url)) = next_line().trim().split_once(" at http://") {
+            break url.trim_end_matches('/').to_owned();
+        }
+    };
+    // Started watching only after serving.
+    while !next_line().contains("watching") {}
+    let request = |head: &str| {
+        let mut stream = TcpStream::connect(&url).unwrap();
+        write!(stream, "GET {head}\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+
+    let index = request("/ HTTP/1.1");
+    assert!(index.starts_with("HTTP/1.1 200 OK\r\n"), "{index}");
+    assert!(index.contains("Content-Type: text/html; charset=utf-8\r\n"));
+    assert!(index.contains("new EventSource("));
+    let css = request("/css/style.css HTTP/1.1\r\nRange: bytes=0-3");
+    assert!(css.starts_with("HTTP/1.1 206 Partial Content\r\n"), "{css}");
+    assert!(css.contains("Content-Length: 4\r\n"));
+    let css =
+        request("/css/style.css HTTP/1.1\r\nIf-Modified-Since: Fri, 01 Jan 2100 00:00:00 GMT");
+    assert!(css.starts_with("HTTP/1.1 304 Not Modified\r\n"), "{css}");
+    assert!(request("/../in/a.jpg HTTP/1.1").starts_with("HTTP/1.1 404"));
+
+    let mut events = TcpStream::connect(&url).unwrap();
+    write!(events, "GET /_photo2html/reload HTTP/1.1\r\n\r\n").unwrap();
+    events
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let mut events = BufReader::new(events);
+    // Past the headers.
+    let mut line = String::new();
+    while events.read_line(&mut line).unwrap() > 2 {
+        line.clear();
+    }
+    gallery.add(B, &fixture(B));
+    // The reload is sent before the summary, so it is there to read by then,
+    // however long the rebuild took.
+    while !next_line().contains("rebuilt") {}
+    line.clear();
+    events.read_line(&mut line).unwrap();
+    server.kill().unwrap();
+    server.wait().unwrap();
+    assert_eq!(line, "data: reload\n");
+}
+
+#[test]
+fn bursts_of_changes_are_rebuilt_once() {
+    let gallery = Gallery::new(&[A]);
+    let mut watcher = gallery
+        .command(&["--watch", "--verbose"])
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = BufReader::new(watcher.stderr.take().unwrap());
+    let mut line = String::new();
+    let mut next_line = |line: &mut String| {
+        line.clear();
+        assert_ne!(stderr.read_line(line).unwrap(), 0, "exited");
+    };
+    while !line.contains("watching") {
+        next_line(&mut line);
+    }
+
+    for name in [B, C, D, E] {
+        gallery.add(name, &fixture(name));
+        thread::sleep(Duration::from_millis(50));
+    }
+    while !line.contains("rebuilt") {
+        next_line(&mut line);
+    }
+    watcher.kill().unwrap();
+    watcher.wait().unwrap();
+    assert!(line.contains("4 new, 0 changed, 0 removed"), "{line}");
+}
+
+#[test]
+fn rebuilds_are_summarized() {
+    let gallery = Gallery::new(&[A, B, C]);
+    let mut watcher = gallery
+        .command(&["--watch", "--verbose"])
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = BufReader::new(watcher.stderr.take().unwrap());
+    let mut line = String::new();
+    let mut next_line = |line: &mut String| {
+        line.clear();
+        assert_ne!(stderr.read_line(line).unwrap(), 0, "exited");
+    };
+    while !line.contains("watching") {
+        next_line(&mut line);
+    }
+
+    gallery.add(A, &fixture(D));
+    next_line(&mut line);
+    while !line.contains("rebuilt") {
+        next_line(&mut line);
+    }
+    assert!(
+        line.contains("0 new, 1 changed, 0 removed, 1 page"),
+        "{line}"
+    );
+
+    fs::remove_file(gallery.input().join(B)).unwrap();
+    next_line(&mut line);
+    while !line.contains("rebuilt") {
+        next_line(&mut line);
+    }
+    assert!(
+        line.contains("0 new, 0 changed, 1 removed, 1 page"),
+        "{line}"
+    );
+    watcher.kill().unwrap();
+    watcher.wait().unwrap();
+}
+
+#[test]
+fn templates_are_reloaded_while_watching() {
+    let gallery = Gallery::new(&[A]);
+    let templates = gallery.dir.path().join("templates");
+    fs::create_dir(&templates).unwrap();
+    let mut watcher = gallery
+        .command(&[
+            "--watch",
+            "--verbose",
+            "--templates",
+            templates.to_str().unwrap(),
+        ])
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = BufReader::new(watcher.stderr.take().unwrap());
+    let mut line = String::new();
+    let mut next_line = |line: &mut String| {
+        line.clear();
+        assert_ne!(stderr.read_line(line).unwrap(), 0, "exited");
+    };
+    // Watched after the inputs.
+    while !line.contains(&format!("watching {}", templates.display())) {
+        next_line(&mut line);
+    }
+
+    fs::write(
+        templates.join("figure.html"),
+        "<div class=\"photo\">{{img}}</div>\n",
+    )
+    .unwrap();
+    while !line.contains("rebuilt") {
+        next_line(&mut line);
+    }
+    watcher.kill().unwrap();
+    watcher.wait().unwrap();
+    assert!(gallery
+        .page("index.html")
+        .contains("<div class=\"photo\"><img src=\"./thumbnail/a.jpg\""));
+}
+
+#[test]
+fn options_are_read_from_the_config_file() {
+    let gallery = Gallery::new(&[A, B]);
+    gallery.add(
+        "photo2html.toml",
+        b"# The gallery\ntitle = \"Holiday\"\nper-page = 1 # a photo each\nexclude-date = [\n    2020-01-01,\n]\n",
+    );
+    gallery.run(&[]);
+    let page = gallery.page("index.html");
+    assert!(page.contains("<title>Holiday</title>"), "{page}");
+    assert_eq!(thumbnails(&page).len(), 1);
+    assert!(gallery.output().join("page_1.html").exists());
+    // Not a photo.
+    assert!(!gallery.output().join("img/photo2html.toml").exists());
+
+    gallery.run(&["--title", "Trip"]);
+    assert!(gallery.page("index.html").contains("<title>Trip</title>"));
+
+    let config = gallery.dir.path().join("other.toml");
+    fs::write(&config, "bogus = 1\n").unwrap();
+    let output = gallery.try_run(&["--config", config.to_str().unwrap()]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("bogus: unknown option"), "{stderr}");
+}
+
+#[test]
+fn galleries_are_generated_through_the_library() {
+    /// Counts the photos instead of writing pages.
+    struct Count(std::cell::Cell<usize>);
+    impl photo2html::Renderer for Count {
+        fn render(
+            &self,
+            _: &photo2html::Options,
+            photos: &[photo2html::Photo],
+        ) -> Result<(), photo2html::Error> {
+            self.0.set(photos.len());
+            Ok(())
+        }
+    }
+
+    let gallery = Gallery::new(&[A, G]);
+    let mut args = vec![
+        PathBuf::from("photo2html"),
+        "--output-dir".into(),
+        gallery.output(),
+    ];
+    if !has_magick() {
+        args.extend(["--image-backend".into(), "none".into()]);
+    }
+    args.push(gallery.input());
+    let options = photo2html::Options::parse_from(args).unwrap();
+    let renderer = Count(Default::default());
+    let photos = photo2html::generate(&options, &renderer).unwrap();
+    assert_eq!(renderer.0.get(), 2);
+    let labels: Vec<&str> = photos.iter().map(|p| p.label()).collect();
+    assert_eq!(labels, [A, G]);
+    assert_eq!(
+        photos[0].zoned_datetime().to_rfc3339(),
+        "2023-08-15T14:22:10+09:00"
+    );
+    assert!(!gallery.output().join("index.html").exists());
+
+    assert!(matches!(
+        photo2html::Options::parse_from(["photo2html", "--no-such-option"]),
+        Err(photo2html::Error::Options(_))
+    ));
+}
+
+#[test]
+fn deploy_is_given_the_changed_files() {
+    let gallery = Gallery::new(&[A, B]);
+    let list = gallery.dir.path().join("deployed.txt");
+    let deploy = format!("cat > '{}'", list.display());
+    gallery.run(&["--deploy", &deploy]);
+    let deployed = fs::read_to_string(&list).unwrap();
+    assert!(deployed.lines().any(|l| l == "index.html"), "{deployed}");
+    assert!(deployed.lines().any(|l| l == format!("img/{A}")));
+
+    fs::remove_file(&list).unwrap();
+    gallery.run(&["--deploy", &deploy]);
+    assert!(!list.exists());
+
+    gallery.add(C, &fixture(C));
+    gallery.run(&["--deploy", &deploy]);
+    let deployed = fs::read_to_string(&list).unwrap();
+    assert!(
+        deployed.lines().any(|l| l == format!("img/{C}")),
+        "{deployed}"
+    );
+    assert!(!deployed.lines().any(|l| l == format!("img/{A}")));
+
+    gallery.add(D, &fixture(D));
+    assert!(!gallery.try_run(&["--deploy", "exit 3"]).status.success());
+    // Which is deployed again next time.
+    gallery.run(&["--deploy", &deploy]);
+    assert!(fs::read_to_string(&list)
+        .unwrap()
+        .contains(&format!("img/{D}")));
+}